@@ -1,92 +1,134 @@
 //! Requests for the authentication endpoint
 
+use crate::api_client::ApiClient;
+use crate::cookie::{find_session_cookie, SessionCookie};
+use crate::error::ApiError;
 use crate::path::auth;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{header::SET_COOKIE, Client, Method, StatusCode};
-use serde::Serialize;
-use serde_json::Value;
+use crate::reqwest::{Client, Method};
+use crate::types::{AuthToken, Username};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a user
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct User {
-    pub username: String,
+    pub username: Username,
     pub password_hash: String,
 }
 
-/// Helper function to extract session ID from the HTTP header
-fn get_session_id(cookie_str: &str) -> Option<String> {
-    for part in cookie_str.split(';') {
-        if part.starts_with("session_id=") {
-            return Some(part["session-id=".len()..].to_string());
+/// Send the renew request with the given auth token, without touching the client's credentials
+pub(crate) async fn renew_auth_token(
+    client: &ApiClient,
+    auth_token: Option<AuthToken>,
+) -> Result<SessionCookie, ApiError> {
+    let url = auth::get_renew_url(client.base_url());
+
+    let (_status, _json, headers) = client
+        .send_with_token(&Method::POST, url, auth_token.as_ref(), None::<()>)
+        .await?;
+
+    // Get the session cookie from the SET_COOKIE headers
+    match (find_session_cookie(&headers), auth_token) {
+        (Some(cookie), _) if cookie.is_deletion() => Err(ApiError::MissingSessionCookie),
+        (Some(cookie), _) => Ok(cookie),
+        // Keep the old auth_token if the server did not send a new one
+        (None, Some(auth_token)) => Ok(SessionCookie::from_auth_token(&auth_token)),
+        (None, None) => Err(ApiError::MissingSessionCookie),
+    }
+}
+
+/// Requests for the authentication endpoint, created with `ApiClient::auth`
+#[derive(Debug, Clone, Copy)]
+pub struct Auth<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> Auth<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to attempt login with provided user credentials
+    ///
+    /// On success the new session ID is stored in the client and used for later requests.
+    pub async fn login(
+        &self,
+        username: &Username,
+        pw: &str,
+    ) -> Result<Option<SessionCookie>, ApiError> {
+        let url = auth::get_login_url(self.client.base_url());
+        let params = User {
+            username: username.clone(),
+            password_hash: pw.to_string(),
+        };
+
+        let (_status, _json, headers) = self
+            .client
+            .send_raw(Method::POST, url, Some(&params))
+            .await?;
+
+        // Get the session cookie from the SET_COOKIE headers
+        let cookie = find_session_cookie(&headers).filter(|cookie| !cookie.is_deletion());
+        if let Some(cookie) = &cookie {
+            self.client
+                .set_credentials(Some(cookie.auth_token()), cookie.expires_at());
         }
+
+        // Return None if the session cookie cannot be extracted
+        Ok(cookie)
+    }
+
+    /// Send request to log out the current user
+    ///
+    /// The session ID stored in the client is cleared. Returns the cookie sent by the server,
+    /// which is normally a deletion cookie (see `SessionCookie::is_deletion`).
+    pub async fn logout(&self) -> Result<Option<SessionCookie>, ApiError> {
+        let url = auth::get_logout_url(self.client.base_url());
+
+        let (_status, _json, headers) = self.client.send_raw(Method::POST, url, None::<()>).await?;
+        self.client.set_credentials(None, None);
+
+        Ok(find_session_cookie(&headers))
+    }
+
+    /// Send request to renew session tokens
+    ///
+    /// The renewed session ID replaces the one stored in the client. If another request is
+    /// already renewing the session, this waits for it and sends a new renewal afterwards.
+    pub async fn renew(&self) -> Result<SessionCookie, ApiError> {
+        self.client.renew_shared(None).await
     }
-    eprintln!("Could not extract session_id");
-    None
 }
 
 /// Send request to attempt login with provided user credentials
 pub async fn user_login(
     client: &Client,
-    username: &str,
+    username: &Username,
     pw: &str,
-) -> (StatusCode, Option<Value>, Option<String>) {
-    let url = auth::get_login_url();
-    let params = User {
-        username: username.to_string(),
-        password_hash: pw.to_string(),
-    };
-
-    let (status, json, headers) =
-        send_request(client, &Method::POST, url, None, Some(&params)).await;
-
-    // Get the session id from the SET_COOKIE header
-    if let Some(cookie) = headers.get(SET_COOKIE).and_then(|cookie| cookie.to_str().ok()) {
-        if let Some(new_session_id) = get_session_id(cookie) {
-            return (status, json, Some(new_session_id));
-        }
-    }
-    // Return None if new_session_id cannot be extracted
-    (status, json, None)
+) -> Result<Option<SessionCookie>, ApiError> {
+    ApiClient::from_http(client, None)?
+        .auth()
+        .login(username, pw)
+        .await
 }
 
-
 /// Send request to log out the current user
 pub async fn user_logout(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>, String) {
-    let url = auth::get_logout_url();
-
-    let (status, json, headers) =
-        send_request(client, &Method::POST, url, Some(session_id), None::<()>).await;
-
-    // Get the session id from the SET_COOKIE header
-    if let Some(cookie) = headers.get(SET_COOKIE).and_then(|cookie| cookie.to_str().ok()) {
-        if let Some(new_session_id) = get_session_id(cookie) {
-            return (status, json, new_session_id);
-        }
-    }
-    // Return old session_id if new_session_id cannot be extracted
-    (status, json, session_id.to_string())
+    auth_token: &AuthToken,
+) -> Result<Option<SessionCookie>, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .auth()
+        .logout()
+        .await
 }
 
-
 /// Send request to renew session tokens
 pub async fn renew_session(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>, String) {
-    let url = auth::get_renew_url();
-
-    let (status, json, headers) =
-        send_request(client, &Method::POST, url, Some(session_id), None::<()>).await;
-
-    // Get the session id from the SET_COOKIE header
-    if let Some(cookie) = headers.get(SET_COOKIE).and_then(|cookie| cookie.to_str().ok()) {
-        if let Some(new_session_id) = get_session_id(cookie) {
-            return (status, json, new_session_id);
-        }
-    }
-    // Return old session_id if new_session_id cannot be extracted
-    (status, json, session_id.to_string())
+    auth_token: &AuthToken,
+) -> Result<SessionCookie, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .auth()
+        .renew()
+        .await
 }
@@ -1,19 +1,21 @@
 //! Requests for the sensor endpoint
 
+use crate::api_client::ApiClient;
+use crate::error::ApiError;
 use crate::path::sensor;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{Client, Method, StatusCode};
-use serde::Serialize;
-use serde_json::Value;
+use crate::requests::send_request::parse_json;
+use crate::reqwest::{Client, Method};
+use crate::types::{AuthToken, SensorId};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a sensor
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Sensor {
     #[serde(rename = "type")]
     pub sensor_type: String,
 }
 
-/// Struct defining a sensor for PATCH
+// Struct defining a sensor for PATCH
 // #[derive(Debug, Serialize)]
 // pub struct PatchSensor {
 //     pub id: String,
@@ -21,78 +23,140 @@ pub struct Sensor {
 //     pub sensor_type: String,
 // }
 
+/// Struct defining a sensor returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorRecord {
+    pub id: SensorId,
+    #[serde(rename = "type")]
+    pub sensor_type: String,
+}
+
+/// Struct defining a list of sensors returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SensorList {
+    pub sensors: Vec<SensorRecord>,
+}
+
+/// Requests for the sensor endpoint, created with `ApiClient::sensors`
+#[derive(Debug, Clone, Copy)]
+pub struct Sensors<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> Sensors<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to create a new sensor
+    pub async fn create(&self, sensor_type: &str) -> Result<SensorRecord, ApiError> {
+        let url = sensor::get_sensor_url(self.client.base_url());
+        let params = Sensor {
+            sensor_type: sensor_type.to_string(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::POST, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all sensors
+    pub async fn list(&self) -> Result<SensorList, ApiError> {
+        let url = sensor::get_sensor_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get a specific sensor according to given ID
+    pub async fn get(&self, id: &SensorId) -> Result<SensorRecord, ApiError> {
+        let url = sensor::get_sensor_id_url(self.client.base_url(), id.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to partially or fully update a sensor
+    pub async fn update(&self, id: &SensorId, sensor_type: &str) -> Result<SensorRecord, ApiError> {
+        let url = sensor::get_sensor_id_url(self.client.base_url(), id.as_str());
+        let params = Sensor {
+            sensor_type: sensor_type.to_string(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::PATCH, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to delete a sensor according to given ID
+    pub async fn delete(&self, id: &SensorId) -> Result<(), ApiError> {
+        let url = sensor::get_sensor_id_url(self.client.base_url(), id.as_str());
+
+        self.client.send(Method::DELETE, url, None::<()>).await?;
+
+        Ok(())
+    }
+}
+
 /// Send request to create a new sensor
 pub async fn create_sensor(
     client: &Client,
-    session_id: &str,
+    auth_token: &AuthToken,
     sensor_type: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = sensor::get_sensor_url();
-    let params = Sensor {
-        sensor_type: sensor_type.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+) -> Result<SensorRecord, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sensors()
+        .create(sensor_type)
+        .await
 }
 
 /// Send request to get all sensors
 pub async fn view_all_sensors(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = sensor::get_sensor_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+) -> Result<SensorList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sensors()
+        .list()
+        .await
 }
 
 /// Send request to get a specific sensor according to given ID
 pub async fn view_sensor_by_id(
     client: &Client,
-    session_id: &str,
-    id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = sensor::get_sensor_id_url(id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SensorId,
+) -> Result<SensorRecord, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sensors()
+        .get(id)
+        .await
 }
 
 /// Send request to partially or fully update a sensor
 pub async fn update_sensor(
     client: &Client,
-    session_id: &str,
-    id: &str,
+    auth_token: &AuthToken,
+    id: &SensorId,
     sensor_type: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = sensor::get_sensor_id_url(id);
-    let params = Sensor {
-        sensor_type: sensor_type.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::PATCH, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+) -> Result<SensorRecord, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sensors()
+        .update(id, sensor_type)
+        .await
 }
 
 /// Send request to delete a sensor according to given ID
 pub async fn delete_sensor(
     client: &Client,
-    session_id: &str,
-    id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = sensor::get_sensor_id_url(id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::DELETE, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SensorId,
+) -> Result<(), ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sensors()
+        .delete(id)
+        .await
 }
@@ -2,109 +2,182 @@
 
 //session id disabled for temporary solution
 
+use crate::api_client::ApiClient;
+use crate::error::ApiError;
 use crate::path::session;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{Client, Method, StatusCode};
-use serde::Serialize;
-use serde_json::Value;
+use crate::requests::send_request::parse_json;
+use crate::reqwest::{Client, Method};
+use crate::types::{AuthToken, SessionId, Username};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a session
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Session {
     //pub id: String,
-    pub username: String,
+    pub username: Username,
 }
 
-/// Struct defining a session for PATCH
+// Struct defining a session for PATCH
 // #[derive(Debug, Serialize)]
 // pub struct PatchSession {
 //     pub id: String,
 //     pub username: String,
 // }
 
+/// Struct defining a session returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub id: SessionId,
+    pub username: Username,
+}
+
+/// Struct defining a list of sessions returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionList {
+    pub sessions: Vec<SessionRecord>,
+}
+
+/// Requests for the session endpoint, created with `ApiClient::sessions`
+#[derive(Debug, Clone, Copy)]
+pub struct Sessions<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> Sessions<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to create a new session
+    pub async fn create(&self, username: &Username) -> Result<SessionRecord, ApiError> {
+        let url = session::get_sessions_url(self.client.base_url());
+        let params = Session {
+            //id: id.to_string(),
+            username: username.clone(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::POST, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all session
+    pub async fn list(&self) -> Result<SessionList, ApiError> {
+        let url = session::get_sessions_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all sessions by user
+    pub async fn list_by_user(&self, username: &Username) -> Result<SessionList, ApiError> {
+        let url =
+            session::get_sessions_subpath_url(self.client.base_url(), "user", username.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get a specific session by ID
+    pub async fn get(&self, id: &SessionId) -> Result<SessionRecord, ApiError> {
+        let url = session::get_sessions_subpath_url(self.client.base_url(), "id", id.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to partially or fully update a session
+    pub async fn update(
+        &self,
+        id: &SessionId,
+        username: &Username,
+    ) -> Result<SessionRecord, ApiError> {
+        let url = session::get_sessions_exp_url(self.client.base_url(), id.as_str());
+        let params = Session {
+            //id: id.to_string(),
+            username: username.clone(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::PATCH, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to delete a session by ID
+    pub async fn delete(&self, id: &SessionId) -> Result<(), ApiError> {
+        let url = session::get_sessions_exp_url(self.client.base_url(), id.as_str());
+
+        self.client.send(Method::DELETE, url, None::<()>).await?;
+
+        Ok(())
+    }
+}
+
 /// Send request to create a new session
 pub async fn create_session(
     client: &Client,
     //id: &str,
-    username: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_url();
-    let params = Session {
-        //id: id.to_string(),
-        username: username.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, None, Some(&params)).await;
-
-    (status, json)
+    username: &Username,
+) -> Result<SessionRecord, ApiError> {
+    ApiClient::from_http(client, None)?
+        .sessions()
+        .create(username)
+        .await
 }
 
 /// Send request to get all session
-pub async fn view_all_sessions(
-    client: &Client,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, None, None::<()>).await;
-
-    (status, json)
+pub async fn view_all_sessions(client: &Client) -> Result<SessionList, ApiError> {
+    ApiClient::from_http(client, None)?.sessions().list().await
 }
 
 /// Send request to get all sessions by user
 pub async fn view_sessions_by_user(
     client: &Client,
-    username: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_subpath_url("user", username);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, None, None::<()>).await;
-
-    (status, json)
+    username: &Username,
+) -> Result<SessionList, ApiError> {
+    ApiClient::from_http(client, None)?
+        .sessions()
+        .list_by_user(username)
+        .await
 }
 
 /// Send request to get a specific session by ID
 pub async fn view_session_by_id(
     client: &Client,
-    id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_subpath_url("id", id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionId,
+) -> Result<SessionRecord, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sessions()
+        .get(id)
+        .await
 }
 
 /// Send request to partially or fully update a session
 pub async fn update_session(
     client: &Client,
-    id: &str,
-    username: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_exp_url(id);
-    let params = Session {
-        //id: id.to_string(),
-        username: username.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::PATCH, url, Some(id), Some(&params)).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionId,
+    username: &Username,
+) -> Result<SessionRecord, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sessions()
+        .update(id, username)
+        .await
 }
 
 /// Send request to delete a session by ID
 pub async fn delete_session(
     client: &Client,
-    id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_sessions_exp_url(id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::DELETE, url, Some(id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionId,
+) -> Result<(), ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .sessions()
+        .delete(id)
+        .await
 }
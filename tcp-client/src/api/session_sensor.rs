@@ -1,114 +1,210 @@
 //! Requests for the session sensor endpoint
 
+use crate::api_client::ApiClient;
+use crate::error::ApiError;
 use crate::path::session;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{Client, Method, StatusCode};
-use serde::Serialize;
-use serde_json::Value;
+use crate::requests::send_request::parse_json;
+use crate::reqwest::{Client, Method};
+use crate::types::{AuthToken, SensorId, SessionId, SessionSensorId};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a session sensor
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SessionSensor {
-    pub session_id: String,
-    pub sensor_id: String,
+    pub session_id: SessionId,
+    pub sensor_id: SensorId,
 }
 
 /// Struct defining a session sensor for PATCH
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PatchSessionSensor {
-    pub id: String,
-    pub session_id: String,
-    pub sensor_id: String,
+    pub id: SessionSensorId,
+    pub session_id: SessionId,
+    pub sensor_id: SensorId,
+}
+
+/// Struct defining a session sensor link returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionSensorLink {
+    pub id: SessionSensorId,
+    pub session_id: SessionId,
+    pub sensor_id: SensorId,
+}
+
+/// Struct defining a list of session sensor links returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SessionSensorLinkList {
+    pub links: Vec<SessionSensorLink>,
+}
+
+/// Requests for the session sensor endpoint, created with `ApiClient::session_sensors`
+#[derive(Debug, Clone, Copy)]
+pub struct SessionSensors<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> SessionSensors<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to link a new sensor to a session
+    pub async fn create(
+        &self,
+        session_id: &SessionId,
+        sensor_id: &SensorId,
+    ) -> Result<SessionSensorLink, ApiError> {
+        let url = session::get_session_sensors_url(self.client.base_url());
+        let params = SessionSensor {
+            session_id: session_id.clone(),
+            sensor_id: sensor_id.clone(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::POST, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all session sensor linkages
+    pub async fn list(&self) -> Result<SessionSensorLinkList, ApiError> {
+        let url = session::get_session_sensors_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all sensors linked to a specific session
+    pub async fn list_by_session(
+        &self,
+        session_id: &SessionId,
+    ) -> Result<SessionSensorLinkList, ApiError> {
+        let url = session::get_session_sensors_subpath_url(
+            self.client.base_url(),
+            "session",
+            session_id.as_str(),
+        );
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get a specific session sensor linkage by sensor ID
+    pub async fn get_by_sensor(&self, sensor_id: &SensorId) -> Result<SessionSensorLink, ApiError> {
+        let url = session::get_session_sensors_subpath_url(
+            self.client.base_url(),
+            "session-sensor",
+            sensor_id.as_str(),
+        );
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to partially or fully udpate a session sensor link
+    pub async fn update(
+        &self,
+        session_sensor_id: &SessionSensorId,
+        session_id: &SessionId,
+        sensor_id: &SensorId,
+    ) -> Result<SessionSensorLink, ApiError> {
+        let url =
+            session::get_session_sensors_id_url(self.client.base_url(), session_sensor_id.as_str());
+        let params = PatchSessionSensor {
+            id: session_sensor_id.clone(),
+            session_id: session_id.clone(),
+            sensor_id: sensor_id.clone(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::PATCH, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to delete a session sensor linkage by ID
+    pub async fn delete(&self, session_sensor_id: &SessionSensorId) -> Result<(), ApiError> {
+        let url =
+            session::get_session_sensors_id_url(self.client.base_url(), session_sensor_id.as_str());
+
+        self.client.send(Method::DELETE, url, None::<()>).await?;
+
+        Ok(())
+    }
 }
 
 /// Send request to link a new sensor to a session
 pub async fn create_session_sensor(
     client: &Client,
-    session_id: &str,
-    sensor_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_url();
-    let params = SessionSensor {
-        session_id: session_id.to_string(),
-        sensor_id: sensor_id.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .create(session_id, sensor_id)
+        .await
 }
 
 /// Send request to get all session sensor linkages
 pub async fn view_all_sensor_sessions(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+) -> Result<SessionSensorLinkList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .list()
+        .await
 }
 
 /// Send request to get all sensors linked to a specific session
 pub async fn view_sensors_by_session_id(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_subpath_url("session", session_id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+) -> Result<SessionSensorLinkList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .list_by_session(session_id)
+        .await
 }
 
 /// Send request to get a specific session sensor linkage by sensor ID
 pub async fn view_session_sensor_by_sensor_id(
     client: &Client,
-    session_id: &str,
-    sensor_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_subpath_url("session-sensor", sensor_id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .get_by_sensor(sensor_id)
+        .await
 }
 
 /// Send request to partially or fully udpate a session sensor link
 pub async fn update_sensor_session(
     client: &Client,
-    session_sensor_id: &str,
-    session_id: &str,
-    sensor_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_id_url(session_sensor_id);
-    let params = PatchSessionSensor {
-        id: session_sensor_id.to_string(),
-        session_id: session_id.to_string(),
-        sensor_id: sensor_id.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::PATCH, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_sensor_id: &SessionSensorId,
+    session_id: &SessionId,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .update(session_sensor_id, session_id, sensor_id)
+        .await
 }
 
 /// Send request to delete a session sensor linkage by ID
 pub async fn delete_sensor_session(
     client: &Client,
-    session_id: &str,
-    sensor_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = session::get_session_sensors_id_url(sensor_id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::DELETE, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_sensor_id: &SessionSensorId,
+) -> Result<(), ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .session_sensors()
+        .delete(session_sensor_id)
+        .await
 }
@@ -1,162 +1,326 @@
 //! Requests for the session sensor data endpoint
 
+use crate::api_client::ApiClient;
+use crate::batch::{BatchError, BatchReport, DatapointKey};
+use crate::error::ApiError;
 use crate::path::datapoint;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{Client, Method, StatusCode};
-use serde::Serialize;
-use serde_json::Value;
+use crate::requests::send_request::parse_json;
+use crate::reqwest::{Client, Method};
+use crate::timestamp::Timestamp;
+use crate::types::{AuthToken, SessionId, SessionSensorId};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a batch of datapoints
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Batch {
     pub datapoints: Vec<SessionSensorData>,
 }
 
 /// Struct defining a single datapoint
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SessionSensorData {
-    pub id: String,
-    pub datetime: String,
+    pub id: SessionSensorId,
+    pub datetime: Timestamp,
     pub data_blob: String,
 }
 
+/// Datapoint returned by the server, which has the same fields as the one sent
+pub type Datapoint = SessionSensorData;
+
+/// Struct defining a list of datapoints returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DatapointList {
+    pub datapoints: Vec<Datapoint>,
+}
+
+/// Requests for the session sensor data endpoint, created with `ApiClient::datapoints`
+#[derive(Debug, Clone, Copy)]
+pub struct Datapoints<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> Datapoints<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to create a new datapoint
+    pub async fn create(
+        &self,
+        id: &SessionSensorId,
+        datetime: Timestamp,
+        data_blob: &str,
+    ) -> Result<Datapoint, ApiError> {
+        let url = datapoint::get_datapoint_url(self.client.base_url());
+        let params = SessionSensorData {
+            id: id.clone(),
+            datetime,
+            data_blob: data_blob.to_string(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::POST, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send requests to batch create new datapoints
+    ///
+    /// The datapoints are split into chunks within the client's `BatchLimits`, which are
+    /// uploaded concurrently. Fails with a `BatchError` telling which datapoints were created
+    /// if any chunk failed.
+    pub async fn batch_create(
+        &self,
+        datapoints: Vec<SessionSensorData>,
+    ) -> Result<BatchReport, BatchError> {
+        let limits = self.client.batch_limits();
+        let chunks = limits.split(datapoints);
+
+        let mut report = BatchReport::default();
+        let mut results = stream::iter(chunks)
+            .map(|chunk| async move {
+                // Keep only the keys for the report and move the chunk into the request
+                let keys = chunk.iter().map(DatapointKey::from).collect();
+                let result = self.send_batch(chunk).await;
+                (keys, result)
+            })
+            .buffered(limits.max_concurrency.max(1));
+        while let Some((keys, result)) = results.next().await {
+            report.add(keys, result);
+        }
+        report.into_result()
+    }
+
+    /// Send request to batch create new datapoints, as a single request
+    pub(crate) async fn send_batch(
+        &self,
+        datapoints: Vec<SessionSensorData>,
+    ) -> Result<(), ApiError> {
+        let url = datapoint::get_batch_url(self.client.base_url());
+        let params = Batch { datapoints };
+
+        self.client.send(Method::POST, url, Some(&params)).await?;
+
+        Ok(())
+    }
+
+    /// Send request to get all datapoints
+    pub async fn list(&self) -> Result<DatapointList, ApiError> {
+        let url = datapoint::get_datapoint_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all datapoints linked to a given session
+    pub async fn list_by_session(&self, session_id: &SessionId) -> Result<DatapointList, ApiError> {
+        let url = datapoint::get_datapoint_subpath_url(
+            self.client.base_url(),
+            "session",
+            session_id.as_str(),
+        );
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all datapoints by session sensor ID
+    pub async fn list_by_session_sensor(
+        &self,
+        id: &SessionSensorId,
+    ) -> Result<DatapointList, ApiError> {
+        let url = datapoint::get_datapoint_subpath_url(self.client.base_url(), "id", id.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get a specific datapoint
+    pub async fn get(
+        &self,
+        id: &SessionSensorId,
+        datetime: Timestamp,
+    ) -> Result<Datapoint, ApiError> {
+        let url = datapoint::get_datapoint_subpath_url(
+            self.client.base_url(),
+            id.as_str(),
+            &datetime.to_string(),
+        );
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all datapoints of a session after specific datetime
+    pub async fn list_after(
+        &self,
+        session_id: &SessionId,
+        datetime: Timestamp,
+    ) -> Result<DatapointList, ApiError> {
+        let url = datapoint::get_datapoint_datetime_url(
+            self.client.base_url(),
+            session_id.as_str(),
+            &datetime.to_string(),
+        );
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to partially or fully udpate a specific datapoint
+    pub async fn update(
+        &self,
+        id: &SessionSensorId,
+        datetime: Timestamp,
+        data_blob: &str,
+    ) -> Result<Datapoint, ApiError> {
+        let url = datapoint::get_datapoint_subpath_url(
+            self.client.base_url(),
+            id.as_str(),
+            &datetime.to_string(),
+        );
+        let params = SessionSensorData {
+            id: id.clone(),
+            datetime,
+            data_blob: data_blob.to_string(),
+        };
+
+        let (_status, json, _headers) = self.client.send(Method::PATCH, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to delete a specific datapoint
+    pub async fn delete(&self, id: &SessionSensorId, datetime: Timestamp) -> Result<(), ApiError> {
+        let url = datapoint::get_datapoint_subpath_url(
+            self.client.base_url(),
+            id.as_str(),
+            &datetime.to_string(),
+        );
+
+        self.client.send(Method::DELETE, url, None::<()>).await?;
+
+        Ok(())
+    }
+}
+
 /// Send request to create a new datapoint
 pub async fn create_datapoint(
     client: &Client,
-    session_id: &str,
-    id: &str,
-    datetime: &str,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
     data_blob: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_url();
-    let params = SessionSensorData {
-        id: id.to_string(),
-        datetime: datetime.to_string(),
-        data_blob: data_blob.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+) -> Result<Datapoint, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .create(id, datetime, data_blob)
+        .await
 }
 
-/// Send request to batch create new datapoints
+/// Send requests to batch create new datapoints, split into chunks the server accepts
 pub async fn batch_create_datapoint(
     client: &Client,
-    session_id: &str,
+    auth_token: &AuthToken,
     datapoints: Vec<SessionSensorData>,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_batch_url();
-    let params = Batch { datapoints };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+) -> Result<BatchReport, BatchError> {
+    ApiClient::from_http(client, Some(auth_token))
+        .map_err(|e| BatchError::unsent(&datapoints, e))?
+        .datapoints()
+        .batch_create(datapoints)
+        .await
 }
 
 /// Send request to get all datapoints
 pub async fn view_all_datapoints(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+) -> Result<DatapointList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .list()
+        .await
 }
 
 /// Send request to get all datapoints linked to a given session
 pub async fn view_datapoints_by_session_id(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_subpath_url("session", session_id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+) -> Result<DatapointList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .list_by_session(session_id)
+        .await
 }
 
 /// Send request to get all datapoints by session sensor ID
 pub async fn view_datapoints_by_session_sensor(
     client: &Client,
-    session_id: &str,
-    id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_subpath_url("id", id);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+) -> Result<DatapointList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .list_by_session_sensor(id)
+        .await
 }
 
 /// Send request to get a specific datapoint
 pub async fn view_datapoints_by_id_datetime(
     client: &Client,
-    session_id: &str,
-    id: &str,
-    datetime: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_subpath_url(id, datetime);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+) -> Result<Datapoint, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .get(id, datetime)
+        .await
 }
 
 /// Send request to get all datapoints after specific datetime
 pub async fn view_all_datapoints_by_id_datetime(
     client: &Client,
-    session_id: &str,
-    datetime: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_datetime_url(session_id, datetime);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+    datetime: Timestamp,
+) -> Result<DatapointList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .list_after(session_id, datetime)
+        .await
 }
 
 /// Send request to partially or fully udpate a specific datapoint
 pub async fn update_datapoint(
     client: &Client,
-    session_id: &str,
-    id: &str,
-    datetime: &str,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
     data_blob: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_subpath_url(id, datetime);
-    let params = SessionSensorData {
-        id: id.to_string(),
-        datetime: datetime.to_string(),
-        data_blob: data_blob.to_string(),
-    };
-
-    let (status, json, _headers) =
-        send_request(client, &Method::PATCH, url, Some(session_id), Some(&params)).await;
-
-    (status, json)
+) -> Result<Datapoint, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .update(id, datetime, data_blob)
+        .await
 }
 
 /// Send request to delete a specific datapoint
 pub async fn delete_datapoint(
     client: &Client,
-    session_id: &str,
-    id: &str,
-    datetime: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = datapoint::get_datapoint_subpath_url(id, datetime);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::DELETE, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+) -> Result<(), ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .datapoints()
+        .delete(id, datetime)
+        .await
 }
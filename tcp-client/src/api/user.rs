@@ -1,104 +1,167 @@
 //! Requests for the user endpoint
 
+use crate::api_client::ApiClient;
+use crate::error::ApiError;
 use crate::path::user;
-use crate::requests::send_request::send_request;
-use reqwest_wasm::{Client, Method, StatusCode};
-use serde_json::Value;
-use serde::Serialize;
+use crate::requests::send_request::parse_json;
+use crate::reqwest::{Client, Method};
+use crate::types::{AuthToken, Username};
+use serde::{Deserialize, Serialize};
 
 /// Struct defining a user
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct User {
-    pub username: String,
+    pub username: Username,
     pub password_hash: String,
 }
 
-/// Send request to create a new user
-pub async fn create_user(
-    client: &Client,
-    username: &str,
-    pw: &str,
-) -> (StatusCode, Option<Value>) {
-    let params = User {
-        username: username.to_string(),
-        password_hash: pw.to_string(),
-    };
+/// Struct defining a user returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub username: Username,
+}
+
+/// Struct defining a list of users returned by the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserList {
+    pub users: Vec<UserInfo>,
+}
+
+/// Requests for the user endpoint, created with `ApiClient::users`
+#[derive(Debug, Clone, Copy)]
+pub struct Users<'a> {
+    client: &'a ApiClient,
+}
+
+impl<'a> Users<'a> {
+    pub(crate) fn new(client: &'a ApiClient) -> Self {
+        Self { client }
+    }
+
+    /// Send request to create a new user
+    pub async fn create(&self, username: &Username, pw: &str) -> Result<UserInfo, ApiError> {
+        let params = User {
+            username: username.clone(),
+            password_hash: pw.to_string(),
+        };
+
+        let url = user::get_user_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::POST, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get all users
+    pub async fn list(&self) -> Result<UserList, ApiError> {
+        let url = user::get_user_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get user currently loggged in
+    pub async fn profile(&self) -> Result<UserInfo, ApiError> {
+        let url = user::get_profile_url(self.client.base_url());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to get a specific user by username
+    pub async fn get(&self, username: &Username) -> Result<UserInfo, ApiError> {
+        let url = user::get_username_url(self.client.base_url(), username.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::GET, url, None::<()>).await?;
 
-    let url = user::get_user_url();
+        parse_json(json)
+    }
 
-    let (status, json, _headers) =
-        send_request(client, &Method::POST, url, None, Some(&params)).await;
+    /// Send request to partially or fully update a user
+    pub async fn update(&self, username: &Username, pw: &str) -> Result<UserInfo, ApiError> {
+        let params = User {
+            username: username.clone(),
+            password_hash: pw.to_string(),
+        };
 
-    (status, json)
+        let url = user::get_username_url(self.client.base_url(), username.as_str());
+
+        let (_status, json, _headers) = self.client.send(Method::PATCH, url, Some(&params)).await?;
+
+        parse_json(json)
+    }
+
+    /// Send request to delete a user by username
+    pub async fn delete(&self, username: &Username) -> Result<(), ApiError> {
+        let url = user::get_username_url(self.client.base_url(), username.as_str());
+
+        self.client.send(Method::DELETE, url, None::<()>).await?;
+
+        Ok(())
+    }
 }
 
-/// Send request to get all users
-pub async fn view_all_users(
+/// Send request to create a new user
+pub async fn create_user(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = user::get_user_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
+    username: &Username,
+    pw: &str,
+) -> Result<UserInfo, ApiError> {
+    ApiClient::from_http(client, None)?
+        .users()
+        .create(username, pw)
+        .await
+}
 
-    (status, json)
+/// Send request to get all users
+pub async fn view_all_users(client: &Client, auth_token: &AuthToken) -> Result<UserList, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .users()
+        .list()
+        .await
 }
 
 /// Send request to get user currently loggged in
 pub async fn view_user_profile(
     client: &Client,
-    session_id: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = user::get_profile_url();
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, Some(session_id), None::<()>).await;
-
-    (status, json)
+    auth_token: &AuthToken,
+) -> Result<UserInfo, ApiError> {
+    ApiClient::from_http(client, Some(auth_token))?
+        .users()
+        .profile()
+        .await
 }
 
 /// Send request to get a specific user by username
 pub async fn view_user_by_username(
     client: &Client,
-    username: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = user::get_username_url(username);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::GET, url, None, None::<()>).await;
-
-    (status, json)
+    username: &Username,
+) -> Result<UserInfo, ApiError> {
+    ApiClient::from_http(client, None)?
+        .users()
+        .get(username)
+        .await
 }
 
 /// Send request to partially or fully update a user
 pub async fn update_user(
     client: &Client,
-    username: &str,
+    username: &Username,
     pw: &str,
-) -> (StatusCode, Option<Value>) {
-    let params = User {
-        username: username.to_string(),
-        password_hash: pw.to_string(),
-    };
-
-    let url = user::get_username_url(username);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::PATCH, url, None, Some(&params)).await;
-
-    (status, json)
+) -> Result<UserInfo, ApiError> {
+    ApiClient::from_http(client, None)?
+        .users()
+        .update(username, pw)
+        .await
 }
 
 /// Send request to delete a user by username
-pub async fn delete_user(
-    client: &Client,
-    username: &str,
-) -> (StatusCode, Option<Value>) {
-    let url = user::get_username_url(username);
-
-    let (status, json, _headers) =
-        send_request(client, &Method::DELETE, url, None, None::<()>).await;
-
-    (status, json)
+pub async fn delete_user(client: &Client, username: &Username) -> Result<(), ApiError> {
+    ApiClient::from_http(client, None)?
+        .users()
+        .delete(username)
+        .await
 }
@@ -0,0 +1,721 @@
+//! Client that owns the HTTP connection pool, base URL and credentials used by every request
+
+use crate::api::auth::renew_auth_token;
+use crate::api::{
+    auth::Auth, sensor::Sensors, session::Sessions, session_sensor::SessionSensors,
+    session_sensor_data::Datapoints, user::Users,
+};
+use crate::batch::BatchLimits;
+use crate::config::{self, ClientConfig, ConfigError};
+use crate::cookie::SessionCookie;
+use crate::error::ApiError;
+use crate::metrics::{self, MeteredTransport, Metrics};
+use crate::path::{BaseUrl, Route};
+use crate::requests::cancel::CancelHandle;
+use crate::requests::limits::{Priority, RateLimiter, RateLimits, RouteGroup};
+use crate::requests::renewal::SessionState;
+use crate::requests::retry::{self, RetryPolicy, IDEMPOTENCY_KEY_HEADER};
+use crate::requests::send_request::send_request;
+use crate::requests::timeout::{with_timeout, TimeoutKind, Timeouts};
+use crate::requests::transport::{ReqwestTransport, Transport};
+use crate::reqwest::{
+    header::{HeaderMap, HeaderValue, USER_AGENT},
+    Client, Method, StatusCode,
+};
+use crate::session_store::{MemorySessionStore, SessionStore};
+use crate::types::AuthToken;
+use futures::future::{self, Either};
+use serde::Serialize;
+use serde_json::Value;
+use std::pin::pin;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::{field, Instrument};
+use web_time::{Instant, SystemTime};
+
+/// Default time before the auth token expires at which it is renewed
+const DEFAULT_RENEW_BEFORE: Duration = Duration::from_secs(60);
+
+/// Time an unused pooled connection is kept open with the `native` backend
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// Interval of TCP keep-alive probes on pooled connections with the `native` backend
+#[cfg(all(feature = "native", not(target_arch = "wasm32")))]
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// Handle to the server API
+///
+/// Cloning is cheap: all clones share the same connection pool and auth token.
+///
+/// Unless disabled with `ApiClientBuilder::auto_renew`, the client renews the login session
+/// shortly before the auth token expires, and on a `401 Unauthorized` response renews once and
+/// replays the request.
+///
+/// Failed requests are retried according to the client's `RetryPolicy` and bounded by its
+/// `Timeouts`. Both can be replaced for individual calls with `with_retry_policy` and
+/// `with_timeouts`, and calls can be cancelled with `with_cancel_handle`.
+///
+/// Requests wait for their turn under the client's `RateLimits`, with calls made through a
+/// `with_priority(Priority::Background)` handle let through after the interactive ones.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    inner: Arc<Inner>,
+    overrides: Overrides,
+}
+
+/// Settings that replace the client-wide ones for calls made through one handle
+#[derive(Debug, Clone, Default)]
+struct Overrides {
+    retry_policy: Option<Arc<RetryPolicy>>,
+    timeouts: Option<Timeouts>,
+    cancel_handle: Option<CancelHandle>,
+    priority: Option<Priority>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    transport: Arc<dyn Transport>,
+    /// HTTP client of the transport, unless a custom transport is used
+    http: Option<Client>,
+    metrics: Arc<Metrics>,
+    limiter: Arc<RateLimiter>,
+    base_url: BaseUrl,
+    user_agent: HeaderValue,
+    session: SessionState,
+    retry_policy: Arc<RetryPolicy>,
+    timeouts: Timeouts,
+    batch_limits: BatchLimits,
+    auto_renew: bool,
+    renew_before: Duration,
+}
+
+/// Builder for an `ApiClient`
+#[derive(Debug)]
+pub struct ApiClientBuilder {
+    base_url: Option<String>,
+    user_agent: Option<String>,
+    http: Option<Client>,
+    transport: Option<Arc<dyn Transport>>,
+    metrics: Option<Arc<Metrics>>,
+    rate_limits: RateLimits,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    auth_token: Option<AuthToken>,
+    auth_token_expires_at: Option<SystemTime>,
+    session_store: Option<Arc<dyn SessionStore>>,
+    retry_policy: RetryPolicy,
+    timeouts: Timeouts,
+    batch_limits: BatchLimits,
+    auto_renew: bool,
+    renew_before: Duration,
+}
+
+impl Default for ApiClientBuilder {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            user_agent: None,
+            http: None,
+            transport: None,
+            metrics: None,
+            rate_limits: RateLimits::default(),
+            rate_limiter: None,
+            auth_token: None,
+            auth_token_expires_at: None,
+            session_store: None,
+            retry_policy: RetryPolicy::default(),
+            timeouts: Timeouts::default(),
+            batch_limits: BatchLimits::default(),
+            auto_renew: true,
+            renew_before: DEFAULT_RENEW_BEFORE,
+        }
+    }
+}
+
+impl ApiClientBuilder {
+    /// Apply the base URL, user agent, timeouts, retry policy, rate limits and batch limits of
+    /// a `ClientConfig`
+    pub fn config(mut self, config: &ClientConfig) -> Self {
+        self.base_url = Some(config.base_url.to_string());
+        self.user_agent = Some(config.user_agent.clone());
+        self.timeouts = config.timeouts;
+        self.retry_policy = config.retry_policy.clone();
+        self.rate_limits = config.limits.clone();
+        self.batch_limits = config.batch;
+        self
+    }
+
+    /// Set the URL all requests are sent to, e.g. `http://127.0.0.1:80`
+    ///
+    /// Defaults to the base URL of `ClientConfig::load`, which is read from the environment
+    /// once per process.
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Set the `User-Agent` header sent with every request (`tcp-client/<version>` by default)
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Use an existing HTTP client instead of creating a new one
+    pub fn http_client(mut self, http: Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Send requests through a custom transport instead of over HTTP
+    ///
+    /// This replaces the HTTP client, so the connect timeout and `http_client` do not apply.
+    /// Tests use it with `FakeTransport` to check requests without a server.
+    pub fn transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Count requests in this registry instead of `metrics::global()`
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Set the request rates and requests in flight allowed per route group (unlimited by
+    /// default)
+    pub fn rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = rate_limits;
+        self
+    }
+
+    /// Share a rate limiter with other clients, replacing `rate_limits`
+    pub fn rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    /// Start with an auth token from a previous login
+    pub fn auth_token(mut self, auth_token: AuthToken) -> Self {
+        self.auth_token = Some(auth_token);
+        self
+    }
+
+    /// Set when the auth token passed to `auth_token` expires
+    pub fn auth_token_expires_at(mut self, expires_at: SystemTime) -> Self {
+        self.auth_token_expires_at = Some(expires_at);
+        self
+    }
+
+    /// Set where the auth token is saved after login and renewal
+    ///
+    /// The client starts with the session saved in the store, unless `auth_token` is also set.
+    /// Defaults to a `MemorySessionStore`, so nothing outlives the client.
+    pub fn session_store(mut self, session_store: Arc<dyn SessionStore>) -> Self {
+        self.session_store = Some(session_store);
+        self
+    }
+
+    /// Set how failed requests are retried (see `RetryPolicy::default`)
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set the connect, read and total timeouts (see `Timeouts::default`)
+    ///
+    /// The connect timeout only applies if the client creates the HTTP client itself, i.e.
+    /// when `http_client` is not used.
+    pub fn timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Set how `Datapoints::batch_create` splits and uploads large batches (see
+    /// `BatchLimits::default`)
+    pub fn batch_limits(mut self, batch_limits: BatchLimits) -> Self {
+        self.batch_limits = batch_limits;
+        self
+    }
+
+    /// Enable or disable automatic session renewal (enabled by default)
+    pub fn auto_renew(mut self, auto_renew: bool) -> Self {
+        self.auto_renew = auto_renew;
+        self
+    }
+
+    /// Set how long before the auth token expires it is renewed (60 seconds by default)
+    pub fn renew_before(mut self, renew_before: Duration) -> Self {
+        self.renew_before = renew_before;
+        self
+    }
+
+    /// Build the client
+    ///
+    /// # Panics
+    ///
+    /// Panics if the base URL or user agent is invalid, or no base URL is set and the
+    /// environment config cannot be loaded; use `try_build` to handle these cases.
+    pub fn build(self) -> ApiClient {
+        self.try_build().unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Build the client, failing if the base URL is not a valid `http` or `https` URL or the
+    /// user agent is not a valid header value
+    pub fn try_build(self) -> Result<ApiClient, ConfigError> {
+        let base_url = match self.base_url {
+            Some(base_url) => BaseUrl::parse(&base_url).map_err(ConfigError::BaseUrl)?,
+            None => ClientConfig::from_environment()?.base_url,
+        };
+        let user_agent = self.user_agent.unwrap_or_else(config::default_user_agent);
+        let user_agent =
+            HeaderValue::from_str(&user_agent).map_err(|_| ConfigError::InvalidValue {
+                origin: "ApiClientBuilder".to_string(),
+                key: "user_agent".to_string(),
+                message: format!(
+                    "`{}` contains characters not allowed in a header",
+                    user_agent
+                ),
+            })?;
+        let session_store = self
+            .session_store
+            .unwrap_or_else(|| Arc::new(MemorySessionStore::new()));
+
+        let (transport, http): (Arc<dyn Transport>, _) = match self.transport {
+            Some(transport) => (transport, None),
+            None => {
+                let http = self.http.unwrap_or_else(|| new_http_client(&self.timeouts));
+                (Arc::new(ReqwestTransport::new(http.clone())), Some(http))
+            }
+        };
+        let metrics = self.metrics.unwrap_or_else(|| metrics::global().clone());
+        let transport = Arc::new(MeteredTransport::new(
+            transport,
+            base_url.clone(),
+            metrics.clone(),
+        ));
+
+        let limiter = self
+            .rate_limiter
+            .unwrap_or_else(|| Arc::new(RateLimiter::new(self.rate_limits)));
+
+        Ok(ApiClient {
+            inner: Arc::new(Inner {
+                transport,
+                http,
+                metrics,
+                limiter,
+                base_url,
+                user_agent,
+                session: SessionState::new(
+                    session_store,
+                    self.auth_token,
+                    self.auth_token_expires_at,
+                ),
+                retry_policy: Arc::new(self.retry_policy),
+                timeouts: self.timeouts,
+                batch_limits: self.batch_limits,
+                auto_renew: self.auto_renew,
+                renew_before: self.renew_before,
+            }),
+            overrides: Overrides::default(),
+        })
+    }
+}
+
+impl ApiClient {
+    /// Create a builder for configuring a new client
+    pub fn builder() -> ApiClientBuilder {
+        ApiClientBuilder::default()
+    }
+
+    /// Create a client using the base URL from the environment
+    pub fn new() -> Self {
+        Self::builder().build()
+    }
+
+    /// Create a client with the settings of a `ClientConfig`
+    pub fn from_config(config: &ClientConfig) -> Self {
+        Self::builder().config(config).build()
+    }
+
+    /// Wrap an existing HTTP client, used by the free endpoint functions
+    ///
+    /// The other settings come from the environment config, with one rate limiter shared by
+    /// all calls. The caller owns the auth token, so the session is never renewed behind its
+    /// back.
+    pub(crate) fn from_http(
+        http: &Client,
+        auth_token: Option<&AuthToken>,
+    ) -> Result<Self, ApiError> {
+        static LIMITER: OnceLock<Arc<RateLimiter>> = OnceLock::new();
+
+        let config = ClientConfig::from_environment().map_err(ApiError::Config)?;
+        let limiter = LIMITER.get_or_init(|| Arc::new(RateLimiter::new(config.limits.clone())));
+        let mut builder = Self::builder()
+            .config(&config)
+            .http_client(http.clone())
+            .rate_limiter(limiter.clone())
+            .auto_renew(false);
+        if let Some(auth_token) = auth_token {
+            builder = builder.auth_token(auth_token.clone());
+        }
+        builder.try_build().map_err(ApiError::Config)
+    }
+
+    /// Get the base URL all requests are sent to
+    pub fn base_url(&self) -> &BaseUrl {
+        &self.inner.base_url
+    }
+
+    /// Get the registry the requests of this client are counted in
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.inner.metrics
+    }
+
+    /// Get the rate limiter requests of this client wait in
+    pub fn rate_limiter(&self) -> &Arc<RateLimiter> {
+        &self.inner.limiter
+    }
+
+    /// Get the underlying HTTP client, or `None` if a custom transport is used
+    pub fn http_client(&self) -> Option<&Client> {
+        self.inner.http.as_ref()
+    }
+
+    /// Get the current auth token, if logged in
+    pub fn auth_token(&self) -> Option<AuthToken> {
+        self.inner.session.auth_token()
+    }
+
+    /// Get when the current auth token expires, if the server said so
+    pub fn auth_token_expires_at(&self) -> Option<SystemTime> {
+        self.inner.session.expires_at()
+    }
+
+    /// Get the retry policy used for requests made through this handle
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        self.overrides
+            .retry_policy
+            .as_deref()
+            .unwrap_or(&self.inner.retry_policy)
+    }
+
+    /// Get a handle that retries its requests with the given policy instead of the client's
+    ///
+    /// The handle shares the connection pool and auth token with this client.
+    pub fn with_retry_policy(&self, retry_policy: RetryPolicy) -> ApiClient {
+        let mut client = self.clone();
+        client.overrides.retry_policy = Some(Arc::new(retry_policy));
+        client
+    }
+
+    /// Get the timeouts used for requests made through this handle
+    pub fn timeouts(&self) -> Timeouts {
+        self.overrides.timeouts.unwrap_or(self.inner.timeouts)
+    }
+
+    /// Get the limits batch uploads are split by
+    pub fn batch_limits(&self) -> &BatchLimits {
+        &self.inner.batch_limits
+    }
+
+    /// Get a handle that uses the given read and total timeouts instead of the client's
+    ///
+    /// The connect timeout belongs to the HTTP client and is not changed.
+    pub fn with_timeouts(&self, timeouts: Timeouts) -> ApiClient {
+        let mut client = self.clone();
+        client.overrides.timeouts = Some(timeouts);
+        client
+    }
+
+    /// Get a handle whose requests fail with `ApiError::Cancelled` once `cancel_handle` is
+    /// cancelled
+    pub fn with_cancel_handle(&self, cancel_handle: &CancelHandle) -> ApiClient {
+        let mut client = self.clone();
+        client.overrides.cancel_handle = Some(cancel_handle.clone());
+        client
+    }
+
+    /// Get the priority of requests made through this handle
+    pub fn priority(&self) -> Priority {
+        self.overrides.priority.unwrap_or_default()
+    }
+
+    /// Get a handle whose requests wait behind those of a higher priority when rate limited
+    pub fn with_priority(&self, priority: Priority) -> ApiClient {
+        let mut client = self.clone();
+        client.overrides.priority = Some(priority);
+        client
+    }
+
+    /// Replace the current auth token, with no known expiry
+    pub fn set_auth_token(&self, auth_token: Option<AuthToken>) {
+        self.inner.session.set(auth_token, None);
+    }
+
+    /// Replace the current auth token and its expiry
+    pub(crate) fn set_credentials(
+        &self,
+        auth_token: Option<AuthToken>,
+        expires_at: Option<SystemTime>,
+    ) {
+        self.inner.session.set(auth_token, expires_at);
+    }
+
+    /// Requests for the user endpoint
+    pub fn users(&self) -> Users<'_> {
+        Users::new(self)
+    }
+
+    /// Requests for the authentication endpoint
+    pub fn auth(&self) -> Auth<'_> {
+        Auth::new(self)
+    }
+
+    /// Requests for the sensor endpoint
+    pub fn sensors(&self) -> Sensors<'_> {
+        Sensors::new(self)
+    }
+
+    /// Requests for the session endpoint
+    pub fn sessions(&self) -> Sessions<'_> {
+        Sessions::new(self)
+    }
+
+    /// Requests for the session sensor endpoint
+    pub fn session_sensors(&self) -> SessionSensors<'_> {
+        SessionSensors::new(self)
+    }
+
+    /// Requests for the session sensor data endpoint
+    pub fn datapoints(&self) -> Datapoints<'_> {
+        Datapoints::new(self)
+    }
+
+    /// Renew the login session, sharing the renewal with any concurrent callers
+    ///
+    /// `seen` is the generation of the auth token found to be stale, or `None` to always renew.
+    pub(crate) async fn renew_shared(&self, seen: Option<u64>) -> Result<SessionCookie, ApiError> {
+        self.inner
+            .session
+            .renew_with(seen, |auth_token| renew_auth_token(self, auth_token))
+            .await
+    }
+
+    /// Send a request with the current auth token, renewing the session when needed
+    pub(crate) async fn send<T>(
+        &self,
+        method: Method,
+        url: String,
+        body: Option<T>,
+    ) -> Result<(StatusCode, Option<Value>, HeaderMap), ApiError>
+    where
+        T: Serialize,
+    {
+        if !self.inner.auto_renew {
+            return self.send_raw(method, url, body).await;
+        }
+
+        // Renew ahead of time if the token is about to expire. A failure here is not fatal:
+        // the request is still sent and a 401 is handled below.
+        if let Some(seen) = self.inner.session.expiring_within(self.inner.renew_before) {
+            let _ = self.renew_shared(Some(seen)).await;
+        }
+
+        let (auth_token, generation) = self.inner.session.snapshot();
+        let result = self
+            .send_with_token(&method, url.clone(), auth_token.as_ref(), body.as_ref())
+            .await;
+
+        // Renew once and replay the request if the server rejected the token
+        match result {
+            Err(ApiError::Status { status, .. })
+                if status == StatusCode::UNAUTHORIZED && auth_token.is_some() =>
+            {
+                match self.renew_shared(Some(generation)).await {
+                    Ok(cookie) => {
+                        let auth_token = cookie.auth_token();
+                        self.send_with_token(&method, url, Some(&auth_token), body.as_ref())
+                            .await
+                    }
+                    Err(_) => result,
+                }
+            }
+            result => result,
+        }
+    }
+
+    /// Send a request with the current auth token, without renewing the session
+    pub(crate) async fn send_raw<T>(
+        &self,
+        method: Method,
+        url: String,
+        body: Option<T>,
+    ) -> Result<(StatusCode, Option<Value>, HeaderMap), ApiError>
+    where
+        T: Serialize,
+    {
+        let auth_token = self.auth_token();
+        self.send_with_token(&method, url, auth_token.as_ref(), body)
+            .await
+    }
+
+    /// Send a request with the given auth token, retrying it according to the retry policy
+    ///
+    /// The total timeout and the cancel handle cover all attempts, which run in an `api` span
+    /// naming the endpoint and recording the final status and the latency.
+    pub(crate) async fn send_with_token<T>(
+        &self,
+        method: &Method,
+        url: String,
+        auth_token: Option<&AuthToken>,
+        body: Option<T>,
+    ) -> Result<(StatusCode, Option<Value>, HeaderMap), ApiError>
+    where
+        T: Serialize,
+    {
+        let route = Route::find(self.base_url(), method, &url);
+        let span = tracing::info_span!(
+            "api",
+            endpoint = route.map_or("unknown", |route| route.name),
+            method = %method,
+            route = route.map_or("unknown", |route| route.template),
+            status = field::Empty,
+            latency_ms = field::Empty,
+        );
+
+        let started = Instant::now();
+        let result = self
+            .send_with_deadline(method, url, auth_token, body)
+            .instrument(span.clone())
+            .await;
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        match &result {
+            Ok((status, _, _)) => {
+                span.record("status", status.as_u16());
+            }
+            Err(e) => {
+                if let Some(status) = e.status() {
+                    span.record("status", status.as_u16());
+                }
+                span.in_scope(|| tracing::debug!(error = %e, "request failed"));
+            }
+        }
+        result
+    }
+
+    /// Send a request within the total timeout, unless the cancel handle fires first
+    async fn send_with_deadline<T>(
+        &self,
+        method: &Method,
+        url: String,
+        auth_token: Option<&AuthToken>,
+        body: Option<T>,
+    ) -> Result<(StatusCode, Option<Value>, HeaderMap), ApiError>
+    where
+        T: Serialize,
+    {
+        let attempts = self.send_with_retries(method, url, auth_token, body);
+        let attempts = async {
+            with_timeout(self.timeouts().total, attempts)
+                .await
+                .unwrap_or(Err(ApiError::Timeout(TimeoutKind::Total)))
+        };
+
+        let Some(cancel_handle) = &self.overrides.cancel_handle else {
+            return attempts.await;
+        };
+        if cancel_handle.is_cancelled() {
+            return Err(ApiError::Cancelled);
+        }
+        match future::select(pin!(attempts), cancel_handle.cancelled()).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => Err(ApiError::Cancelled),
+        }
+    }
+
+    async fn send_with_retries<T>(
+        &self,
+        method: &Method,
+        url: String,
+        auth_token: Option<&AuthToken>,
+        body: Option<T>,
+    ) -> Result<(StatusCode, Option<Value>, HeaderMap), ApiError>
+    where
+        T: Serialize,
+    {
+        let retry_policy = self.retry_policy();
+        let read_timeout = self.timeouts().read;
+        let group = RouteGroup::of(method, Route::find(&self.inner.base_url, method, &url));
+
+        // Every attempt carries the same key so the server can recognize retries
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, self.inner.user_agent.clone());
+        if retry_policy.uses_idempotency_keys() {
+            let key = HeaderValue::from_str(&retry::new_idempotency_key())?;
+            headers.insert(IDEMPOTENCY_KEY_HEADER, key);
+        }
+
+        let mut attempt = 1;
+        loop {
+            // Each attempt waits for its turn, so retries respect the limits too
+            let permit = self.inner.limiter.acquire(group, self.priority()).await;
+            let result = send_request(
+                self.inner.transport.as_ref(),
+                method,
+                url.clone(),
+                auth_token,
+                headers.clone(),
+                body.as_ref(),
+                read_timeout,
+            )
+            .await;
+            drop(permit);
+
+            let delay = match &result {
+                Err(e) => retry_policy.retry_delay(method, attempt, e),
+                Ok(_) => None,
+            };
+            match delay {
+                Some(delay) => retry::sleep(delay).await,
+                None => return result,
+            }
+            attempt += 1;
+        }
+    }
+}
+
+impl Default for ApiClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Create the HTTP client used when none is passed to `ApiClientBuilder::http_client`
+fn new_http_client(timeouts: &Timeouts) -> Client {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut builder = Client::builder();
+        if let Some(connect) = timeouts.connect {
+            builder = builder.connect_timeout(connect);
+        }
+        // Keep connections open between requests, and let servers that offer it over TLS
+        // negotiate HTTP/2
+        #[cfg(feature = "native")]
+        {
+            builder = builder
+                .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+                .tcp_keepalive(TCP_KEEPALIVE)
+                .http2_adaptive_window(true);
+        }
+        // Building only fails if the TLS backend cannot be initialized, in which case
+        // `Client::new` panics with the same error
+        builder.build().unwrap_or_else(|_| Client::new())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = timeouts;
+        Client::new()
+    }
+}
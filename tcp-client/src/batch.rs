@@ -0,0 +1,303 @@
+//! Splitting of large datapoint batches into uploads the server accepts
+//!
+//! `Datapoints::batch_create` splits its datapoints into chunks of at most `max_len` datapoints
+//! and `max_bytes` bytes of JSON, keeping their order, and uploads up to `max_concurrency`
+//! chunks at a time. The result lists every datapoint by `id` and `datetime`, as created or
+//! with the error of the chunk it was sent in.
+
+use crate::api::session_sensor_data::SessionSensorData;
+use crate::error::ApiError;
+use crate::timestamp::Timestamp;
+use crate::types::SessionSensorId;
+use std::fmt;
+
+/// Size of `{"datapoints":[]}`, the JSON around the datapoints of a batch
+const ENVELOPE_BYTES: usize = 17;
+
+/// Limits on the chunks a batch upload is split into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimits {
+    /// Most datapoints sent in one request
+    pub max_len: usize,
+    /// Most bytes of JSON sent in one request, unless a single datapoint is larger
+    pub max_bytes: usize,
+    /// Most chunks uploaded at the same time
+    pub max_concurrency: usize,
+}
+
+impl Default for BatchLimits {
+    fn default() -> Self {
+        Self {
+            max_len: 500,
+            max_bytes: 1024 * 1024,
+            max_concurrency: 4,
+        }
+    }
+}
+
+impl BatchLimits {
+    /// Split `datapoints` into chunks within the limits, keeping their order
+    ///
+    /// A datapoint larger than `max_bytes` on its own is sent alone, for the server to accept
+    /// or reject.
+    pub fn split(&self, datapoints: Vec<SessionSensorData>) -> Vec<Vec<SessionSensorData>> {
+        let mut chunks = Vec::new();
+        let mut chunk = Vec::new();
+        let mut chunk_size = BatchSize::default();
+
+        for datapoint in datapoints {
+            let bytes = BatchSize::datapoint_bytes(&datapoint);
+            if !chunk.is_empty()
+                && (chunk.len() >= self.max_len || chunk_size.exceeds(bytes, self.max_bytes))
+            {
+                chunks.push(std::mem::take(&mut chunk));
+                chunk_size = BatchSize::default();
+            }
+            chunk_size.add(bytes);
+            chunk.push(datapoint);
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+}
+
+/// Size of the JSON body of a batch, kept up to date as datapoints are added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BatchSize {
+    bytes: usize,
+    len: usize,
+}
+
+impl Default for BatchSize {
+    fn default() -> Self {
+        Self {
+            bytes: ENVELOPE_BYTES,
+            len: 0,
+        }
+    }
+}
+
+impl BatchSize {
+    /// Get the size of the JSON of one datapoint
+    pub(crate) fn datapoint_bytes(datapoint: &SessionSensorData) -> usize {
+        serde_json::to_vec(datapoint).map_or(0, |json| json.len())
+    }
+
+    /// Check whether adding a datapoint of `bytes` bytes would make the batch larger than
+    /// `max_bytes`
+    ///
+    /// An empty batch never does, so a datapoint too large on its own is still sent.
+    pub(crate) fn exceeds(&self, bytes: usize, max_bytes: usize) -> bool {
+        // Datapoints after the first are preceded by a comma
+        self.len > 0 && self.bytes + 1 + bytes > max_bytes
+    }
+
+    /// Add a datapoint of `bytes` bytes
+    pub(crate) fn add(&mut self, bytes: usize) {
+        self.bytes += bytes + usize::from(self.len > 0);
+        self.len += 1;
+    }
+}
+
+/// Identity of a datapoint: its session sensor and datetime
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DatapointKey {
+    pub id: SessionSensorId,
+    pub datetime: Timestamp,
+}
+
+impl From<&SessionSensorData> for DatapointKey {
+    fn from(datapoint: &SessionSensorData) -> Self {
+        Self {
+            id: datapoint.id.clone(),
+            datetime: datapoint.datetime,
+        }
+    }
+}
+
+/// Datapoints of a chunk the server did not create, with the error of their request
+#[derive(Debug)]
+pub struct FailedChunk {
+    pub datapoints: Vec<DatapointKey>,
+    pub error: ApiError,
+}
+
+/// Outcome of a batch upload, split into chunks
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    /// Datapoints created, in the order they were given
+    pub created: Vec<DatapointKey>,
+    /// Chunks that failed, in the order they were given
+    pub failed: Vec<FailedChunk>,
+    /// Number of requests the batch was split into
+    pub chunks: usize,
+}
+
+impl BatchReport {
+    /// Add the outcome of uploading the chunk with the datapoints `keys`
+    pub(crate) fn add(&mut self, keys: Vec<DatapointKey>, result: Result<(), ApiError>) {
+        match result {
+            Ok(()) => self.created.extend(keys),
+            Err(error) => self.failed.push(FailedChunk {
+                datapoints: keys,
+                error,
+            }),
+        }
+        self.chunks += 1;
+    }
+
+    /// Get the datapoints that were not created
+    pub fn failed_datapoints(&self) -> impl Iterator<Item = &DatapointKey> {
+        self.failed.iter().flat_map(|chunk| &chunk.datapoints)
+    }
+
+    /// Check whether every datapoint was created
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Return the report if every datapoint was created, or a `BatchError` with it otherwise
+    pub(crate) fn into_result(self) -> Result<Self, BatchError> {
+        if self.is_complete() {
+            Ok(self)
+        } else {
+            Err(BatchError { report: self })
+        }
+    }
+}
+
+/// Error returned when some datapoints of a batch upload were not created
+///
+/// The report tells which datapoints were created and which were not, and why.
+#[derive(Debug)]
+pub struct BatchError {
+    pub report: BatchReport,
+}
+
+impl BatchError {
+    /// Report a batch that could not be sent at all
+    pub(crate) fn unsent(datapoints: &[SessionSensorData], error: ApiError) -> Self {
+        let mut report = BatchReport::default();
+        report.add(
+            datapoints.iter().map(DatapointKey::from).collect(),
+            Err(error),
+        );
+        report.chunks = 0;
+        Self { report }
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.report.failed_datapoints().count();
+        write!(
+            f,
+            "{} of {} datapoints were not created",
+            failed,
+            failed + self.report.created.len()
+        )?;
+        if let Some(chunk) = self.report.failed.first() {
+            write!(f, ": {}", chunk.error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BatchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.report
+            .failed
+            .first()
+            .map(|chunk| &chunk.error as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqwest::StatusCode;
+
+    fn datapoint(second: i64, data_blob: &str) -> SessionSensorData {
+        SessionSensorData {
+            id: SessionSensorId::from("7"),
+            datetime: Timestamp::from_unix_seconds(1_735_689_600 + second).unwrap(),
+            data_blob: data_blob.to_string(),
+        }
+    }
+
+    fn batch_bytes(chunk: &[SessionSensorData]) -> usize {
+        serde_json::to_vec(&crate::api::session_sensor_data::Batch {
+            datapoints: chunk.to_vec(),
+        })
+        .unwrap()
+        .len()
+    }
+
+    #[test]
+    fn splits_by_count() {
+        let limits = BatchLimits {
+            max_len: 2,
+            ..BatchLimits::default()
+        };
+        let datapoints: Vec<_> = (0..5).map(|i| datapoint(i, "x")).collect();
+
+        let chunks = limits.split(datapoints.clone());
+        let lens: Vec<_> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(lens, [2, 2, 1]);
+        assert_eq!(chunks.concat(), datapoints);
+        assert!(limits.split(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn splits_by_serialized_size() {
+        let datapoints: Vec<_> = (0..6).map(|i| datapoint(i, &"x".repeat(40))).collect();
+        let max_bytes = batch_bytes(&datapoints[..2]);
+        let limits = BatchLimits {
+            max_bytes,
+            ..BatchLimits::default()
+        };
+
+        let chunks = limits.split(datapoints.clone());
+        assert_eq!(chunks.len(), 3);
+        for chunk in &chunks {
+            assert!(batch_bytes(chunk) <= max_bytes);
+        }
+        assert_eq!(chunks.concat(), datapoints);
+
+        // Too large on its own, so sent alone
+        let huge = datapoint(9, &"x".repeat(max_bytes));
+        let chunks = limits.split(vec![datapoints[0].clone(), huge.clone()]);
+        assert_eq!(chunks, [vec![datapoints[0].clone()], vec![huge]]);
+    }
+
+    #[test]
+    fn reports_datapoints_by_key() {
+        let datapoints: Vec<_> = (0..3).map(|i| datapoint(i, "x")).collect();
+        let keys: Vec<_> = datapoints.iter().map(DatapointKey::from).collect();
+        let mut report = BatchReport::default();
+        report.add(keys[..2].to_vec(), Ok(()));
+        report.add(
+            keys[2..].to_vec(),
+            Err(ApiError::Status {
+                status: StatusCode::PAYLOAD_TOO_LARGE,
+                body: None,
+                retry_after: None,
+            }),
+        );
+
+        assert_eq!(report.chunks, 2);
+        assert_eq!(
+            report.created,
+            [(&datapoints[0]).into(), (&datapoints[1]).into()]
+        );
+        let failed: Vec<_> = report.failed_datapoints().cloned().collect();
+        assert_eq!(failed, [DatapointKey::from(&datapoints[2])]);
+
+        let error = report.into_result().unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("1 of 3 datapoints were not created: "));
+    }
+}
@@ -0,0 +1,234 @@
+//! Command-line arguments of `pdap`
+
+use crate::output::Format;
+use clap::{Args, Parser, Subcommand};
+use client::{Profile, SensorId, SessionId, SessionSensorId, Timestamp, Username};
+use std::io;
+use std::path::PathBuf;
+
+/// Command-line client for the personal data acquisition backend
+#[derive(Debug, Parser)]
+#[command(name = "pdap", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub options: Options,
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+/// Options shared by all commands
+#[derive(Debug, Args)]
+pub struct Options {
+    /// URL of the backend, overriding the configuration
+    #[arg(long, global = true)]
+    pub base_url: Option<String>,
+    /// Configuration profile: dev, staging or prod [default: API_PROFILE or dev]
+    #[arg(long, global = true)]
+    pub profile: Option<Profile>,
+    /// TOML configuration file [default: API_CONFIG]
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// File that keeps the login session between invocations
+    #[arg(long, global = true, env = "PDAP_SESSION_FILE")]
+    pub session_file: Option<PathBuf>,
+    /// Print results as a table or as JSON [default: table, json in the shell]
+    #[arg(long, short, global = true, value_enum)]
+    pub output: Option<Format>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CliCommand {
+    #[command(flatten)]
+    Api(Command),
+    /// Start an interactive shell
+    Shell,
+}
+
+/// Commands that call the backend, available from the command line and in the shell
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Manage users
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Log in and keep the session for later commands
+    Login {
+        username: Username,
+        #[command(flatten)]
+        password: Password,
+    },
+    /// Log out and forget the session
+    Logout,
+    /// Renew the login session
+    Renew,
+    /// Manage sensors
+    #[command(subcommand)]
+    Sensor(SensorCommand),
+    /// Manage recording sessions
+    #[command(subcommand)]
+    Session(SessionCommand),
+    /// Manage links between sessions and sensors
+    #[command(subcommand)]
+    Link(LinkCommand),
+    /// Manage datapoints
+    #[command(subcommand)]
+    Data(DataCommand),
+}
+
+/// Where the password of a user is read from
+///
+/// Passwords are not accepted as arguments, where other users could read them from the
+/// process list and they end up in the shell history.
+#[derive(Debug, Args)]
+pub struct Password {
+    /// Read the password from the first line of standard input [default: PDAP_PASSWORD, or a
+    /// prompt that does not echo it]
+    #[arg(long)]
+    pub password_stdin: bool,
+}
+
+impl Password {
+    /// Get the password from standard input, `PDAP_PASSWORD` or a prompt on the terminal
+    pub fn read(&self) -> io::Result<String> {
+        if self.password_stdin {
+            let mut password = String::new();
+            if io::stdin().read_line(&mut password)? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "no password on standard input",
+                ));
+            }
+            let len = password.trim_end_matches(['\r', '\n']).len();
+            password.truncate(len);
+            return Ok(password);
+        }
+        if let Some(password) = std::env::var_os("PDAP_PASSWORD") {
+            return password.into_string().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "PDAP_PASSWORD is not UTF-8")
+            });
+        }
+        rpassword::prompt_password("Password: ")
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Create a user
+    Create {
+        username: Username,
+        #[command(flatten)]
+        password: Password,
+    },
+    /// List all users
+    List,
+    /// Show a user, or the logged in user if none is given
+    Show { username: Option<Username> },
+    /// Change the password of a user
+    Update {
+        username: Username,
+        #[command(flatten)]
+        password: Password,
+    },
+    /// Delete a user
+    Delete { username: Username },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SensorCommand {
+    /// Create a sensor
+    Create { sensor_type: String },
+    /// List all sensors
+    List,
+    /// Show a sensor
+    Show { id: SensorId },
+    /// Change the type of a sensor
+    Update { id: SensorId, sensor_type: String },
+    /// Delete a sensor
+    Delete { id: SensorId },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SessionCommand {
+    /// Create a session for a user
+    Create { username: Username },
+    /// List all sessions, or those of one user
+    List {
+        /// Only sessions of this user
+        #[arg(long)]
+        user: Option<Username>,
+    },
+    /// Show a session
+    Show { id: SessionId },
+    /// Move a session to another user
+    Update { id: SessionId, username: Username },
+    /// Delete a session
+    Delete { id: SessionId },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LinkCommand {
+    /// Link a sensor to a session
+    Create {
+        session_id: SessionId,
+        sensor_id: SensorId,
+    },
+    /// List all links, or those of one session
+    List {
+        /// Only links of this session
+        #[arg(long)]
+        session: Option<SessionId>,
+    },
+    /// Show the link of a sensor
+    Show { sensor_id: SensorId },
+    /// Change the session and sensor of a link
+    Update {
+        id: SessionSensorId,
+        session_id: SessionId,
+        sensor_id: SensorId,
+    },
+    /// Delete a link
+    Delete { id: SessionSensorId },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DataCommand {
+    /// Add a datapoint to a session sensor link
+    Add {
+        link_id: SessionSensorId,
+        data_blob: String,
+        /// Time of the reading, e.g. 2025-01-31T13:45:00Z [default: now]
+        #[arg(long)]
+        at: Option<Timestamp>,
+    },
+    /// Add the datapoints of a JSON file in one request, `-` for standard input
+    ///
+    /// The file holds an array of objects with `id`, `datetime` and `data_blob`.
+    Batch { file: PathBuf },
+    /// List all datapoints, or those of one session or link
+    List {
+        /// Only datapoints of this session
+        #[arg(long, conflicts_with = "link")]
+        session: Option<SessionId>,
+        /// Only datapoints of this link
+        #[arg(long)]
+        link: Option<SessionSensorId>,
+        /// Only datapoints after this time, requires --session
+        #[arg(long)]
+        after: Option<Timestamp>,
+    },
+    /// Show a datapoint
+    Get {
+        link_id: SessionSensorId,
+        datetime: Timestamp,
+    },
+    /// Replace the data of a datapoint
+    Update {
+        link_id: SessionSensorId,
+        datetime: Timestamp,
+        data_blob: String,
+    },
+    /// Delete a datapoint
+    Delete {
+        link_id: SessionSensorId,
+        datetime: Timestamp,
+    },
+}
@@ -0,0 +1,217 @@
+//! Running commands against the backend
+
+use crate::cli::{Command, DataCommand, LinkCommand, SensorCommand, SessionCommand, UserCommand};
+use crate::output::{Output, Row};
+use client::api::session_sensor_data::SessionSensorData;
+use client::cookie::SessionCookie;
+use client::{ApiClient, Timestamp, Username};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+/// Login session shown after `login` and `renew`
+#[derive(Debug, Serialize)]
+struct LoginInfo {
+    username: Option<Username>,
+    expires_at: Option<Timestamp>,
+}
+
+impl LoginInfo {
+    fn new(username: Option<Username>, cookie: &SessionCookie) -> Self {
+        Self {
+            username,
+            expires_at: cookie.expires_at().map(Timestamp::from),
+        }
+    }
+}
+
+impl Row for LoginInfo {
+    const HEADERS: &'static [&'static str] = &["USERNAME", "EXPIRES"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.username
+                .as_ref()
+                .map_or_else(String::new, |username| username.as_str().to_string()),
+            self.expires_at.map_or_else(
+                || "unknown".to_string(),
+                |expires_at| expires_at.to_string(),
+            ),
+        ]
+    }
+}
+
+/// Run a command and return what to print
+pub async fn run(client: &ApiClient, command: Command) -> Result<Output, Box<dyn Error>> {
+    let output = match command {
+        Command::User(command) => user(client, command).await?,
+        Command::Login { username, password } => {
+            let cookie = client
+                .auth()
+                .login(&username, &password.read()?)
+                .await?
+                .ok_or("server did not send a session cookie")?;
+            Output::one(&LoginInfo::new(Some(username), &cookie))
+        }
+        Command::Logout => {
+            client.auth().logout().await?;
+            Output::message("Logged out")
+        }
+        Command::Renew => {
+            let cookie = client.auth().renew().await?;
+            Output::one(&LoginInfo::new(None, &cookie))
+        }
+        Command::Sensor(command) => sensor(client, command).await?,
+        Command::Session(command) => session(client, command).await?,
+        Command::Link(command) => link(client, command).await?,
+        Command::Data(command) => data(client, command).await?,
+    };
+    Ok(output)
+}
+
+async fn user(client: &ApiClient, command: UserCommand) -> Result<Output, Box<dyn Error>> {
+    let users = client.users();
+    let output = match command {
+        UserCommand::Create { username, password } => {
+            Output::one(&users.create(&username, &password.read()?).await?)
+        }
+        UserCommand::List => Output::list(&users.list().await?.users),
+        UserCommand::Show { username: None } => Output::one(&users.profile().await?),
+        UserCommand::Show {
+            username: Some(username),
+        } => Output::one(&users.get(&username).await?),
+        UserCommand::Update { username, password } => {
+            Output::one(&users.update(&username, &password.read()?).await?)
+        }
+        UserCommand::Delete { username } => {
+            users.delete(&username).await?;
+            Output::message(format!("Deleted user {}", username))
+        }
+    };
+    Ok(output)
+}
+
+async fn sensor(client: &ApiClient, command: SensorCommand) -> Result<Output, Box<dyn Error>> {
+    let sensors = client.sensors();
+    let output = match command {
+        SensorCommand::Create { sensor_type } => Output::one(&sensors.create(&sensor_type).await?),
+        SensorCommand::List => Output::list(&sensors.list().await?.sensors),
+        SensorCommand::Show { id } => Output::one(&sensors.get(&id).await?),
+        SensorCommand::Update { id, sensor_type } => {
+            Output::one(&sensors.update(&id, &sensor_type).await?)
+        }
+        SensorCommand::Delete { id } => {
+            sensors.delete(&id).await?;
+            Output::message(format!("Deleted sensor {}", id))
+        }
+    };
+    Ok(output)
+}
+
+async fn session(client: &ApiClient, command: SessionCommand) -> Result<Output, Box<dyn Error>> {
+    let sessions = client.sessions();
+    let output = match command {
+        SessionCommand::Create { username } => Output::one(&sessions.create(&username).await?),
+        SessionCommand::List { user: None } => Output::list(&sessions.list().await?.sessions),
+        SessionCommand::List { user: Some(user) } => {
+            Output::list(&sessions.list_by_user(&user).await?.sessions)
+        }
+        SessionCommand::Show { id } => Output::one(&sessions.get(&id).await?),
+        SessionCommand::Update { id, username } => {
+            Output::one(&sessions.update(&id, &username).await?)
+        }
+        SessionCommand::Delete { id } => {
+            sessions.delete(&id).await?;
+            Output::message(format!("Deleted session {}", id))
+        }
+    };
+    Ok(output)
+}
+
+async fn link(client: &ApiClient, command: LinkCommand) -> Result<Output, Box<dyn Error>> {
+    let links = client.session_sensors();
+    let output = match command {
+        LinkCommand::Create {
+            session_id,
+            sensor_id,
+        } => Output::one(&links.create(&session_id, &sensor_id).await?),
+        LinkCommand::List { session: None } => Output::list(&links.list().await?.links),
+        LinkCommand::List {
+            session: Some(session),
+        } => Output::list(&links.list_by_session(&session).await?.links),
+        LinkCommand::Show { sensor_id } => Output::one(&links.get_by_sensor(&sensor_id).await?),
+        LinkCommand::Update {
+            id,
+            session_id,
+            sensor_id,
+        } => Output::one(&links.update(&id, &session_id, &sensor_id).await?),
+        LinkCommand::Delete { id } => {
+            links.delete(&id).await?;
+            Output::message(format!("Deleted link {}", id))
+        }
+    };
+    Ok(output)
+}
+
+async fn data(client: &ApiClient, command: DataCommand) -> Result<Output, Box<dyn Error>> {
+    let datapoints = client.datapoints();
+    let output = match command {
+        DataCommand::Add {
+            link_id,
+            data_blob,
+            at,
+        } => {
+            let datetime = at.unwrap_or_else(Timestamp::now);
+            Output::one(&datapoints.create(&link_id, datetime, &data_blob).await?)
+        }
+        DataCommand::Batch { file } => {
+            let report = datapoints.batch_create(read_batch(&file)?).await?;
+            Output::message(format!("Added {} datapoints", report.created.len()))
+        }
+        DataCommand::List {
+            session: Some(session),
+            after: Some(after),
+            ..
+        } => Output::list(&datapoints.list_after(&session, after).await?.datapoints),
+        DataCommand::List {
+            session: None,
+            after: Some(_),
+            ..
+        } => return Err("--after requires --session".into()),
+        DataCommand::List {
+            session: Some(session),
+            ..
+        } => Output::list(&datapoints.list_by_session(&session).await?.datapoints),
+        DataCommand::List {
+            link: Some(link), ..
+        } => Output::list(&datapoints.list_by_session_sensor(&link).await?.datapoints),
+        DataCommand::List { .. } => Output::list(&datapoints.list().await?.datapoints),
+        DataCommand::Get { link_id, datetime } => {
+            Output::one(&datapoints.get(&link_id, datetime).await?)
+        }
+        DataCommand::Update {
+            link_id,
+            datetime,
+            data_blob,
+        } => Output::one(&datapoints.update(&link_id, datetime, &data_blob).await?),
+        DataCommand::Delete { link_id, datetime } => {
+            datapoints.delete(&link_id, datetime).await?;
+            Output::message(format!("Deleted datapoint {} at {}", link_id, datetime))
+        }
+    };
+    Ok(output)
+}
+
+/// Read the datapoints of a batch from a JSON file, or standard input for `-`
+fn read_batch(file: &Path) -> Result<Vec<SessionSensorData>, Box<dyn Error>> {
+    let mut json = String::new();
+    if file == Path::new("-") {
+        std::io::stdin().read_to_string(&mut json)?;
+    } else {
+        json = std::fs::read_to_string(file)
+            .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+    }
+    serde_json::from_str(&json)
+        .map_err(|e| format!("invalid batch in {}: {}", file.display(), e).into())
+}
@@ -0,0 +1,98 @@
+//! `pdap`, a command-line client for every endpoint of the backend
+//!
+//! The login session is kept in a file, so `pdap login` once lets later invocations make
+//! authenticated requests until `pdap logout`. `pdap shell` runs the same commands interactively.
+
+mod cli;
+mod commands;
+mod output;
+mod shell;
+
+use clap::Parser;
+use cli::{Cli, CliCommand, Options};
+use client::path::BaseUrl;
+use client::session_store::FileSessionStore;
+use client::{ApiClient, ClientConfig, ConfigError, Profile};
+use output::Format;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = async {
+        let client = connect(&cli.options)?;
+        match cli.command {
+            CliCommand::Api(command) => {
+                let output = commands::run(&client, command).await?;
+                println!(
+                    "{}",
+                    output.render(cli.options.output.unwrap_or(Format::Table))
+                );
+            }
+            CliCommand::Shell => {
+                let format = cli.options.output.unwrap_or(Format::Json);
+                let history = config_dir().map(|dir| dir.join("pdap").join("history"));
+                shell::run(&client, format, history).await?;
+            }
+        }
+        Ok::<_, Box<dyn Error>>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pdap: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Build a client from the configuration and the saved login session
+fn connect(options: &Options) -> Result<ApiClient, Box<dyn Error>> {
+    let mut loader = ClientConfig::loader();
+    if let Some(profile) = options.profile {
+        loader = loader.profile(profile);
+    }
+    if let Some(file) = &options.config {
+        loader = loader.file(file);
+    }
+    let mut config = loader.load()?;
+    if let Some(base_url) = &options.base_url {
+        config.base_url = BaseUrl::parse(base_url).map_err(ConfigError::BaseUrl)?;
+    }
+
+    let session_file = options
+        .session_file
+        .clone()
+        .unwrap_or_else(|| default_session_file(config.profile));
+    let client = ApiClient::builder()
+        .config(&config)
+        .session_store(Arc::new(FileSessionStore::new(session_file)))
+        .try_build()?;
+    Ok(client)
+}
+
+/// Get where the login session of a profile is kept when `--session-file` is not given
+///
+/// This is `pdap/session-<profile>.json` in the user's configuration directory, so logins to
+/// different backends do not replace each other.
+fn default_session_file(profile: Profile) -> PathBuf {
+    let file_name = format!("session-{}.json", profile);
+    match config_dir() {
+        Some(config_dir) => config_dir.join("pdap").join(file_name),
+        None => PathBuf::from(format!(".pdap-{}", file_name)),
+    }
+}
+
+/// Get the user's configuration directory
+fn config_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+}
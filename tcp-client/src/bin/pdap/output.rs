@@ -0,0 +1,159 @@
+//! Rendering command results as tables or JSON
+
+use client::api::{
+    sensor::SensorRecord, session::SessionRecord, session_sensor::SessionSensorLink,
+    session_sensor_data::Datapoint, user::UserInfo,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+/// Record that can be shown as a table row
+pub trait Row: Serialize {
+    const HEADERS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+}
+
+/// Result of a command
+#[derive(Debug)]
+pub enum Output {
+    Rows {
+        headers: &'static [&'static str],
+        rows: Vec<Vec<String>>,
+        json: Value,
+    },
+    Message(String),
+}
+
+impl Output {
+    /// Show a single record
+    pub fn one<T: Row>(record: &T) -> Self {
+        Output::Rows {
+            headers: T::HEADERS,
+            rows: vec![record.cells()],
+            json: serde_json::to_value(record).unwrap_or_default(),
+        }
+    }
+
+    /// Show a list of records
+    pub fn list<T: Row>(records: &[T]) -> Self {
+        Output::Rows {
+            headers: T::HEADERS,
+            rows: records.iter().map(Row::cells).collect(),
+            json: serde_json::to_value(records).unwrap_or_default(),
+        }
+    }
+
+    /// Show a confirmation
+    pub fn message(message: impl Into<String>) -> Self {
+        Output::Message(message.into())
+    }
+
+    /// Get the JSON form of the result
+    pub fn json(&self) -> Value {
+        match self {
+            Output::Rows { json, .. } => json.clone(),
+            Output::Message(message) => serde_json::json!({ "message": message }),
+        }
+    }
+
+    /// Render the result, without a trailing newline
+    pub fn render(&self, format: Format) -> String {
+        match (format, self) {
+            (Format::Json, _) => serde_json::to_string_pretty(&self.json()).unwrap_or_default(),
+            (Format::Table, Output::Rows { headers, rows, .. }) => table(headers, rows),
+            (Format::Table, Output::Message(message)) => message.clone(),
+        }
+    }
+}
+
+/// Lay out rows in columns under their headers
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut lines = vec![line(headers.to_vec())];
+    let rules: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    lines.push(line(rules.iter().map(String::as_str).collect()));
+    lines.extend(
+        rows.iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+    lines.join("\n")
+}
+
+/// Keep a cell on one line
+fn cell(value: &str) -> String {
+    value.replace('\n', "\\n")
+}
+
+impl Row for UserInfo {
+    const HEADERS: &'static [&'static str] = &["USERNAME"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![cell(self.username.as_str())]
+    }
+}
+
+impl Row for SensorRecord {
+    const HEADERS: &'static [&'static str] = &["ID", "TYPE"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![cell(self.id.as_str()), cell(&self.sensor_type)]
+    }
+}
+
+impl Row for SessionRecord {
+    const HEADERS: &'static [&'static str] = &["ID", "USERNAME"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![cell(self.id.as_str()), cell(self.username.as_str())]
+    }
+}
+
+impl Row for SessionSensorLink {
+    const HEADERS: &'static [&'static str] = &["ID", "SESSION", "SENSOR"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            cell(self.id.as_str()),
+            cell(self.session_id.as_str()),
+            cell(self.sensor_id.as_str()),
+        ]
+    }
+}
+
+impl Row for Datapoint {
+    const HEADERS: &'static [&'static str] = &["LINK", "DATETIME", "DATA"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            cell(self.id.as_str()),
+            self.datetime.to_string(),
+            cell(&self.data_blob),
+        ]
+    }
+}
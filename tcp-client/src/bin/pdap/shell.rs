@@ -0,0 +1,475 @@
+//! Interactive shell running the same commands as the command line
+//!
+//! The shell keeps one client, and so one login session, for all of its commands. IDs and
+//! usernames seen in results are remembered for tab completion, and `use session <id>` makes
+//! `data list` default to that session. Unless `PDAP_PASSWORD` is set, passwords are asked for
+//! with a prompt that does not echo them. Lines passing a password as an argument are left out of
+//! the history, which is only readable by the user.
+
+use crate::cli::{Command, DataCommand};
+use crate::commands;
+use crate::output::Format;
+use clap::{CommandFactory, Parser, Subcommand};
+use client::{ApiClient, SessionId};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Commands of the pdap shell, one per line
+#[derive(Debug, Parser)]
+#[command(multicall = true)]
+struct ShellLine {
+    #[command(subcommand)]
+    command: ShellCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum ShellCommand {
+    #[command(flatten)]
+    Api(Command),
+    /// Set defaults for later commands
+    #[command(subcommand)]
+    Use(UseCommand),
+    /// Print results as a table or as JSON
+    Output { format: Format },
+    /// Leave the shell
+    #[command(alias = "quit")]
+    Exit,
+}
+
+#[derive(Debug, Subcommand)]
+enum UseCommand {
+    /// Make `data list` show the datapoints of this session
+    Session { id: SessionId },
+    /// Clear the session set with `use session`
+    None,
+}
+
+/// Run the shell until `exit` or the end of input
+///
+/// History is loaded from and saved to `history` when given.
+pub async fn run(
+    client: &ApiClient,
+    format: Format,
+    history: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ShellHelper::default()));
+    if let Some(history) = &history {
+        // There is no history yet on the first run
+        let _ = editor.load_history(history);
+    }
+
+    let mut format = format;
+    let mut session: Option<SessionId> = None;
+    loop {
+        let prompt = match &session {
+            Some(session) => format!("pdap [session {}]> ", session),
+            None => "pdap> ".to_string(),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !carries_password(line) {
+            editor.add_history_entry(line)?;
+        }
+
+        let Some(words) = shlex::split(line) else {
+            eprintln!("error: unbalanced quotes");
+            continue;
+        };
+        if words.iter().any(|word| word == "--password-stdin") {
+            // The shell reads its commands from standard input
+            eprintln!("error: the shell prompts for passwords instead of reading --password-stdin");
+            continue;
+        }
+        let command = match ShellLine::try_parse_from(words) {
+            Ok(line) => line.command,
+            Err(e) => {
+                // Also prints `help` output
+                let _ = e.print();
+                continue;
+            }
+        };
+
+        let known = &mut editor.helper_mut().expect("helper is set").known;
+        match command {
+            ShellCommand::Exit => break,
+            ShellCommand::Output { format: new_format } => format = new_format,
+            ShellCommand::Use(UseCommand::Session { id }) => {
+                known.sessions.insert(id.to_string());
+                session = Some(id);
+            }
+            ShellCommand::Use(UseCommand::None) => session = None,
+            ShellCommand::Api(command) => {
+                let group = group(&command);
+                let command = default_session(command, session.as_ref());
+                match commands::run(client, command).await {
+                    Ok(output) => {
+                        known.learn(group, &output.json());
+                        println!("{}", output.render(format));
+                    }
+                    Err(e) => eprintln!("error: {}", e),
+                }
+            }
+        }
+    }
+
+    if let Some(history) = &history {
+        if let Some(dir) = history.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        create_private(history)?;
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+/// Check whether a line passes a password as an argument
+///
+/// Passwords are not accepted as arguments, but a line trying to pass one still must not end up
+/// in the history file.
+fn carries_password(line: &str) -> bool {
+    line.split_whitespace().any(|word| {
+        let word = word.trim_matches(['"', '\'']);
+        word == "--password" || word.starts_with("--password=")
+    })
+}
+
+/// Create the history file if needed and make it readable only by the user
+fn create_private(path: &Path) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?;
+
+    // The mode only applies to new files, so also restrict one written by an older version
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// Get the name of the command group, e.g. `sensor` for `sensor list`
+fn group(command: &Command) -> &'static str {
+    match command {
+        Command::User(_) => "user",
+        Command::Login { .. } => "login",
+        Command::Logout => "logout",
+        Command::Renew => "renew",
+        Command::Sensor(_) => "sensor",
+        Command::Session(_) => "session",
+        Command::Link(_) => "link",
+        Command::Data(_) => "data",
+    }
+}
+
+/// Limit `data list` to the session in use when no other filter is given
+fn default_session(command: Command, session: Option<&SessionId>) -> Command {
+    match (command, session) {
+        (
+            Command::Data(DataCommand::List {
+                session: None,
+                link: None,
+                after,
+            }),
+            Some(session),
+        ) => Command::Data(DataCommand::List {
+            session: Some(session.clone()),
+            link: None,
+            after,
+        }),
+        (command, _) => command,
+    }
+}
+
+/// Kind of value that can be completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    User,
+    Sensor,
+    Session,
+    Link,
+}
+
+impl Kind {
+    /// Get what an argument of a command group holds
+    fn of(group: &str, arg: &str) -> Option<Self> {
+        match (group, arg) {
+            (_, "username" | "user") => Some(Kind::User),
+            (_, "sensor_id") | ("sensor", "id") => Some(Kind::Sensor),
+            (_, "session_id" | "session") | ("session" | "use", "id") => Some(Kind::Session),
+            (_, "link_id" | "link") | ("link" | "data", "id") => Some(Kind::Link),
+            _ => None,
+        }
+    }
+}
+
+/// IDs and usernames seen in results
+#[derive(Debug, Default)]
+struct KnownIds {
+    users: BTreeSet<String>,
+    sensors: BTreeSet<String>,
+    sessions: BTreeSet<String>,
+    links: BTreeSet<String>,
+}
+
+impl KnownIds {
+    fn get(&self, kind: Kind) -> &BTreeSet<String> {
+        match kind {
+            Kind::User => &self.users,
+            Kind::Sensor => &self.sensors,
+            Kind::Session => &self.sessions,
+            Kind::Link => &self.links,
+        }
+    }
+
+    fn get_mut(&mut self, kind: Kind) -> &mut BTreeSet<String> {
+        match kind {
+            Kind::User => &mut self.users,
+            Kind::Sensor => &mut self.sensors,
+            Kind::Session => &mut self.sessions,
+            Kind::Link => &mut self.links,
+        }
+    }
+
+    /// Remember the IDs in the JSON result of a command group
+    fn learn(&mut self, group: &str, json: &Value) {
+        let records = match json {
+            Value::Array(records) => records.as_slice(),
+            record => std::slice::from_ref(record),
+        };
+        for record in records.iter().filter_map(Value::as_object) {
+            for (field, value) in record {
+                if let (Some(kind), Some(value)) = (Kind::of(group, field), value.as_str()) {
+                    self.get_mut(kind).insert(value.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Tab completion of commands, options and known IDs
+#[derive(Debug, Default)]
+struct ShellHelper {
+    known: KnownIds,
+}
+
+impl ShellHelper {
+    /// Get the completions for the word after `words`
+    fn candidates(&self, words: &[&str], word: &str) -> Vec<String> {
+        let mut command = ShellLine::command();
+        let mut path = Vec::new();
+        let mut args = words;
+        while let Some((first, rest)) = args.split_first() {
+            let Some(subcommand) = command.find_subcommand(first).cloned() else {
+                break;
+            };
+            path.push(subcommand.get_name().to_string());
+            command = subcommand;
+            args = rest;
+        }
+
+        if command.has_subcommands() {
+            if !args.is_empty() {
+                return Vec::new();
+            }
+            return command
+                .get_subcommands()
+                .map(|subcommand| subcommand.get_name().to_string())
+                .collect();
+        }
+        if word.starts_with('-') {
+            return command
+                .get_arguments()
+                .filter_map(|arg| arg.get_long())
+                .map(|long| format!("--{}", long))
+                .collect();
+        }
+
+        // Find the argument the word is a value of
+        let takes_value = |word: &str| {
+            word.strip_prefix("--").is_some_and(|long| {
+                command
+                    .get_arguments()
+                    .any(|arg| arg.get_long() == Some(long) && arg.get_action().takes_values())
+            })
+        };
+        let arg = match args.last() {
+            Some(last) if takes_value(last) => {
+                let long = &last[2..];
+                command
+                    .get_arguments()
+                    .find(|arg| arg.get_long() == Some(long))
+            }
+            _ => {
+                let mut position = 0;
+                let mut skip = false;
+                for arg in args {
+                    if skip {
+                        skip = false;
+                    } else if arg.starts_with('-') {
+                        skip = takes_value(arg);
+                    } else {
+                        position += 1;
+                    }
+                }
+                command.get_positionals().nth(position)
+            }
+        };
+
+        let group = path.first().map_or("", String::as_str);
+        arg.and_then(|arg| Kind::of(group, arg.get_id().as_str()))
+            .map(|kind| self.known.get(kind).iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let word = &line[start..];
+        let words: Vec<&str> = line[..start].split_whitespace().collect();
+
+        let candidates = self
+            .candidates(&words, word)
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(word))
+            .map(|candidate| Pair {
+                display: candidate.clone(),
+                replacement: candidate,
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn helper() -> ShellHelper {
+        let mut helper = ShellHelper::default();
+        helper.known.learn(
+            "link",
+            &json!([{ "id": "l1", "session_id": "s1", "sensor_id": "t1" }]),
+        );
+        helper
+            .known
+            .learn("sensor", &json!({ "id": "t2", "sensor_type": "gps" }));
+        helper.known.learn(
+            "data",
+            &json!([{ "id": "l2", "datetime": "2025-01-01T00:00:00.000000Z", "data_blob": "1" }]),
+        );
+        helper
+    }
+
+    fn complete(line: &str) -> Vec<String> {
+        let helper = helper();
+        let history = DefaultHistory::new();
+        let (_, pairs) = helper
+            .complete(line, line.len(), &Context::new(&history))
+            .unwrap();
+        pairs.into_iter().map(|pair| pair.replacement).collect()
+    }
+
+    #[test]
+    fn completes_commands() {
+        assert_eq!(complete("se"), ["sensor", "session"]);
+        assert_eq!(complete("data l"), ["list"]);
+        assert_eq!(complete("use "), ["session", "none"]);
+    }
+
+    #[test]
+    fn completes_known_ids() {
+        assert_eq!(complete("sensor show "), ["t1", "t2"]);
+        assert_eq!(complete("link create s"), ["s1"]);
+        assert_eq!(complete("link create s1 t"), ["t1", "t2"]);
+        assert_eq!(complete("data list --session "), ["s1"]);
+        assert_eq!(complete("data add "), ["l1", "l2"]);
+        assert_eq!(complete("use session "), ["s1"]);
+        assert!(complete("sensor create ").is_empty());
+    }
+
+    #[test]
+    fn completes_options() {
+        assert_eq!(complete("data list --l"), ["--link"]);
+    }
+
+    #[test]
+    fn recognizes_lines_carrying_a_password() {
+        assert!(carries_password("login alice --password secret"));
+        assert!(carries_password("user update bob --password=secret"));
+        assert!(carries_password("login alice '--password' secret"));
+        assert!(!carries_password("login alice"));
+        assert!(!carries_password("user create bob --password-stdin"));
+    }
+
+    #[test]
+    fn defaults_data_list_to_the_session_in_use() {
+        let session = SessionId::new("s1");
+        let list = Command::Data(DataCommand::List {
+            session: None,
+            link: None,
+            after: None,
+        });
+        match default_session(list, Some(&session)) {
+            Command::Data(DataCommand::List { session, .. }) => {
+                assert_eq!(session.unwrap().as_str(), "s1")
+            }
+            command => panic!("unexpected {:?}", command),
+        }
+
+        let by_link = Command::Data(DataCommand::List {
+            session: None,
+            link: Some("l1".into()),
+            after: None,
+        });
+        match default_session(by_link, Some(&session)) {
+            Command::Data(DataCommand::List { session, .. }) => assert!(session.is_none()),
+            command => panic!("unexpected {:?}", command),
+        }
+    }
+}
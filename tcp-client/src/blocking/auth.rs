@@ -0,0 +1,30 @@
+//! Blocking requests for the authentication endpoint
+
+use super::block_on;
+use crate::api::auth;
+use crate::cookie::SessionCookie;
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::types::{AuthToken, Username};
+
+/// Send request to attempt login with provided user credentials
+pub fn user_login(
+    client: &Client,
+    username: &Username,
+    pw: &str,
+) -> Result<Option<SessionCookie>, ApiError> {
+    block_on(auth::user_login(client, username, pw))
+}
+
+/// Send request to log out the current user
+pub fn user_logout(
+    client: &Client,
+    auth_token: &AuthToken,
+) -> Result<Option<SessionCookie>, ApiError> {
+    block_on(auth::user_logout(client, auth_token))
+}
+
+/// Send request to renew session tokens
+pub fn renew_session(client: &Client, auth_token: &AuthToken) -> Result<SessionCookie, ApiError> {
+    block_on(auth::renew_session(client, auth_token))
+}
@@ -0,0 +1,35 @@
+//! Synchronous versions of the `api::*` functions
+//!
+//! Every function has the same parameters and return type as its async counterpart in `api`
+//! and blocks the calling thread until the request finishes. The requests run on a background
+//! runtime shared by the whole process, so pooled connections are reused between calls.
+//!
+//! The functions panic when called from within an async runtime; use the async functions
+//! there instead.
+
+pub mod auth;
+pub mod sensor;
+pub mod session;
+pub mod session_sensor;
+pub mod session_sensor_data;
+pub mod user;
+
+use std::future::Future;
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+/// Run a request to completion on the shared runtime
+fn block_on<F: Future>(future: F) -> F::Output {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    RUNTIME
+        .get_or_init(|| {
+            Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("tcp-client-blocking")
+                .enable_all()
+                .build()
+                .expect("failed to start the runtime for blocking requests")
+        })
+        .block_on(future)
+}
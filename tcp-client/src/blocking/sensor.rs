@@ -0,0 +1,49 @@
+//! Blocking requests for the sensor endpoint
+
+use super::block_on;
+use crate::api::sensor::{self, SensorList, SensorRecord};
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::types::{AuthToken, SensorId};
+
+/// Send request to create a new sensor
+pub fn create_sensor(
+    client: &Client,
+    auth_token: &AuthToken,
+    sensor_type: &str,
+) -> Result<SensorRecord, ApiError> {
+    block_on(sensor::create_sensor(client, auth_token, sensor_type))
+}
+
+/// Send request to get all sensors
+pub fn view_all_sensors(client: &Client, auth_token: &AuthToken) -> Result<SensorList, ApiError> {
+    block_on(sensor::view_all_sensors(client, auth_token))
+}
+
+/// Send request to get a specific sensor according to given ID
+pub fn view_sensor_by_id(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SensorId,
+) -> Result<SensorRecord, ApiError> {
+    block_on(sensor::view_sensor_by_id(client, auth_token, id))
+}
+
+/// Send request to partially or fully update a sensor
+pub fn update_sensor(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SensorId,
+    sensor_type: &str,
+) -> Result<SensorRecord, ApiError> {
+    block_on(sensor::update_sensor(client, auth_token, id, sensor_type))
+}
+
+/// Send request to delete a sensor according to given ID
+pub fn delete_sensor(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SensorId,
+) -> Result<(), ApiError> {
+    block_on(sensor::delete_sensor(client, auth_token, id))
+}
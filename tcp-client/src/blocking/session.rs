@@ -0,0 +1,53 @@
+//! Blocking requests for the session endpoint
+
+use super::block_on;
+use crate::api::session::{self, SessionList, SessionRecord};
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::types::{AuthToken, SessionId, Username};
+
+/// Send request to create a new session
+pub fn create_session(client: &Client, username: &Username) -> Result<SessionRecord, ApiError> {
+    block_on(session::create_session(client, username))
+}
+
+/// Send request to get all session
+pub fn view_all_sessions(client: &Client) -> Result<SessionList, ApiError> {
+    block_on(session::view_all_sessions(client))
+}
+
+/// Send request to get all sessions by user
+pub fn view_sessions_by_user(
+    client: &Client,
+    username: &Username,
+) -> Result<SessionList, ApiError> {
+    block_on(session::view_sessions_by_user(client, username))
+}
+
+/// Send request to get a specific session by ID
+pub fn view_session_by_id(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionId,
+) -> Result<SessionRecord, ApiError> {
+    block_on(session::view_session_by_id(client, auth_token, id))
+}
+
+/// Send request to partially or fully update a session
+pub fn update_session(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionId,
+    username: &Username,
+) -> Result<SessionRecord, ApiError> {
+    block_on(session::update_session(client, auth_token, id, username))
+}
+
+/// Send request to delete a session by ID
+pub fn delete_session(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionId,
+) -> Result<(), ApiError> {
+    block_on(session::delete_session(client, auth_token, id))
+}
@@ -0,0 +1,79 @@
+//! Blocking requests for the session sensor endpoint
+
+use super::block_on;
+use crate::api::session_sensor::{self, SessionSensorLink, SessionSensorLinkList};
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::types::{AuthToken, SensorId, SessionId, SessionSensorId};
+
+/// Send request to link a new sensor to a session
+pub fn create_session_sensor(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    block_on(session_sensor::create_session_sensor(
+        client, auth_token, session_id, sensor_id,
+    ))
+}
+
+/// Send request to get all session sensor linkages
+pub fn view_all_sensor_sessions(
+    client: &Client,
+    auth_token: &AuthToken,
+) -> Result<SessionSensorLinkList, ApiError> {
+    block_on(session_sensor::view_all_sensor_sessions(client, auth_token))
+}
+
+/// Send request to get all sensors linked to a specific session
+pub fn view_sensors_by_session_id(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+) -> Result<SessionSensorLinkList, ApiError> {
+    block_on(session_sensor::view_sensors_by_session_id(
+        client, auth_token, session_id,
+    ))
+}
+
+/// Send request to get a specific session sensor linkage by sensor ID
+pub fn view_session_sensor_by_sensor_id(
+    client: &Client,
+    auth_token: &AuthToken,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    block_on(session_sensor::view_session_sensor_by_sensor_id(
+        client, auth_token, sensor_id,
+    ))
+}
+
+/// Send request to partially or fully udpate a session sensor link
+pub fn update_sensor_session(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_sensor_id: &SessionSensorId,
+    session_id: &SessionId,
+    sensor_id: &SensorId,
+) -> Result<SessionSensorLink, ApiError> {
+    block_on(session_sensor::update_sensor_session(
+        client,
+        auth_token,
+        session_sensor_id,
+        session_id,
+        sensor_id,
+    ))
+}
+
+/// Send request to delete a session sensor linkage by ID
+pub fn delete_sensor_session(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_sensor_id: &SessionSensorId,
+) -> Result<(), ApiError> {
+    block_on(session_sensor::delete_sensor_session(
+        client,
+        auth_token,
+        session_sensor_id,
+    ))
+}
@@ -0,0 +1,112 @@
+//! Blocking requests for the session sensor data endpoint
+
+use super::block_on;
+use crate::api::session_sensor_data::{self, Datapoint, DatapointList, SessionSensorData};
+use crate::batch::{BatchError, BatchReport};
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::timestamp::Timestamp;
+use crate::types::{AuthToken, SessionId, SessionSensorId};
+
+/// Send request to create a new datapoint
+pub fn create_datapoint(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+    data_blob: &str,
+) -> Result<Datapoint, ApiError> {
+    block_on(session_sensor_data::create_datapoint(
+        client, auth_token, id, datetime, data_blob,
+    ))
+}
+
+/// Send requests to batch create new datapoints, split into chunks the server accepts
+pub fn batch_create_datapoint(
+    client: &Client,
+    auth_token: &AuthToken,
+    datapoints: Vec<SessionSensorData>,
+) -> Result<BatchReport, BatchError> {
+    block_on(session_sensor_data::batch_create_datapoint(
+        client, auth_token, datapoints,
+    ))
+}
+
+/// Send request to get all datapoints
+pub fn view_all_datapoints(
+    client: &Client,
+    auth_token: &AuthToken,
+) -> Result<DatapointList, ApiError> {
+    block_on(session_sensor_data::view_all_datapoints(client, auth_token))
+}
+
+/// Send request to get all datapoints linked to a given session
+pub fn view_datapoints_by_session_id(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+) -> Result<DatapointList, ApiError> {
+    block_on(session_sensor_data::view_datapoints_by_session_id(
+        client, auth_token, session_id,
+    ))
+}
+
+/// Send request to get all datapoints by session sensor ID
+pub fn view_datapoints_by_session_sensor(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+) -> Result<DatapointList, ApiError> {
+    block_on(session_sensor_data::view_datapoints_by_session_sensor(
+        client, auth_token, id,
+    ))
+}
+
+/// Send request to get a specific datapoint
+pub fn view_datapoints_by_id_datetime(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+) -> Result<Datapoint, ApiError> {
+    block_on(session_sensor_data::view_datapoints_by_id_datetime(
+        client, auth_token, id, datetime,
+    ))
+}
+
+/// Send request to get all datapoints after specific datetime
+pub fn view_all_datapoints_by_id_datetime(
+    client: &Client,
+    auth_token: &AuthToken,
+    session_id: &SessionId,
+    datetime: Timestamp,
+) -> Result<DatapointList, ApiError> {
+    block_on(session_sensor_data::view_all_datapoints_by_id_datetime(
+        client, auth_token, session_id, datetime,
+    ))
+}
+
+/// Send request to partially or fully udpate a specific datapoint
+pub fn update_datapoint(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+    data_blob: &str,
+) -> Result<Datapoint, ApiError> {
+    block_on(session_sensor_data::update_datapoint(
+        client, auth_token, id, datetime, data_blob,
+    ))
+}
+
+/// Send request to delete a specific datapoint
+pub fn delete_datapoint(
+    client: &Client,
+    auth_token: &AuthToken,
+    id: &SessionSensorId,
+    datetime: Timestamp,
+) -> Result<(), ApiError> {
+    block_on(session_sensor_data::delete_datapoint(
+        client, auth_token, id, datetime,
+    ))
+}
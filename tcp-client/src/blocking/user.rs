@@ -0,0 +1,37 @@
+//! Blocking requests for the user endpoint
+
+use super::block_on;
+use crate::api::user::{self, UserInfo, UserList};
+use crate::error::ApiError;
+use crate::reqwest::Client;
+use crate::types::{AuthToken, Username};
+
+/// Send request to create a new user
+pub fn create_user(client: &Client, username: &Username, pw: &str) -> Result<UserInfo, ApiError> {
+    block_on(user::create_user(client, username, pw))
+}
+
+/// Send request to get all users
+pub fn view_all_users(client: &Client, auth_token: &AuthToken) -> Result<UserList, ApiError> {
+    block_on(user::view_all_users(client, auth_token))
+}
+
+/// Send request to get user currently loggged in
+pub fn view_user_profile(client: &Client, auth_token: &AuthToken) -> Result<UserInfo, ApiError> {
+    block_on(user::view_user_profile(client, auth_token))
+}
+
+/// Send request to get a specific user by username
+pub fn view_user_by_username(client: &Client, username: &Username) -> Result<UserInfo, ApiError> {
+    block_on(user::view_user_by_username(client, username))
+}
+
+/// Send request to partially or fully update a user
+pub fn update_user(client: &Client, username: &Username, pw: &str) -> Result<UserInfo, ApiError> {
+    block_on(user::update_user(client, username, pw))
+}
+
+/// Send request to delete a user by username
+pub fn delete_user(client: &Client, username: &Username) -> Result<(), ApiError> {
+    block_on(user::delete_user(client, username))
+}
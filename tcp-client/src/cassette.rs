@@ -0,0 +1,629 @@
+//! Recording traffic to cassette files and replaying it without a server
+//!
+//! A `RecordingTransport` wraps the real transport and keeps every request (method, URL,
+//! cookie, body) and response (status, headers, body) it sees; `save` writes them to a JSON
+//! cassette file. A `ReplayTransport` loads the cassette and answers each request with the
+//! response of a matching recorded one, failing with `ApiError::NotRecorded` when none matches.
+//!
+//! Secrets never reach the file: by default the value of the `session_id` cookie (in `Cookie`
+//! and `Set-Cookie` headers) and every `password_hash` field of a JSON body are replaced with
+//! `<redacted>`. Requests are redacted the same way before they are matched during replay.
+//!
+//! ```ignore
+//! // Once, against staging
+//! let recorder = Arc::new(RecordingTransport::new(ReqwestTransport::default()));
+//! let client = ApiClient::builder().transport(recorder.clone()).build();
+//! // ... make requests ...
+//! recorder.save("tests/cassettes/sensors.json")?;
+//!
+//! // In CI
+//! let replay = Arc::new(ReplayTransport::load("tests/cassettes/sensors.json")?);
+//! let client = ApiClient::builder().transport(replay.clone()).build();
+//! ```
+
+use crate::error::ApiError;
+use crate::requests::transport::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use crate::reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, COOKIE, SET_COOKIE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Value that replaces secrets
+pub const REDACTED: &str = "<redacted>";
+
+/// Error reading or writing a cassette file
+#[derive(Debug)]
+pub enum CassetteError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for CassetteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CassetteError::Io { path, source } => {
+                write!(
+                    f,
+                    "failed to access cassette {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            CassetteError::Parse { path, source } => {
+                write!(f, "invalid cassette {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl Error for CassetteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CassetteError::Io { source, .. } => Some(source),
+            CassetteError::Parse { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Recorded traffic, as stored in a cassette file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Read a cassette file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CassetteError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|source| CassetteError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&json).map_err(|source| CassetteError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Write the cassette to a file, creating its directory if needed
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CassetteError> {
+        let path = path.as_ref();
+        let io_error = |source| CassetteError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let mut json =
+            serde_json::to_string_pretty(self).map_err(|source| CassetteError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        json.push('\n');
+        std::fs::write(path, json).map_err(io_error)
+    }
+}
+
+/// One request and the response it got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Request as stored in a cassette
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub cookie: Option<String>,
+    pub body: Option<Body>,
+}
+
+/// Response as stored in a cassette
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// Header names and values, in the order they were received
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Body>,
+}
+
+/// Body of a recorded request or response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Body {
+    /// A JSON body, stored as JSON so the cassette stays readable
+    Json(Value),
+    /// Any other body
+    Text(String),
+}
+
+impl Body {
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        Some(match serde_json::from_slice(bytes) {
+            Ok(json) => Body::Json(json),
+            Err(_) => Body::Text(String::from_utf8_lossy(bytes).into_owned()),
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Body::Json(json) => json.to_string().into_bytes(),
+            Body::Text(text) => text.clone().into_bytes(),
+        }
+    }
+}
+
+/// Which cookies and JSON fields are replaced with `<redacted>`
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    cookies: Vec<String>,
+    body_fields: Vec<String>,
+}
+
+impl Default for Redaction {
+    /// Redact the `session_id` cookie and `password_hash` fields
+    fn default() -> Self {
+        Self::none()
+            .cookie("session_id")
+            .body_field("password_hash")
+    }
+}
+
+impl Redaction {
+    /// Redact nothing
+    pub fn none() -> Self {
+        Self {
+            cookies: Vec::new(),
+            body_fields: Vec::new(),
+        }
+    }
+
+    /// Also redact the value of a cookie
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        self.cookies.push(name.into());
+        self
+    }
+
+    /// Also redact a field of JSON bodies, at any depth
+    pub fn body_field(mut self, name: impl Into<String>) -> Self {
+        self.body_fields.push(name.into());
+        self
+    }
+
+    fn request(&self, request: &HttpRequest) -> RecordedRequest {
+        RecordedRequest {
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            cookie: request
+                .headers
+                .get(COOKIE)
+                .map(|cookie| self.cookie_header(&String::from_utf8_lossy(cookie.as_bytes()))),
+            body: Body::from_bytes(&request.body).map(|body| self.body(body)),
+        }
+    }
+
+    fn response(&self, response: &HttpResponse) -> RecordedResponse {
+        RecordedResponse {
+            status: response.status.as_u16(),
+            headers: response
+                .headers
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    let value = if name == SET_COOKIE {
+                        self.set_cookie_header(&value)
+                    } else {
+                        value
+                    };
+                    (name.to_string(), value)
+                })
+                .collect(),
+            body: Body::from_bytes(&response.body).map(|body| self.body(body)),
+        }
+    }
+
+    /// Redact the cookies of a `Cookie` header, `name=value; name=value`
+    fn cookie_header(&self, header: &str) -> String {
+        header
+            .split(';')
+            .map(|pair| self.cookie_pair(pair.trim()))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Redact the cookie of a `Set-Cookie` header, keeping its attributes
+    fn set_cookie_header(&self, header: &str) -> String {
+        match header.split_once(';') {
+            Some((pair, attributes)) => format!("{};{}", self.cookie_pair(pair.trim()), attributes),
+            None => self.cookie_pair(header.trim()),
+        }
+    }
+
+    fn cookie_pair(&self, pair: &str) -> String {
+        match pair.split_once('=') {
+            Some((name, value)) if !value.is_empty() && self.cookies.iter().any(|c| c == name) => {
+                format!("{}={}", name, REDACTED)
+            }
+            _ => pair.to_string(),
+        }
+    }
+
+    fn body(&self, body: Body) -> Body {
+        match body {
+            Body::Json(mut json) => {
+                replace_fields(&mut json, &self.body_fields);
+                Body::Json(json)
+            }
+            text => text,
+        }
+    }
+}
+
+/// Replace the fields named in `fields` with `<redacted>`, at any depth
+fn replace_fields(json: &mut Value, fields: &[String]) {
+    match json {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if fields.contains(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    replace_fields(value, fields);
+                }
+            }
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| replace_fields(value, fields)),
+        _ => {}
+    }
+}
+
+/// Remove the fields named in `fields`, at any depth
+fn remove_fields(json: &mut Value, fields: &[String]) {
+    match json {
+        Value::Object(object) => {
+            object.retain(|key, _| !fields.contains(key));
+            object
+                .values_mut()
+                .for_each(|value| remove_fields(value, fields));
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| remove_fields(value, fields)),
+        _ => {}
+    }
+}
+
+/// Transport that records the traffic of another transport
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    redaction: Redaction,
+    cassette: Mutex<Cassette>,
+}
+
+impl RecordingTransport {
+    /// Record the traffic of `inner`, redacting it with `Redaction::default`
+    pub fn new(inner: impl Transport + 'static) -> Self {
+        Self::with_redaction(Arc::new(inner), Redaction::default())
+    }
+
+    /// Record the traffic of a shared transport with custom redaction
+    pub fn with_redaction(inner: Arc<dyn Transport>, redaction: Redaction) -> Self {
+        Self {
+            inner,
+            redaction,
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Get the interactions recorded so far
+    pub fn cassette(&self) -> Cassette {
+        self.cassette
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Write the interactions recorded so far to a cassette file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CassetteError> {
+        self.cassette().save(path)
+    }
+}
+
+impl Transport for RecordingTransport {
+    fn send(&self, request: HttpRequest, read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let recorded = self.redaction.request(&request);
+            // Requests that got no response, e.g. because of a timeout, are not recorded
+            let response = self.inner.send(request, read_timeout).await?;
+            self.cassette
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .interactions
+                .push(Interaction {
+                    request: recorded,
+                    response: self.redaction.response(&response),
+                });
+            Ok(response)
+        })
+    }
+}
+
+/// Which parts of a request must equal a recorded one for it to be replayed
+///
+/// The method and URL always have to match. By default the body has to match as well, the
+/// cookie does not, and recorded interactions are used once each, in any order.
+#[derive(Debug, Clone)]
+pub struct MatchRules {
+    cookie: bool,
+    body: bool,
+    ignored_fields: Vec<String>,
+    in_order: bool,
+    repeat: bool,
+}
+
+impl Default for MatchRules {
+    fn default() -> Self {
+        Self {
+            cookie: false,
+            body: true,
+            ignored_fields: Vec::new(),
+            in_order: false,
+            repeat: false,
+        }
+    }
+}
+
+impl MatchRules {
+    /// Require the (redacted) `Cookie` header to match
+    pub fn cookie(mut self, cookie: bool) -> Self {
+        self.cookie = cookie;
+        self
+    }
+
+    /// Require the body to match
+    pub fn body(mut self, body: bool) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Leave a JSON field out when comparing bodies, e.g. a timestamp that changes every run
+    pub fn ignore_field(mut self, name: impl Into<String>) -> Self {
+        self.ignored_fields.push(name.into());
+        self
+    }
+
+    /// Only match the next unused interaction, so requests must come in the recorded order
+    pub fn in_order(mut self, in_order: bool) -> Self {
+        self.in_order = in_order;
+        self
+    }
+
+    /// Allow an interaction to answer more than one request
+    pub fn repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    fn matches(&self, recorded: &RecordedRequest, request: &RecordedRequest) -> bool {
+        recorded.method == request.method
+            && recorded.url == request.url
+            && (!self.cookie || recorded.cookie == request.cookie)
+            && (!self.body || self.comparable(&recorded.body) == self.comparable(&request.body))
+    }
+
+    fn comparable(&self, body: &Option<Body>) -> Option<Body> {
+        match body {
+            Some(Body::Json(json)) => {
+                let mut json = json.clone();
+                remove_fields(&mut json, &self.ignored_fields);
+                Some(Body::Json(json))
+            }
+            body => body.clone(),
+        }
+    }
+}
+
+/// Transport answering requests from a cassette
+#[derive(Debug)]
+pub struct ReplayTransport {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+    rules: MatchRules,
+    redaction: Redaction,
+}
+
+impl ReplayTransport {
+    /// Replay a cassette file with the default rules and redaction
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CassetteError> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Replay a cassette with the default rules and redaction
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            used: Mutex::new(vec![false; cassette.interactions.len()]),
+            interactions: cassette.interactions,
+            rules: MatchRules::default(),
+            redaction: Redaction::default(),
+        }
+    }
+
+    /// Set which parts of a request have to match
+    pub fn rules(mut self, rules: MatchRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Set the redaction the cassette was recorded with
+    pub fn redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = redaction;
+        self
+    }
+
+    /// Get the number of interactions that have not answered a request yet
+    pub fn remaining(&self) -> usize {
+        self.used
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|used| !**used)
+            .count()
+    }
+
+    /// Panic if some interactions have not answered a request
+    pub fn assert_done(&self) {
+        // Copied so the lock is not held while panicking
+        let used = self.used.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let unused: Vec<String> = self
+            .interactions
+            .iter()
+            .zip(used.iter())
+            .filter(|(_, used)| !**used)
+            .map(|(interaction, _)| {
+                format!("{} {}", interaction.request.method, interaction.request.url)
+            })
+            .collect();
+        if !unused.is_empty() {
+            panic!(
+                "ReplayTransport: {} recorded requests were not sent: {}",
+                unused.len(),
+                unused.join(", ")
+            );
+        }
+    }
+
+    /// Find the interaction answering `request` and mark it as used
+    fn answer(&self, request: &RecordedRequest) -> Option<&RecordedResponse> {
+        let mut used = self.used.lock().unwrap_or_else(|e| e.into_inner());
+        let mut candidates = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.rules.repeat || !used[*i]);
+        let (i, interaction) = if self.rules.in_order {
+            candidates
+                .next()
+                .filter(|(_, interaction)| self.rules.matches(&interaction.request, request))?
+        } else {
+            candidates.find(|(_, interaction)| self.rules.matches(&interaction.request, request))?
+        };
+        used[i] = true;
+        Some(&interaction.response)
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn send(&self, request: HttpRequest, _read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        let recorded = self.redaction.request(&request);
+        let result = match self.answer(&recorded) {
+            Some(response) => Ok(replayed_response(response)),
+            None => Err(ApiError::NotRecorded(format!(
+                "{} {} cookie={:?} body={:?}",
+                recorded.method, recorded.url, recorded.cookie, recorded.body
+            ))),
+        };
+        Box::pin(async move { result })
+    }
+}
+
+/// Rebuild a response from its recording, skipping headers that are no longer valid
+fn replayed_response(recorded: &RecordedResponse) -> HttpResponse {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &recorded.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    HttpResponse {
+        status: StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        headers,
+        body: recorded
+            .body
+            .as_ref()
+            .map(Body::to_bytes)
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_only_the_named_cookies() {
+        let redaction = Redaction::default();
+        assert_eq!(
+            redaction.cookie_header("theme=dark; session_id=abc"),
+            "theme=dark; session_id=<redacted>"
+        );
+        assert_eq!(
+            redaction.set_cookie_header("session_id=abc; Path=/; Max-Age=60"),
+            "session_id=<redacted>; Path=/; Max-Age=60"
+        );
+        // Clearing the cookie reveals nothing
+        assert_eq!(
+            redaction.set_cookie_header("session_id=; Max-Age=0"),
+            "session_id=; Max-Age=0"
+        );
+    }
+
+    #[test]
+    fn redacts_fields_at_any_depth() {
+        let body = Body::Json(json!({
+            "username": "alice",
+            "password_hash": "pw",
+            "users": [{ "password_hash": "pw2" }],
+        }));
+        assert_eq!(
+            Redaction::default().body(body),
+            Body::Json(json!({
+                "username": "alice",
+                "password_hash": REDACTED,
+                "users": [{ "password_hash": REDACTED }],
+            }))
+        );
+    }
+
+    #[test]
+    fn ignored_fields_do_not_count_when_matching() {
+        let request = |datetime: &str| RecordedRequest {
+            method: "POST".to_string(),
+            url: "http://backend.test/sessions-sensors-data".to_string(),
+            cookie: None,
+            body: Some(Body::Json(json!({ "id": "1", "datetime": datetime }))),
+        };
+        let recorded = request("2025-01-01T00:00:00.000000Z");
+        let sent = request("2025-06-01T00:00:00.000000Z");
+
+        assert!(!MatchRules::default().matches(&recorded, &sent));
+        assert!(MatchRules::default()
+            .ignore_field("datetime")
+            .matches(&recorded, &sent));
+    }
+}
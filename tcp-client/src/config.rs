@@ -0,0 +1,921 @@
+//! Client configuration loaded from defaults, a TOML file, the environment and code
+
+use crate::batch::BatchLimits;
+use crate::path::{BaseUrl, InvalidBaseUrl};
+use crate::requests::limits::{GroupLimits, RateLimits};
+use crate::requests::retry::RetryPolicy;
+use crate::requests::timeout::Timeouts;
+use crate::reqwest::header::HeaderValue;
+use crate::uploader::UploaderConfig;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Base URL of the dev profile when none is configured
+const DEV_BASE_URL: &str = "http://127.0.0.1:7878";
+
+/// Named set of settings, selected at runtime
+///
+/// Each profile can override the shared settings of the config file in its own
+/// `[profiles.<name>]` table. Only `Dev` has a built-in base URL, the local backend at
+/// `http://127.0.0.1:7878`; the others need one from the config file or `API_BASE_URL`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    #[default]
+    Dev,
+    Staging,
+    Prod,
+}
+
+impl Profile {
+    /// Get the name used in config files and `API_PROFILE`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Profile::Dev => "dev",
+            Profile::Staging => "staging",
+            Profile::Prod => "prod",
+        }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Profile {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dev" => Ok(Profile::Dev),
+            "staging" => Ok(Profile::Staging),
+            "prod" => Ok(Profile::Prod),
+            _ => Err(ConfigError::UnknownProfile(s.to_string())),
+        }
+    }
+}
+
+/// Errors that can occur while loading a `ClientConfig`
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file could not be read
+    Read { path: PathBuf, source: io::Error },
+    /// The config file is not valid TOML or contains unknown keys
+    Parse { origin: String, message: String },
+    /// A setting has a malformed or out of range value
+    InvalidValue {
+        /// The config file or `environment`
+        origin: String,
+        /// Key in the config file, or name of the environment variable
+        key: String,
+        message: String,
+    },
+    /// The selected profile is not one of dev, staging or prod
+    UnknownProfile(String),
+    /// No base URL is configured for a profile without a built-in one
+    MissingBaseUrl(Profile),
+    /// The configured base URL is not a valid `http` or `https` URL
+    BaseUrl(InvalidBaseUrl),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(
+                    f,
+                    "failed to read config file {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            ConfigError::Parse { origin, message } => write!(f, "invalid {}: {}", origin, message),
+            ConfigError::InvalidValue {
+                origin,
+                key,
+                message,
+            } => write!(f, "invalid value for `{}` in {}: {}", key, origin, message),
+            ConfigError::UnknownProfile(name) => write!(
+                f,
+                "unknown profile `{}`, expected dev, staging or prod",
+                name
+            ),
+            ConfigError::MissingBaseUrl(profile) => write!(
+                f,
+                "no base URL configured for the {} profile, set `base_url` in the config file \
+                 or API_BASE_URL",
+                profile
+            ),
+            ConfigError::BaseUrl(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::BaseUrl(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for the client and the datapoint uploader
+///
+/// `ClientConfig::load` reads them in layers, each overriding the one before:
+///
+/// 1. built-in defaults of the profile
+/// 2. the shared settings of the TOML config file, if any
+/// 3. the `[profiles.<name>]` table of the selected profile in that file
+/// 4. environment variables, including a `.env` file
+///
+/// The fields are public, so code can override anything afterwards. Pass the config to
+/// `ApiClientBuilder::config` and `DatapointUploader::new` to use it.
+///
+/// ```toml
+/// user_agent = "field-logger/1.2"
+///
+/// [timeouts]
+/// read = "10s"
+/// total = "none"
+///
+/// [retry]
+/// max_attempts = 5
+/// base_delay = "250ms"
+///
+/// [batch]
+/// max_len = 200
+///
+/// [limits.batch]
+/// rate = 2.0
+/// burst = 4
+/// max_in_flight = 1
+///
+/// [profiles.prod]
+/// base_url = "https://api.example.com"
+/// ```
+///
+/// The environment variables are `API_BASE_URL`, `API_USER_AGENT`, `API_CONNECT_TIMEOUT`,
+/// `API_READ_TIMEOUT`, `API_TOTAL_TIMEOUT`, `API_RETRY_MAX_ATTEMPTS`, `API_RETRY_BASE_DELAY`,
+/// `API_RETRY_MAX_DELAY`, `API_RETRY_JITTER`, `API_RETRY_IDEMPOTENCY_KEYS`, `API_BATCH_MAX_LEN`,
+/// `API_BATCH_MAX_BYTES`, `API_BATCH_MAX_CONCURRENCY`, `API_BATCH_FLUSH_INTERVAL` and
+/// `API_BATCH_CHANNEL_CAPACITY`.
+/// `API_PROFILE` selects the profile and `API_CONFIG` the config file. Rate limits are only
+/// read from the config file, in the `[limits.<group>]` tables of the `auth`, `reads`,
+/// `writes` and `batch` route groups.
+///
+/// Durations are written with a unit, e.g. `500ms`, `30s`, `2m` or `1h`. Timeouts can be
+/// disabled with `none`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientConfig {
+    /// Profile the settings were loaded for
+    pub profile: Profile,
+    /// URL all requests are sent to
+    pub base_url: BaseUrl,
+    /// Value of the `User-Agent` header sent with every request
+    pub user_agent: String,
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy,
+    /// Batch sizes and flush interval of the datapoint uploader
+    pub uploader: UploaderConfig,
+    /// Chunk sizes and concurrency of `Datapoints::batch_create`, sharing `max_len` and
+    /// `max_bytes` of the `[batch]` table with the uploader
+    pub batch: BatchLimits,
+    /// Request rates and requests in flight allowed per route group, unlimited by default
+    pub limits: RateLimits,
+}
+
+impl ClientConfig {
+    /// Create a loader for choosing the profile, config file and layers
+    pub fn loader() -> ConfigLoader {
+        ConfigLoader::default()
+    }
+
+    /// Load the config for the profile in `API_PROFILE` from the file in `API_CONFIG` and
+    /// the environment
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::loader().load()
+    }
+
+    /// Get the built-in defaults of a profile
+    ///
+    /// All profiles start out pointing at the local backend, but `load` only keeps that base
+    /// URL for `Dev`.
+    pub fn defaults(profile: Profile) -> Self {
+        Self {
+            profile,
+            base_url: BaseUrl::parse(DEV_BASE_URL).expect("built-in base URL is valid"),
+            user_agent: default_user_agent(),
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::default(),
+            uploader: UploaderConfig::default(),
+            batch: BatchLimits::default(),
+            limits: RateLimits::default(),
+        }
+    }
+
+    /// Get the config loaded from the environment, reading it only on the first call
+    ///
+    /// Used by clients built without a base URL, including those behind the free endpoint
+    /// functions. Failed loads are not remembered, so a fixed environment is picked up.
+    pub(crate) fn from_environment() -> Result<Self, ConfigError> {
+        static LOADED: OnceLock<ClientConfig> = OnceLock::new();
+
+        if let Some(config) = LOADED.get() {
+            return Ok(config.clone());
+        }
+        let config = Self::load()?;
+        Ok(LOADED.get_or_init(|| config).clone())
+    }
+
+    /// Apply a layer of settings on top of this config
+    fn apply(&mut self, layer: Layer) {
+        let timeouts = layer.timeouts;
+        if let Some(connect) = timeouts.connect {
+            self.timeouts.connect = connect;
+        }
+        if let Some(read) = timeouts.read {
+            self.timeouts.read = read;
+        }
+        if let Some(total) = timeouts.total {
+            self.timeouts.total = total;
+        }
+
+        let retry = layer.retry;
+        let mut retry_policy = self.retry_policy.clone();
+        if let Some(max_attempts) = retry.max_attempts {
+            retry_policy = retry_policy.max_attempts(max_attempts);
+        }
+        if let Some(base_delay) = retry.base_delay {
+            retry_policy = retry_policy.base_delay(base_delay);
+        }
+        if let Some(max_delay) = retry.max_delay {
+            retry_policy = retry_policy.max_delay(max_delay);
+        }
+        if let Some(jitter) = retry.jitter {
+            retry_policy = retry_policy.jitter(jitter);
+        }
+        if let Some(idempotency_keys) = retry.idempotency_keys {
+            retry_policy = retry_policy.idempotency_keys(idempotency_keys);
+        }
+        self.retry_policy = retry_policy;
+
+        let batch = layer.batch;
+        if let Some(max_len) = batch.max_len {
+            self.uploader.max_batch_len = max_len;
+            self.batch.max_len = max_len;
+        }
+        if let Some(max_bytes) = batch.max_bytes {
+            self.uploader.max_batch_bytes = max_bytes;
+            self.batch.max_bytes = max_bytes;
+        }
+        if let Some(max_concurrency) = batch.max_concurrency {
+            self.batch.max_concurrency = max_concurrency;
+        }
+        if let Some(flush_interval) = batch.flush_interval {
+            self.uploader.flush_interval = flush_interval;
+        }
+        if let Some(channel_capacity) = batch.channel_capacity {
+            self.uploader.channel_capacity = channel_capacity;
+        }
+
+        let limits = layer.limits;
+        for (group, layer) in [
+            (&mut self.limits.auth, limits.auth),
+            (&mut self.limits.reads, limits.reads),
+            (&mut self.limits.writes, limits.writes),
+            (&mut self.limits.batch, limits.batch),
+        ] {
+            layer.apply(group);
+        }
+
+        if let Some(user_agent) = layer.user_agent {
+            self.user_agent = user_agent;
+        }
+    }
+}
+
+/// Get the `User-Agent` sent when none is configured, e.g. `tcp-client/0.1.0`
+pub(crate) fn default_user_agent() -> String {
+    format!("tcp-client/{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Loader for a `ClientConfig`, created with `ClientConfig::loader`
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    profile: Option<Profile>,
+    file: Option<PathBuf>,
+    toml: Option<String>,
+    environment: bool,
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            file: None,
+            toml: None,
+            environment: true,
+        }
+    }
+}
+
+impl ConfigLoader {
+    /// Select the profile instead of reading `API_PROFILE` (dev if neither is set)
+    pub fn profile(mut self, profile: Profile) -> Self {
+        self.profile = Some(profile);
+        self
+    }
+
+    /// Read the config file at `path` instead of the one in `API_CONFIG`
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Use config file contents directly, e.g. on platforms without a file system
+    pub fn toml(mut self, toml: impl Into<String>) -> Self {
+        self.toml = Some(toml.into());
+        self
+    }
+
+    /// Enable or disable reading environment variables and `.env` (enabled by default)
+    pub fn environment(mut self, environment: bool) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Load the config
+    pub fn load(self) -> Result<ClientConfig, ConfigError> {
+        if self.environment {
+            dotenv::dotenv().ok();
+        }
+        self.load_with(|name| std::env::var(name).ok())
+    }
+
+    /// Load the config, looking up environment variables with `var`
+    fn load_with(self, var: impl Fn(&str) -> Option<String>) -> Result<ClientConfig, ConfigError> {
+        let var = |name: &str| {
+            if self.environment {
+                var(name).filter(|value| !value.trim().is_empty())
+            } else {
+                None
+            }
+        };
+
+        let profile = match (self.profile, var("API_PROFILE")) {
+            (Some(profile), _) => profile,
+            (None, Some(name)) => name.parse()?,
+            (None, None) => Profile::default(),
+        };
+
+        let file = match (&self.toml, &self.file, var("API_CONFIG")) {
+            (Some(toml), _, _) => Some(ConfigFile::parse(toml, "config")?),
+            (None, Some(path), _) => Some(ConfigFile::read(path)?),
+            (None, None, Some(path)) => Some(ConfigFile::read(Path::new(&path))?),
+            (None, None, None) => None,
+        };
+
+        let mut config = ClientConfig::defaults(profile);
+        let mut base_url = (profile == Profile::Dev).then(|| DEV_BASE_URL.to_string());
+        let mut layers = Vec::new();
+        if let Some(file) = file {
+            layers.push(file.shared);
+            layers.extend(file.profiles.take(profile));
+        }
+        layers.push(Layer::from_env(var)?);
+
+        for mut layer in layers {
+            if let Some(url) = layer.base_url.take() {
+                base_url = Some(url);
+            }
+            config.apply(layer);
+        }
+
+        let base_url = base_url.ok_or(ConfigError::MissingBaseUrl(profile))?;
+        config.base_url = BaseUrl::parse(&base_url).map_err(ConfigError::BaseUrl)?;
+        Ok(config)
+    }
+}
+
+/// Contents of a config file
+struct ConfigFile {
+    shared: Layer,
+    profiles: Profiles,
+}
+
+impl ConfigFile {
+    fn read(path: &Path) -> Result<Self, ConfigError> {
+        let toml = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&toml, &format!("config file {}", path.display()))
+    }
+
+    fn parse(toml: &str, origin: &str) -> Result<Self, ConfigError> {
+        let mut shared: Layer = toml::from_str(toml).map_err(|e| ConfigError::Parse {
+            origin: origin.to_string(),
+            message: e.to_string(),
+        })?;
+        let profiles = shared
+            .profiles
+            .take()
+            .map_or_else(Profiles::default, |profiles| *profiles);
+
+        shared.validate(origin, "")?;
+        for (profile, layer) in profiles.iter() {
+            let prefix = format!("profiles.{}.", profile);
+            if layer.profiles.is_some() {
+                return Err(ConfigError::Parse {
+                    origin: origin.to_string(),
+                    message: format!("profiles cannot be nested in `profiles.{}`", profile),
+                });
+            }
+            layer.validate(origin, &prefix)?;
+        }
+        Ok(Self { shared, profiles })
+    }
+}
+
+/// One layer of settings, where unset values keep those of the layers below
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    base_url: Option<String>,
+    user_agent: Option<String>,
+    timeouts: TimeoutsLayer,
+    retry: RetryLayer,
+    batch: BatchLayer,
+    limits: LimitsLayer,
+    profiles: Option<Box<Profiles>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TimeoutsLayer {
+    #[serde(deserialize_with = "deserialize_limit")]
+    connect: Option<Option<Duration>>,
+    #[serde(deserialize_with = "deserialize_limit")]
+    read: Option<Option<Duration>>,
+    #[serde(deserialize_with = "deserialize_limit")]
+    total: Option<Option<Duration>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RetryLayer {
+    max_attempts: Option<u32>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    base_delay: Option<Duration>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    max_delay: Option<Duration>,
+    jitter: Option<f64>,
+    idempotency_keys: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BatchLayer {
+    max_len: Option<usize>,
+    max_bytes: Option<usize>,
+    max_concurrency: Option<usize>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    flush_interval: Option<Duration>,
+    channel_capacity: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LimitsLayer {
+    auth: GroupLimitsLayer,
+    reads: GroupLimitsLayer,
+    writes: GroupLimitsLayer,
+    batch: GroupLimitsLayer,
+}
+
+impl LimitsLayer {
+    fn iter(&self) -> impl Iterator<Item = (&'static str, &GroupLimitsLayer)> {
+        [
+            ("auth", &self.auth),
+            ("reads", &self.reads),
+            ("writes", &self.writes),
+            ("batch", &self.batch),
+        ]
+        .into_iter()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GroupLimitsLayer {
+    rate: Option<f64>,
+    burst: Option<u32>,
+    max_in_flight: Option<usize>,
+}
+
+impl GroupLimitsLayer {
+    fn apply(self, limits: &mut GroupLimits) {
+        if let Some(rate) = self.rate {
+            *limits = limits.rate(rate);
+        }
+        if let Some(burst) = self.burst {
+            *limits = limits.burst(burst);
+        }
+        if let Some(max_in_flight) = self.max_in_flight {
+            *limits = limits.max_in_flight(max_in_flight);
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Profiles {
+    dev: Option<Layer>,
+    staging: Option<Layer>,
+    prod: Option<Layer>,
+}
+
+impl Profiles {
+    fn iter(&self) -> impl Iterator<Item = (Profile, &Layer)> {
+        [
+            (Profile::Dev, &self.dev),
+            (Profile::Staging, &self.staging),
+            (Profile::Prod, &self.prod),
+        ]
+        .into_iter()
+        .filter_map(|(profile, layer)| Some((profile, layer.as_ref()?)))
+    }
+
+    fn take(self, profile: Profile) -> Option<Layer> {
+        match profile {
+            Profile::Dev => self.dev,
+            Profile::Staging => self.staging,
+            Profile::Prod => self.prod,
+        }
+    }
+}
+
+impl Layer {
+    /// Read the settings from environment variables looked up with `var`
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        fn parse<T>(
+            var: &impl Fn(&str) -> Option<String>,
+            name: &str,
+            parse: impl Fn(&str) -> Result<T, String>,
+        ) -> Result<Option<T>, ConfigError> {
+            var(name)
+                .map(|value| {
+                    parse(value.trim()).map_err(|message| ConfigError::InvalidValue {
+                        origin: "environment".to_string(),
+                        key: name.to_string(),
+                        message: format!("`{}` {}", value, message),
+                    })
+                })
+                .transpose()
+        }
+
+        Ok(Self {
+            base_url: var("API_BASE_URL"),
+            user_agent: parse(&var, "API_USER_AGENT", |value| {
+                check_user_agent(value).map(|()| value.to_string())
+            })?,
+            timeouts: TimeoutsLayer {
+                connect: parse(&var, "API_CONNECT_TIMEOUT", parse_limit)?,
+                read: parse(&var, "API_READ_TIMEOUT", parse_limit)?,
+                total: parse(&var, "API_TOTAL_TIMEOUT", parse_limit)?,
+            },
+            retry: RetryLayer {
+                max_attempts: parse(&var, "API_RETRY_MAX_ATTEMPTS", |value| {
+                    parse_number(value).and_then(check_positive)
+                })?,
+                base_delay: parse(&var, "API_RETRY_BASE_DELAY", parse_duration)?,
+                max_delay: parse(&var, "API_RETRY_MAX_DELAY", parse_duration)?,
+                jitter: parse(&var, "API_RETRY_JITTER", |value| {
+                    parse_number(value).and_then(check_jitter)
+                })?,
+                idempotency_keys: parse(&var, "API_RETRY_IDEMPOTENCY_KEYS", parse_bool)?,
+            },
+            batch: BatchLayer {
+                max_len: parse(&var, "API_BATCH_MAX_LEN", |value| {
+                    parse_number(value).and_then(check_positive)
+                })?,
+                max_bytes: parse(&var, "API_BATCH_MAX_BYTES", |value| {
+                    parse_number(value).and_then(check_positive)
+                })?,
+                max_concurrency: parse(&var, "API_BATCH_MAX_CONCURRENCY", |value| {
+                    parse_number(value).and_then(check_positive)
+                })?,
+                flush_interval: parse(&var, "API_BATCH_FLUSH_INTERVAL", parse_duration)?,
+                channel_capacity: parse(&var, "API_BATCH_CHANNEL_CAPACITY", |value| {
+                    parse_number(value).and_then(check_positive)
+                })?,
+            },
+            limits: LimitsLayer::default(),
+            profiles: None,
+        })
+    }
+
+    /// Check the values that TOML types alone do not constrain
+    fn validate(&self, origin: &str, prefix: &str) -> Result<(), ConfigError> {
+        let check = |key: &str, result: Result<(), String>| {
+            result.map_err(|message| ConfigError::InvalidValue {
+                origin: origin.to_string(),
+                key: format!("{}{}", prefix, key),
+                message,
+            })
+        };
+
+        if let Some(user_agent) = &self.user_agent {
+            check("user_agent", check_user_agent(user_agent))?;
+        }
+        if let Some(max_attempts) = self.retry.max_attempts {
+            check("retry.max_attempts", check_positive(max_attempts).map(drop))?;
+        }
+        if let Some(jitter) = self.retry.jitter {
+            check("retry.jitter", check_jitter(jitter).map(drop))?;
+        }
+        for (key, value) in [
+            ("batch.max_len", self.batch.max_len),
+            ("batch.max_bytes", self.batch.max_bytes),
+            ("batch.max_concurrency", self.batch.max_concurrency),
+            ("batch.channel_capacity", self.batch.channel_capacity),
+        ] {
+            if let Some(value) = value {
+                check(key, check_positive(value).map(drop))?;
+            }
+        }
+        for (group, limits) in self.limits.iter() {
+            if let Some(rate) = limits.rate {
+                check(&format!("limits.{}.rate", group), check_rate(rate))?;
+            }
+            if let Some(burst) = limits.burst {
+                check(
+                    &format!("limits.{}.burst", group),
+                    check_positive(burst).map(drop),
+                )?;
+            }
+            if let Some(max_in_flight) = limits.max_in_flight {
+                check(
+                    &format!("limits.{}.max_in_flight", group),
+                    check_positive(max_in_flight).map(drop),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a duration with a unit, e.g. `500ms`, `30s`, `2m` or `1h`
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    const EXPECTED: &str = "is not a duration such as `500ms`, `30s`, `2m` or `1h`";
+
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).ok_or(EXPECTED)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| EXPECTED)?;
+    let duration = match unit.trim() {
+        "ms" => Some(Duration::from_millis(amount)),
+        "s" => Some(Duration::from_secs(amount)),
+        "m" => amount.checked_mul(60).map(Duration::from_secs),
+        "h" => amount.checked_mul(3600).map(Duration::from_secs),
+        _ => return Err(EXPECTED.to_string()),
+    };
+    duration.ok_or_else(|| "is too long".to_string())
+}
+
+/// Parse a duration, or `none` for no limit
+fn parse_limit(value: &str) -> Result<Option<Duration>, String> {
+    if value.trim().eq_ignore_ascii_case("none") {
+        Ok(None)
+    } else {
+        parse_duration(value)
+            .map(Some)
+            .map_err(|_| "is not a duration such as `30s`, or `none`".to_string())
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| "is not a number".to_string())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err("is not `true` or `false`".to_string()),
+    }
+}
+
+fn check_positive<T: PartialOrd + Default>(value: T) -> Result<T, String> {
+    if value > T::default() {
+        Ok(value)
+    } else {
+        Err("must be at least 1".to_string())
+    }
+}
+
+fn check_jitter(jitter: f64) -> Result<f64, String> {
+    if (0.0..=1.0).contains(&jitter) {
+        Ok(jitter)
+    } else {
+        Err("must be between 0 and 1".to_string())
+    }
+}
+
+fn check_rate(rate: f64) -> Result<(), String> {
+    if rate > 0.0 && rate.is_finite() {
+        Ok(())
+    } else {
+        Err("must be a number of requests per second greater than 0".to_string())
+    }
+}
+
+fn check_user_agent(user_agent: &str) -> Result<(), String> {
+    HeaderValue::from_str(user_agent)
+        .map(drop)
+        .map_err(|_| "contains characters not allowed in a header".to_string())
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_duration(&value)
+        .map(Some)
+        .map_err(|message| de::Error::custom(format!("`{}` {}", value, message)))
+}
+
+fn deserialize_limit<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Option<Duration>>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_limit(&value)
+        .map(Some)
+        .map_err(|message| de::Error::custom(format!("`{}` {}", value, message)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientConfig, ConfigError, Profile};
+    use crate::requests::limits::{GroupLimits, RateLimits};
+    use crate::requests::retry::RetryPolicy;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    const FILE: &str = r#"
+        user_agent = "field-logger/1.2"
+
+        [timeouts]
+        read = "10s"
+        total = "none"
+
+        [retry]
+        max_attempts = 5
+
+        [batch]
+        max_len = 200
+        flush_interval = "250ms"
+
+        [limits.batch]
+        rate = 2.0
+        max_in_flight = 1
+
+        [profiles.staging]
+        base_url = "https://staging.example.com"
+
+        [profiles.staging.retry]
+        max_attempts = 2
+    "#;
+
+    fn load(
+        profile: Option<Profile>,
+        toml: &str,
+        env: &[(&str, &str)],
+    ) -> Result<ClientConfig, ConfigError> {
+        let env: HashMap<String, String> = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let mut loader = ClientConfig::loader().toml(toml);
+        if let Some(profile) = profile {
+            loader = loader.profile(profile);
+        }
+        loader.load_with(|name| env.get(name).cloned())
+    }
+
+    #[test]
+    fn layers_override_in_order() {
+        let config = load(None, FILE, &[]).unwrap();
+        assert_eq!(config.profile, Profile::Dev);
+        assert_eq!(config.base_url.as_str(), "http://127.0.0.1:7878");
+        assert_eq!(config.user_agent, "field-logger/1.2");
+        assert_eq!(config.timeouts.connect, Some(Duration::from_secs(10)));
+        assert_eq!(config.timeouts.read, Some(Duration::from_secs(10)));
+        assert_eq!(config.timeouts.total, None);
+        assert_eq!(config.retry_policy, RetryPolicy::default().max_attempts(5));
+        assert_eq!(config.uploader.max_batch_len, 200);
+        assert_eq!(config.batch.max_len, 200);
+        assert_eq!(config.uploader.flush_interval, Duration::from_millis(250));
+        assert_eq!(
+            config.limits,
+            RateLimits {
+                batch: GroupLimits::unlimited().rate(2.0).max_in_flight(1),
+                ..RateLimits::default()
+            }
+        );
+
+        let config = load(
+            None,
+            FILE,
+            &[
+                ("API_PROFILE", "staging"),
+                ("API_READ_TIMEOUT", "2m"),
+                ("API_BATCH_MAX_LEN", "50"),
+                ("API_BATCH_MAX_CONCURRENCY", "2"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.profile, Profile::Staging);
+        assert_eq!(config.base_url.as_str(), "https://staging.example.com");
+        assert_eq!(config.retry_policy, RetryPolicy::default().max_attempts(2));
+        assert_eq!(config.timeouts.read, Some(Duration::from_secs(120)));
+        assert_eq!(config.uploader.max_batch_len, 50);
+        assert_eq!(config.batch.max_concurrency, 2);
+    }
+
+    #[test]
+    fn profiles_without_base_url_need_one() {
+        let error = load(Some(Profile::Prod), FILE, &[]).unwrap_err();
+        assert!(matches!(error, ConfigError::MissingBaseUrl(Profile::Prod)));
+
+        let config = load(
+            Some(Profile::Prod),
+            FILE,
+            &[("API_BASE_URL", "https://api.example.com/")],
+        )
+        .unwrap();
+        assert_eq!(config.base_url.as_str(), "https://api.example.com");
+    }
+
+    #[test]
+    fn reports_malformed_values() {
+        let error = load(None, "", &[("API_READ_TIMEOUT", "soon")]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value for `API_READ_TIMEOUT` in environment: `soon` is not a duration \
+             such as `30s`, or `none`"
+        );
+
+        let error = load(None, "[retry]\njitter = 1.5", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value for `retry.jitter` in config: must be between 0 and 1"
+        );
+
+        let error = load(None, "[profiles.staging.batch]\nmax_len = 0", &[]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("`profiles.staging.batch.max_len`"));
+
+        let error = load(None, "[limits.reads]\nrate = 0.0", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value for `limits.reads.rate` in config: must be a number of requests per \
+             second greater than 0"
+        );
+
+        let error = load(None, "[timeouts]\nread = \"10 parsecs\"", &[]).unwrap_err();
+        assert!(matches!(error, ConfigError::Parse { .. }));
+        assert!(error.to_string().contains("10 parsecs"));
+
+        for toml in [
+            "retries = 3",
+            "[profiles.qa]",
+            "base_url = 7",
+            "[limits.uploads]",
+        ] {
+            assert!(matches!(
+                load(None, toml, &[]),
+                Err(ConfigError::Parse { .. })
+            ));
+        }
+
+        assert!(matches!(
+            load(None, "", &[("API_PROFILE", "qa")]),
+            Err(ConfigError::UnknownProfile(_))
+        ));
+        assert!(matches!(
+            load(None, "", &[("API_BASE_URL", "localhost")]),
+            Err(ConfigError::BaseUrl(_))
+        ));
+    }
+}
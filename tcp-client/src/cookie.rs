@@ -0,0 +1,327 @@
+//! Parsing of `Set-Cookie` response headers following RFC 6265, section 5.2
+
+use crate::reqwest::header::{HeaderMap, SET_COOKIE};
+use crate::types::AuthToken;
+use std::fmt;
+use std::time::Duration;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the cookie holding the auth token
+pub const SESSION_COOKIE_NAME: &str = "session_id";
+
+/// Value of the `SameSite` cookie attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// Cookie sent by the server in a `Set-Cookie` header, with its attributes
+///
+/// The `Debug` output leaves out the value, which is the auth token.
+#[derive(Clone, PartialEq)]
+pub struct SessionCookie {
+    pub name: String,
+    pub value: String,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    /// Parsed `Expires` attribute
+    pub expires: Option<SystemTime>,
+    /// Parsed `Max-Age` attribute in seconds
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+    /// When the header was parsed, used as the base for `Max-Age`
+    pub received_at: SystemTime,
+}
+
+impl fmt::Debug for SessionCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionCookie")
+            .field("name", &self.name)
+            .field("value", &format_args!("***"))
+            .field("path", &self.path)
+            .field("domain", &self.domain)
+            .field("expires", &self.expires)
+            .field("max_age", &self.max_age)
+            .field("secure", &self.secure)
+            .field("http_only", &self.http_only)
+            .field("same_site", &self.same_site)
+            .field("received_at", &self.received_at)
+            .finish()
+    }
+}
+
+impl SessionCookie {
+    /// Parse a single `Set-Cookie` header value
+    ///
+    /// Returns `None` if the header has no `name=value` pair or the name is empty.
+    /// Unknown attributes and attributes with malformed values are ignored.
+    pub fn parse(header: &str) -> Option<Self> {
+        Self::parse_at(header, SystemTime::now())
+    }
+
+    /// Create a session cookie without attributes holding the given auth token
+    pub(crate) fn from_auth_token(auth_token: &AuthToken) -> Self {
+        SessionCookie {
+            name: SESSION_COOKIE_NAME.to_string(),
+            value: auth_token.as_str().to_string(),
+            path: None,
+            domain: None,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            received_at: SystemTime::now(),
+        }
+    }
+
+    fn parse_at(header: &str, received_at: SystemTime) -> Option<Self> {
+        let (pair, attributes) = match header.split_once(';') {
+            Some((pair, attributes)) => (pair, attributes),
+            None => (header, ""),
+        };
+
+        let (name, value) = pair.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = SessionCookie {
+            name: name.to_string(),
+            value: unquote(value.trim()).to_string(),
+            path: None,
+            domain: None,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+            received_at,
+        };
+
+        for attribute in attributes.split(';') {
+            let (name, value) = match attribute.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "expires" => {
+                    if let Some(expires) = parse_date(value) {
+                        cookie.expires = Some(expires);
+                    }
+                }
+                "max-age" => {
+                    if let Some(max_age) = parse_max_age(value) {
+                        cookie.max_age = Some(max_age);
+                    }
+                }
+                "domain" => {
+                    let domain = value.trim_start_matches('.');
+                    if !domain.is_empty() {
+                        cookie.domain = Some(domain.to_ascii_lowercase());
+                    }
+                }
+                "path" => {
+                    // A path that does not start with a slash means the default path
+                    cookie.path = value.starts_with('/').then(|| value.to_string());
+                }
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                "samesite" => {
+                    cookie.same_site = match value.to_ascii_lowercase().as_str() {
+                        "strict" => Some(SameSite::Strict),
+                        "lax" => Some(SameSite::Lax),
+                        "none" => Some(SameSite::None),
+                        _ => None,
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        Some(cookie)
+    }
+
+    /// Get when the cookie expires, or `None` for a cookie that lasts until the browser session
+    /// ends
+    ///
+    /// `Max-Age` takes precedence over `Expires`.
+    pub fn expires_at(&self) -> Option<SystemTime> {
+        match self.max_age {
+            Some(max_age) if max_age <= 0 => Some(UNIX_EPOCH),
+            Some(max_age) => self
+                .received_at
+                .checked_add(Duration::from_secs(max_age as u64)),
+            None => self.expires,
+        }
+    }
+
+    /// Check whether the server is asking for the cookie to be removed, as it does on logout
+    pub fn is_deletion(&self) -> bool {
+        self.value.is_empty()
+            || self
+                .expires_at()
+                .is_some_and(|expires_at| expires_at <= self.received_at)
+    }
+
+    /// Get the cookie value as an auth token
+    pub fn auth_token(&self) -> AuthToken {
+        AuthToken::new(self.value.clone())
+    }
+}
+
+/// Find the session cookie among all `Set-Cookie` headers of a response
+///
+/// If the cookie is set more than once, the last header wins.
+pub fn find_session_cookie(headers: &HeaderMap) -> Option<SessionCookie> {
+    let received_at = SystemTime::now();
+
+    headers
+        .get_all(SET_COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .filter_map(|header| SessionCookie::parse_at(header, received_at))
+        .rfind(|cookie| cookie.name == SESSION_COOKIE_NAME)
+}
+
+/// Remove the double quotes around a cookie value, if present
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Parse the value of a `Max-Age` attribute: an optional minus sign followed by digits
+fn parse_max_age(value: &str) -> Option<i64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Values too large to represent are clamped rather than rejected
+    Some(value.parse().unwrap_or(if value.starts_with('-') {
+        i64::MIN
+    } else {
+        i64::MAX
+    }))
+}
+
+/// Parse the value of an `Expires` attribute in any of the HTTP date formats
+fn parse_date(value: &str) -> Option<SystemTime> {
+    let date = httpdate::parse_http_date(value.trim_matches('"')).ok()?;
+    let since_epoch = date.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(UNIX_EPOCH + since_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reqwest::header::HeaderValue;
+
+    /// 2025-01-01T00:00:00Z
+    fn received_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_735_689_600)
+    }
+
+    fn parse(header: &str) -> SessionCookie {
+        SessionCookie::parse_at(header, received_at()).unwrap()
+    }
+
+    #[test]
+    fn strips_quotes_around_values() {
+        assert_eq!(parse("session_id=\"abc\"; Path=/").value, "abc");
+        assert_eq!(parse("session_id=\"abc").value, "\"abc");
+        assert_eq!(parse("session_id=").value, "");
+        assert!(SessionCookie::parse_at("=abc", received_at()).is_none());
+        assert!(SessionCookie::parse_at("abc", received_at()).is_none());
+    }
+
+    #[test]
+    fn last_session_cookie_wins() {
+        let mut headers = HeaderMap::new();
+        for header in [
+            "session_id=first",
+            "theme=dark",
+            "session_id=second; Path=/",
+            "lang=en",
+        ] {
+            headers.append(SET_COOKIE, HeaderValue::from_static(header));
+        }
+
+        let cookie = find_session_cookie(&headers).unwrap();
+        assert_eq!(cookie.value, "second");
+        assert_eq!(cookie.path.as_deref(), Some("/"));
+        assert!(find_session_cookie(&HeaderMap::new()).is_none());
+    }
+
+    #[test]
+    fn max_age_takes_precedence_over_expires() {
+        let cookie = parse("session_id=abc; Expires=Wed, 01 Jan 2025 01:00:00 GMT; Max-Age=60");
+        assert_eq!(
+            cookie.expires,
+            Some(received_at() + Duration::from_secs(3600))
+        );
+        assert_eq!(
+            cookie.expires_at(),
+            Some(received_at() + Duration::from_secs(60))
+        );
+
+        let cookie = parse("session_id=abc; Expires=Wed, 01 Jan 2025 01:00:00 GMT");
+        assert_eq!(
+            cookie.expires_at(),
+            Some(received_at() + Duration::from_secs(3600))
+        );
+        assert_eq!(parse("session_id=abc").expires_at(), None);
+    }
+
+    #[test]
+    fn attributes_are_read_in_any_order_and_case() {
+        let expected = parse(
+            "session_id=abc; Path=/api; Domain=.Example.com; Max-Age=60; Secure; HttpOnly; \
+             SameSite=Lax",
+        );
+        assert_eq!(expected.path.as_deref(), Some("/api"));
+        assert_eq!(expected.domain.as_deref(), Some("example.com"));
+        assert_eq!(expected.max_age, Some(60));
+        assert!(expected.secure);
+        assert!(expected.http_only);
+        assert_eq!(expected.same_site, Some(SameSite::Lax));
+
+        let shuffled = parse(
+            "session_id=abc;samesite=LAX;  HTTPONLY ;max-age=60;SECURE;domain=example.com;\
+             PATH=/api; Unknown=1",
+        );
+        assert_eq!(shuffled, expected);
+    }
+
+    #[test]
+    fn recognizes_deletion_cookies() {
+        assert!(parse("session_id=abc; Max-Age=0").is_deletion());
+        assert!(parse("session_id=abc; Max-Age=-1").is_deletion());
+        assert!(parse("session_id=abc; Expires=Thu, 01 Jan 1970 00:00:00 GMT").is_deletion());
+        assert!(parse("session_id=; Path=/").is_deletion());
+
+        assert!(!parse("session_id=abc").is_deletion());
+        assert!(!parse("session_id=abc; Max-Age=60").is_deletion());
+        assert!(!parse("session_id=abc; Expires=Wed, 01 Jan 2025 01:00:00 GMT").is_deletion());
+        // Max-Age wins over an Expires in the past
+        assert!(
+            !parse("session_id=abc; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=60")
+                .is_deletion()
+        );
+    }
+
+    #[test]
+    fn debug_output_hides_the_value() {
+        let debug = format!("{:?}", parse("session_id=secret-token; Path=/"));
+        assert!(!debug.contains("secret-token"), "{}", debug);
+        assert!(debug.contains("value: ***"), "{}", debug);
+        assert!(debug.contains("path: Some(\"/\")"), "{}", debug);
+    }
+}
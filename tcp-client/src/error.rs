@@ -0,0 +1,152 @@
+//! Error type returned by all requests to the server
+
+use crate::config::ConfigError;
+use crate::requests::timeout::TimeoutKind;
+use crate::reqwest::{
+    header::{HeaderMap, InvalidHeaderValue, RETRY_AFTER},
+    StatusCode,
+};
+use serde_json::Value;
+use std::fmt;
+use std::time::Duration;
+use web_time::SystemTime;
+
+/// Errors that can occur while sending a request or reading its response
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be delivered (DNS, connection refused, broken connection, etc.)
+    Transport(crate::reqwest::Error),
+    /// The request ran into one of its timeouts
+    Timeout(TimeoutKind),
+    /// The request was cancelled with a `CancelHandle`
+    Cancelled,
+    /// The server responded with a non-success status code
+    Status {
+        status: StatusCode,
+        body: Option<Value>,
+        /// Delay requested by the server with a `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+    /// The request body could not be serialized to JSON
+    Serialize(serde_json::Error),
+    /// The response body could not be decoded from JSON
+    Decode(serde_json::Error),
+    /// A header value for the request could not be built
+    InvalidHeader(InvalidHeaderValue),
+    /// The server did not send the expected session cookie
+    MissingSessionCookie,
+    /// The client configuration could not be loaded, e.g. because of an invalid base URL
+    Config(ConfigError),
+    /// A `ReplayTransport` has no recorded interaction matching the request, described here
+    NotRecorded(String),
+}
+
+impl ApiError {
+    /// Get the HTTP status code if the server responded with an error
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            ApiError::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Get the error message sent by the server, if the error body contains one
+    pub fn server_message(&self) -> Option<&str> {
+        match self {
+            ApiError::Status {
+                body: Some(body), ..
+            } => body
+                .get("error")
+                .or_else(|| body.get("message"))
+                .and_then(Value::as_str),
+            _ => None,
+        }
+    }
+
+    /// Get how long the server asked to wait before retrying, from the `Retry-After` header
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ApiError::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Parse the `Retry-After` header of a response
+    ///
+    /// The header may hold a number of seconds or an HTTP date. A date in the past gives zero.
+    pub(crate) fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let date = httpdate::parse_http_date(value).ok()?;
+        let since_epoch = date.duration_since(std::time::UNIX_EPOCH).ok()?;
+        let now = SystemTime::now()
+            .duration_since(web_time::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        Some(since_epoch.saturating_sub(now))
+    }
+
+    /// Check whether the request ran into one of its timeouts
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, ApiError::Timeout(_))
+    }
+
+    /// Convert a reqwest error into a transport or timeout error
+    pub(crate) fn from_reqwest(e: crate::reqwest::Error) -> Self {
+        if e.is_timeout() && e.is_connect() {
+            ApiError::Timeout(TimeoutKind::Connect)
+        } else if e.is_timeout() {
+            ApiError::Timeout(TimeoutKind::Total)
+        } else {
+            ApiError::Transport(e)
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Transport(e) => write!(f, "failed to reach the server: {}", e),
+            ApiError::Timeout(kind) => write!(f, "request timed out ({} timeout)", kind),
+            ApiError::Cancelled => write!(f, "request was cancelled"),
+            ApiError::Status { status, .. } => match self.server_message() {
+                Some(message) => write!(f, "server responded with {}: {}", status, message),
+                None => write!(f, "server responded with {}", status),
+            },
+            ApiError::Serialize(e) => write!(f, "failed to serialize request body: {}", e),
+            ApiError::Decode(e) => write!(f, "failed to decode response body: {}", e),
+            ApiError::InvalidHeader(e) => write!(f, "invalid header value: {}", e),
+            ApiError::MissingSessionCookie => write!(f, "server did not send a session cookie"),
+            ApiError::Config(e) => write!(f, "{}", e),
+            ApiError::NotRecorded(request) => write!(
+                f,
+                "ReplayTransport: no recorded interaction matches {}",
+                request
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Transport(e) => Some(e),
+            ApiError::Serialize(e) | ApiError::Decode(e) => Some(e),
+            ApiError::InvalidHeader(e) => Some(e),
+            ApiError::Config(e) => Some(e),
+            ApiError::Timeout(_)
+            | ApiError::Cancelled
+            | ApiError::Status { .. }
+            | ApiError::MissingSessionCookie
+            | ApiError::NotRecorded(_) => None,
+        }
+    }
+}
+
+impl From<InvalidHeaderValue> for ApiError {
+    fn from(e: InvalidHeaderValue) -> Self {
+        ApiError::InvalidHeader(e)
+    }
+}
@@ -0,0 +1,211 @@
+//! Scripted in-memory `Transport` for testing requests without a server
+//!
+//! A `FakeTransport` is given the exchanges it should see, in order. Each request is checked
+//! against the next exchange: method, URL, `Cookie` header and JSON body must match exactly,
+//! otherwise the transport panics with both requests. The scripted response is returned on a
+//! match.
+//!
+//! ```ignore
+//! let fake = Arc::new(FakeTransport::new());
+//! fake.expect(
+//!     Exchange::new(Method::GET, "http://test/sensors/3")
+//!         .cookie("session_id=abc")
+//!         .respond_json(StatusCode::OK, json!({ "id": "3", "sensor_type": "gps" })),
+//! );
+//! let client = ApiClient::builder()
+//!     .base_url("http://test")
+//!     .auth_token(AuthToken::from("abc"))
+//!     .transport(fake.clone())
+//!     .build();
+//! client.sensors().get(&SensorId::from("3")).await?;
+//! fake.assert_done();
+//! ```
+
+use crate::error::ApiError;
+use crate::requests::transport::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use crate::reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, COOKIE},
+    Method, StatusCode,
+};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Request the fake transport expects, and the response it answers with
+#[derive(Debug, Clone)]
+pub struct Exchange {
+    method: Method,
+    url: String,
+    cookie: Option<String>,
+    body: Option<Value>,
+    response: HttpResponse,
+}
+
+impl Exchange {
+    /// Expect a request without cookie or body, answered with `204 No Content`
+    pub fn new(method: Method, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            cookie: None,
+            body: None,
+            response: HttpResponse {
+                status: StatusCode::NO_CONTENT,
+                headers: HeaderMap::new(),
+                body: Vec::new(),
+            },
+        }
+    }
+
+    /// Expect this exact `Cookie` header, e.g. `session_id=abc`
+    pub fn cookie(mut self, cookie: impl Into<String>) -> Self {
+        self.cookie = Some(cookie.into());
+        self
+    }
+
+    /// Expect a JSON body equal to `body`
+    pub fn json_body(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Answer with a status and no body
+    pub fn respond(mut self, status: StatusCode) -> Self {
+        self.response.status = status;
+        self.response.body = Vec::new();
+        self
+    }
+
+    /// Answer with a status and a JSON body
+    pub fn respond_json(mut self, status: StatusCode, body: Value) -> Self {
+        self.response.status = status;
+        self.response.body = body.to_string().into_bytes();
+        self.response
+            .headers
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        self
+    }
+
+    /// Add a header to the response, e.g. `Set-Cookie`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is not a valid header value.
+    pub fn respond_header(mut self, name: HeaderName, value: &str) -> Self {
+        let value = HeaderValue::from_str(value).expect("invalid header value");
+        self.response.headers.append(name, value);
+        self
+    }
+
+    /// Panic unless `request` is the expected one
+    fn check(&self, request: &HttpRequest) {
+        let cookie = request
+            .headers
+            .get(COOKIE)
+            .map(|cookie| cookie.to_str().unwrap_or("<not UTF-8>").to_string());
+        let body = if request.body.is_empty() {
+            None
+        } else {
+            Some(serde_json::from_slice(&request.body).unwrap_or_else(|_| {
+                Value::String(String::from_utf8_lossy(&request.body).into_owned())
+            }))
+        };
+
+        let matches = request.method == self.method
+            && request.url == self.url
+            && cookie == self.cookie
+            && body == self.body;
+        if !matches {
+            panic!(
+                "FakeTransport: unexpected request\n  expected: {} {} cookie={:?} body={}\n  \
+                 got:      {} {} cookie={:?} body={}",
+                self.method,
+                self.url,
+                self.cookie,
+                display_body(&self.body),
+                request.method,
+                request.url,
+                cookie,
+                display_body(&body),
+            );
+        }
+    }
+}
+
+fn display_body(body: &Option<Value>) -> String {
+    body.as_ref()
+        .map_or_else(|| "none".to_string(), Value::to_string)
+}
+
+/// Transport answering requests from a script of exchanges
+#[derive(Debug, Default)]
+pub struct FakeTransport {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    expected: VecDeque<Exchange>,
+    requests: Vec<HttpRequest>,
+}
+
+impl FakeTransport {
+    /// Create a transport expecting no requests
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect another request after the ones already scripted
+    pub fn expect(&self, exchange: Exchange) -> &Self {
+        self.state().expected.push_back(exchange);
+        self
+    }
+
+    /// Get the requests sent so far, with all their headers
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.state().requests.clone()
+    }
+
+    /// Panic if some scripted exchanges have not happened
+    pub fn assert_done(&self) {
+        let message = {
+            let state = self.state();
+            state.expected.front().map(|next| {
+                format!(
+                    "FakeTransport: {} expected requests were not sent, the next one is {} {}",
+                    state.expected.len(),
+                    next.method,
+                    next.url
+                )
+            })
+        };
+        if let Some(message) = message {
+            panic!("{}", message);
+        }
+    }
+
+    // Never panic while holding the guard, so the state stays readable after a failed check
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Transport for FakeTransport {
+    fn send(&self, request: HttpRequest, _read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        let exchange = {
+            let mut state = self.state();
+            state.requests.push(request.clone());
+            state.expected.pop_front()
+        };
+        let Some(exchange) = exchange else {
+            panic!(
+                "FakeTransport: unexpected request {} {}, no more were expected",
+                request.method, request.url
+            );
+        };
+        exchange.check(&request);
+        let response = exchange.response;
+        Box::pin(async move { Ok::<_, ApiError>(response) })
+    }
+}
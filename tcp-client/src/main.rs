@@ -1,18 +1,51 @@
 pub mod api;
+pub mod api_client;
+pub mod batch;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+#[cfg(all(feature = "cassette", not(target_arch = "wasm32")))]
+pub mod cassette;
+pub mod config;
+pub mod cookie;
+pub mod error;
+#[cfg(feature = "fake-transport")]
+pub mod fake_transport;
+pub mod metrics;
+#[cfg(all(feature = "mock-server", not(target_arch = "wasm32")))]
+pub mod mock_server;
+pub mod outbox;
 pub mod path;
 pub mod requests;
+pub mod session_store;
+pub mod timestamp;
+pub mod types;
+pub mod uploader;
 
+use crate::reqwest::Client;
 #[allow(unused_imports)]
 use api::{auth, sensor, session, session_sensor, session_sensor_data, user};
-use reqwest_wasm::Client;
 
-pub fn get_client() -> Client {
-    let client = Client::new();
+#[cfg(not(any(feature = "native", feature = "wasm")))]
+compile_error!("enable the `wasm` or `native` feature to choose an HTTP backend");
 
-    return client;
-}
+/// HTTP library the requests are sent with, upstream reqwest with the `native` feature and
+/// reqwest-wasm otherwise
+#[cfg(feature = "native")]
+pub use ::reqwest;
+#[cfg(all(feature = "wasm", not(feature = "native")))]
+pub use reqwest_wasm as reqwest;
 
-#[allow(dead_code)]
-fn main() {
-    println!("Starting client...");
+pub use api_client::{ApiClient, ApiClientBuilder};
+pub use batch::{BatchError, BatchLimits, BatchReport, DatapointKey};
+pub use config::{ClientConfig, ConfigError, Profile};
+pub use error::ApiError;
+pub use requests::cancel::CancelHandle;
+pub use requests::limits::{GroupLimits, Priority, RateLimiter, RateLimits, RouteGroup};
+pub use requests::retry::RetryPolicy;
+pub use requests::timeout::{TimeoutKind, Timeouts};
+pub use timestamp::{Timestamp, TimestampError};
+pub use types::{AuthToken, SensorId, SessionId, SessionSensorId, Username};
+
+pub fn get_client() -> Client {
+    Client::new()
 }
@@ -0,0 +1,397 @@
+//! Request metrics per endpoint, rendered in the Prometheus text format
+//!
+//! Every request an `ApiClient` sends is counted in its `Metrics` registry, which is the
+//! process-wide `metrics::global()` unless `ApiClientBuilder::metrics` sets another one. The
+//! series are labelled with the method and the route template, e.g.
+//! `/sessions-sensors-data/{id}/{datetime}`, so IDs do not create a series each. Each attempt
+//! of a retried request counts as a request of its own.
+//!
+//! ```ignore
+//! // In the handler of the daemon's /metrics endpoint
+//! let body = client::metrics::global().render_prometheus();
+//! ```
+
+use crate::path::{BaseUrl, Route};
+use crate::requests::transport::{HttpRequest, Transport, TransportFuture};
+use crate::reqwest::{Method, StatusCode};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use web_time::Instant;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Route label of requests to URLs outside the API
+const UNKNOWN_ROUTE: &str = "unknown";
+
+/// Status label of requests that got no response
+const NO_RESPONSE: &str = "none";
+
+/// Get the registry used by clients that were not given one
+pub fn global() -> &'static Arc<Metrics> {
+    static GLOBAL: OnceLock<Arc<Metrics>> = OnceLock::new();
+    GLOBAL.get_or_init(|| Arc::new(Metrics::new()))
+}
+
+/// Registry of request counts, errors, latencies and body sizes per endpoint
+#[derive(Debug, Default)]
+pub struct Metrics {
+    routes: Mutex<BTreeMap<(String, &'static str), RouteMetrics>>,
+}
+
+/// Metrics of one method and route template
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteMetrics {
+    /// Requests by response status, with `none` for requests that got no response
+    pub requests: BTreeMap<String, u64>,
+    /// Requests that got no response or an error status
+    pub errors: u64,
+    /// Requests per latency bucket, not cumulative, with the overflow bucket last
+    pub latency_buckets: Vec<u64>,
+    pub latency_sum: Duration,
+    /// Bytes of request bodies sent
+    pub request_bytes: u64,
+    /// Bytes of response bodies received
+    pub response_bytes: u64,
+}
+
+impl RouteMetrics {
+    /// Get the number of requests, whatever their outcome
+    pub fn count(&self) -> u64 {
+        self.requests.values().sum()
+    }
+}
+
+impl Metrics {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the metrics of a method and route template, e.g. `POST` and
+    /// `/sessions-sensors-data/batch`
+    pub fn route(&self, method: &Method, route: &str) -> Option<RouteMetrics> {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        routes
+            .iter()
+            .find(|((m, r), _)| m == method.as_str() && *r == route)
+            .map(|(_, metrics)| metrics.clone())
+    }
+
+    /// Count a request
+    ///
+    /// `status` is `None` when no response was received.
+    pub fn record(
+        &self,
+        method: &Method,
+        route: Option<&'static Route>,
+        status: Option<StatusCode>,
+        latency: Duration,
+        request_bytes: usize,
+        response_bytes: usize,
+    ) {
+        let route = route.map_or(UNKNOWN_ROUTE, |route| route.template);
+        let mut routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let metrics = routes
+            .entry((method.to_string(), route))
+            .or_insert_with(|| RouteMetrics {
+                latency_buckets: vec![0; LATENCY_BUCKETS.len() + 1],
+                ..RouteMetrics::default()
+            });
+
+        let status_label =
+            status.map_or_else(|| NO_RESPONSE.to_string(), |s| s.as_u16().to_string());
+        *metrics.requests.entry(status_label).or_default() += 1;
+        if !status.is_some_and(|status| status.is_success()) {
+            metrics.errors += 1;
+        }
+        let seconds = latency.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        metrics.latency_buckets[bucket] += 1;
+        metrics.latency_sum += latency;
+        metrics.request_bytes += request_bytes as u64;
+        metrics.response_bytes += response_bytes as u64;
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let routes = self.routes.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        let labels = |method: &str, route: &str| {
+            format!("method=\"{}\",route=\"{}\"", escape(method), escape(route))
+        };
+
+        header(
+            &mut out,
+            "tcp_client_requests_total",
+            "counter",
+            "Requests sent, by response status",
+        );
+        for ((method, route), metrics) in routes.iter() {
+            for (status, count) in &metrics.requests {
+                let _ = writeln!(
+                    out,
+                    "tcp_client_requests_total{{{},status=\"{}\"}} {}",
+                    labels(method, route),
+                    status,
+                    count
+                );
+            }
+        }
+
+        header(
+            &mut out,
+            "tcp_client_request_errors_total",
+            "counter",
+            "Requests that got no response or an error status",
+        );
+        for ((method, route), metrics) in routes.iter() {
+            let _ = writeln!(
+                out,
+                "tcp_client_request_errors_total{{{}}} {}",
+                labels(method, route),
+                metrics.errors
+            );
+        }
+
+        header(
+            &mut out,
+            "tcp_client_request_duration_seconds",
+            "histogram",
+            "Time from sending a request to receiving the whole response",
+        );
+        for ((method, route), metrics) in routes.iter() {
+            let labels = labels(method, route);
+            let mut cumulative = 0;
+            for (i, count) in metrics.latency_buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = LATENCY_BUCKETS
+                    .get(i)
+                    .map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+                let _ = writeln!(
+                    out,
+                    "tcp_client_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "tcp_client_request_duration_seconds_sum{{{}}} {}",
+                labels,
+                metrics.latency_sum.as_secs_f64()
+            );
+            let _ = writeln!(
+                out,
+                "tcp_client_request_duration_seconds_count{{{}}} {}",
+                labels, cumulative
+            );
+        }
+
+        header(
+            &mut out,
+            "tcp_client_request_bytes_total",
+            "counter",
+            "Bytes of request bodies sent",
+        );
+        for ((method, route), metrics) in routes.iter() {
+            let _ = writeln!(
+                out,
+                "tcp_client_request_bytes_total{{{}}} {}",
+                labels(method, route),
+                metrics.request_bytes
+            );
+        }
+
+        header(
+            &mut out,
+            "tcp_client_response_bytes_total",
+            "counter",
+            "Bytes of response bodies received",
+        );
+        for ((method, route), metrics) in routes.iter() {
+            let _ = writeln!(
+                out,
+                "tcp_client_response_bytes_total{{{}}} {}",
+                labels(method, route),
+                metrics.response_bytes
+            );
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Escape a label value: backslash, double quote and line feed
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Transport counting the requests of another transport in a registry
+#[derive(Debug)]
+pub(crate) struct MeteredTransport {
+    inner: Arc<dyn Transport>,
+    base_url: BaseUrl,
+    metrics: Arc<Metrics>,
+}
+
+impl MeteredTransport {
+    pub(crate) fn new(inner: Arc<dyn Transport>, base_url: BaseUrl, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner,
+            base_url,
+            metrics,
+        }
+    }
+}
+
+impl Transport for MeteredTransport {
+    fn send(&self, request: HttpRequest, read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let method = request.method.clone();
+            let route = Route::find(&self.base_url, &method, &request.url);
+            let request_bytes = request.body.len();
+
+            let started = Instant::now();
+            let result = self.inner.send(request, read_timeout).await;
+            let (status, response_bytes) = match &result {
+                Ok(response) => (Some(response.status), response.body.len()),
+                Err(_) => (None, 0),
+            };
+            self.metrics.record(
+                &method,
+                route,
+                status,
+                started.elapsed(),
+                request_bytes,
+                response_bytes,
+            );
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::ROUTES;
+
+    fn batch_route() -> &'static Route {
+        ROUTES
+            .iter()
+            .find(|route| route.name == "batch_create_datapoint")
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_requests_per_route_and_status() {
+        let metrics = Metrics::new();
+        let latency = Duration::from_millis(20);
+        metrics.record(
+            &Method::POST,
+            Some(batch_route()),
+            Some(StatusCode::CREATED),
+            latency,
+            100,
+            0,
+        );
+        metrics.record(
+            &Method::POST,
+            Some(batch_route()),
+            Some(StatusCode::BAD_GATEWAY),
+            latency,
+            50,
+            10,
+        );
+        metrics.record(
+            &Method::POST,
+            Some(batch_route()),
+            None,
+            Duration::from_secs(60),
+            50,
+            0,
+        );
+
+        let batch = metrics
+            .route(&Method::POST, "/sessions-sensors-data/batch")
+            .unwrap();
+        assert_eq!(batch.count(), 3);
+        assert_eq!(batch.requests["201"], 1);
+        assert_eq!(batch.requests["502"], 1);
+        assert_eq!(batch.requests["none"], 1);
+        assert_eq!(batch.errors, 2);
+        assert_eq!(batch.request_bytes, 200);
+        assert_eq!(batch.response_bytes, 10);
+        assert_eq!(batch.latency_buckets[2], 2);
+        assert_eq!(batch.latency_buckets[LATENCY_BUCKETS.len()], 1);
+        assert!(metrics
+            .route(&Method::GET, "/sessions-sensors-data/batch")
+            .is_none());
+    }
+
+    #[test]
+    fn renders_the_prometheus_text_format() {
+        let metrics = Metrics::new();
+        metrics.record(
+            &Method::POST,
+            Some(batch_route()),
+            Some(StatusCode::CREATED),
+            Duration::from_millis(30),
+            120,
+            0,
+        );
+        metrics.record(&Method::GET, None, None, Duration::from_millis(1), 0, 0);
+
+        let text = metrics.render_prometheus();
+        let labels = "method=\"POST\",route=\"/sessions-sensors-data/batch\"";
+        for line in [
+            "# TYPE tcp_client_requests_total counter".to_string(),
+            format!("tcp_client_requests_total{{{},status=\"201\"}} 1", labels),
+            format!("tcp_client_request_errors_total{{{}}} 0", labels),
+            "# TYPE tcp_client_request_duration_seconds histogram".to_string(),
+            format!(
+                "tcp_client_request_duration_seconds_bucket{{{},le=\"0.025\"}} 0",
+                labels
+            ),
+            format!(
+                "tcp_client_request_duration_seconds_bucket{{{},le=\"0.05\"}} 1",
+                labels
+            ),
+            format!(
+                "tcp_client_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 1",
+                labels
+            ),
+            format!("tcp_client_request_duration_seconds_count{{{}}} 1", labels),
+            format!("tcp_client_request_bytes_total{{{}}} 120", labels),
+            "tcp_client_requests_total{method=\"GET\",route=\"unknown\",status=\"none\"} 1"
+                .to_string(),
+            "tcp_client_request_errors_total{method=\"GET\",route=\"unknown\"} 1".to_string(),
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing `{}` in\n{}",
+                line,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
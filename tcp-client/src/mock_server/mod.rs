@@ -0,0 +1,127 @@
+//! In-process mock of the backend for testing the client without the real server
+//!
+//! The mock serves every route from `path.rs` on a local port, keeping all data in memory.
+//! It issues `session_id` cookies on login and renewal, and answers like the real server:
+//! `401 Unauthorized` without a valid session, `404 Not Found` for unknown resources and
+//! `409 Conflict` for duplicates and resources that are still referenced.
+
+mod http;
+mod routes;
+
+use routes::State;
+use std::io::{self, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Default lifetime of the auth tokens issued by the mock server
+const DEFAULT_SESSION_TTL: Duration = Duration::from_secs(3600);
+
+/// Mock backend listening on `127.0.0.1`, stopped when dropped
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Start a mock server on a free port
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::new(DEFAULT_SESSION_TTL)));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let state = Arc::clone(&state);
+            let shutdown = Arc::clone(&shutdown);
+            thread::spawn(move || serve(listener, state, shutdown))
+        };
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    /// Get the base URL to pass to `ApiClientBuilder::base_url`
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Set the lifetime of auth tokens issued from now on (1 hour by default)
+    pub fn set_session_ttl(&self, session_ttl: Duration) {
+        self.state().session_ttl = session_ttl;
+    }
+
+    /// Make every auth token issued so far expired, so requests using them get `401`
+    ///
+    /// Expired tokens can still be renewed until they are logged out.
+    pub fn expire_sessions(&self) {
+        self.state().expire_logins();
+    }
+
+    /// Answer every request with `503 Service Unavailable` until called with `false`, like a
+    /// gateway in front of a restarting backend
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state().unavailable = unavailable;
+    }
+
+    /// Get the number of auth tokens that are currently valid
+    pub fn active_sessions(&self) -> usize {
+        self.state().active_logins()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it sees the shutdown flag
+        let _ = TcpStream::connect(self.addr);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Accept connections until shut down, handling each on its own thread
+fn serve(listener: TcpListener, state: Arc<Mutex<State>>, shutdown: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if shutdown.load(Ordering::SeqCst) {
+            break;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let state = Arc::clone(&state);
+        thread::spawn(move || {
+            let _ = handle_connection(stream, &state);
+        });
+    }
+}
+
+/// Answer a single request and close the connection
+fn handle_connection(stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Some(request) = http::read_request(&mut reader)? else {
+        return Ok(());
+    };
+
+    let response = state
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .handle(&request);
+
+    let mut stream = stream;
+    http::write_response(&mut stream, &response)
+}
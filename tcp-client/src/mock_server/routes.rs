@@ -0,0 +1,540 @@
+//! In-memory data and request handling for every route of the mock server
+
+use super::http::{Request, Response};
+use crate::api::auth::User;
+use crate::api::sensor::{Sensor, SensorRecord};
+use crate::api::session::{Session, SessionRecord};
+use crate::api::session_sensor::{PatchSessionSensor, SessionSensor, SessionSensorLink};
+use crate::api::session_sensor_data::{Batch, Datapoint, SessionSensorData};
+use crate::api::user::UserInfo;
+use crate::cookie::SESSION_COOKIE_NAME;
+use crate::reqwest::StatusCode;
+use crate::timestamp::Timestamp;
+use crate::types::{AuthToken, SensorId, SessionId, SessionSensorId, Username};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+/// Login session issued by the mock server
+#[derive(Debug)]
+struct LoginSession {
+    username: Username,
+    expires_at: SystemTime,
+}
+
+/// Everything stored by the mock server
+#[derive(Debug)]
+pub(crate) struct State {
+    pub session_ttl: Duration,
+    users: Vec<User>,
+    logins: HashMap<AuthToken, LoginSession>,
+    sensors: Vec<SensorRecord>,
+    sessions: Vec<SessionRecord>,
+    links: Vec<SessionSensorLink>,
+    datapoints: Vec<Datapoint>,
+    next_id: u64,
+    /// Answer every request with `503 Service Unavailable`
+    pub unavailable: bool,
+}
+
+impl State {
+    pub fn new(session_ttl: Duration) -> Self {
+        Self {
+            session_ttl,
+            users: Vec::new(),
+            logins: HashMap::new(),
+            sensors: Vec::new(),
+            sessions: Vec::new(),
+            links: Vec::new(),
+            datapoints: Vec::new(),
+            next_id: 1,
+            unavailable: false,
+        }
+    }
+
+    /// Make every issued auth token expired, as if the server had been idle for a long time
+    pub fn expire_logins(&mut self) {
+        let now = SystemTime::now();
+        for login in self.logins.values_mut() {
+            login.expires_at = now;
+        }
+    }
+
+    /// Get the number of auth tokens that are currently valid
+    pub fn active_logins(&self) -> usize {
+        let now = SystemTime::now();
+        self.logins
+            .values()
+            .filter(|login| login.expires_at > now)
+            .count()
+    }
+
+    /// Answer a request
+    pub fn handle(&mut self, request: &Request) -> Response {
+        if self.unavailable {
+            return Response::error(StatusCode::SERVICE_UNAVAILABLE, "backend is restarting");
+        }
+        let result = match request.segments().as_slice() {
+            ["users", ..] => self.users(request),
+            ["authentication", ..] => self.authentication(request),
+            ["sensors", ..] => self.sensors(request),
+            ["sessions", ..] => self.sessions(request),
+            ["sessions-sensors", ..] => self.session_sensors(request),
+            ["sessions-sensors-data", ..] => self.datapoints(request),
+            _ => Err(not_found("route")),
+        };
+
+        result.unwrap_or_else(|response| response)
+    }
+
+    fn users(&mut self, request: &Request) -> Result<Response, Response> {
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", []) => {
+                let user: User = parse_body(request)?;
+                if self.users.iter().any(|u| u.username == user.username) {
+                    return Err(conflict("user already exists"));
+                }
+                let info = UserInfo {
+                    username: user.username.clone(),
+                };
+                self.users.push(user);
+                Ok(Response::json(StatusCode::CREATED, &info))
+            }
+            ("GET", []) => {
+                self.authenticate(request)?;
+                let users: Vec<UserInfo> = self
+                    .users
+                    .iter()
+                    .map(|user| UserInfo {
+                        username: user.username.clone(),
+                    })
+                    .collect();
+                Ok(Response::json(StatusCode::OK, &users))
+            }
+            ("GET", ["profile"]) => {
+                let username = self.authenticate(request)?;
+                Ok(Response::json(StatusCode::OK, &UserInfo { username }))
+            }
+            ("GET", [username]) => {
+                let user = self.find_user(username)?;
+                Ok(Response::json(
+                    StatusCode::OK,
+                    &UserInfo {
+                        username: user.username.clone(),
+                    },
+                ))
+            }
+            ("PATCH", [username]) => {
+                let update: User = parse_body(request)?;
+                let index = self.user_index(username)?;
+                if update.username.as_str() != *username
+                    && self.users.iter().any(|u| u.username == update.username)
+                {
+                    return Err(conflict("user already exists"));
+                }
+                self.users[index] = update.clone();
+                Ok(Response::json(
+                    StatusCode::OK,
+                    &UserInfo {
+                        username: update.username,
+                    },
+                ))
+            }
+            ("DELETE", [username]) => {
+                let index = self.user_index(username)?;
+                if self
+                    .sessions
+                    .iter()
+                    .any(|s| s.username.as_str() == *username)
+                {
+                    return Err(conflict("user still has sessions"));
+                }
+                self.users.remove(index);
+                self.logins
+                    .retain(|_, login| login.username.as_str() != *username);
+                Ok(Response::empty(StatusCode::NO_CONTENT))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    fn authentication(&mut self, request: &Request) -> Result<Response, Response> {
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", ["login"]) => {
+                let credentials: User = parse_body(request)?;
+                let valid = self.users.iter().any(|user| {
+                    user.username == credentials.username
+                        && user.password_hash == credentials.password_hash
+                });
+                if !valid {
+                    return Err(unauthorized("invalid username or password"));
+                }
+                Ok(Response::empty(StatusCode::OK)
+                    .with_cookie(self.issue_login(credentials.username)))
+            }
+            ("POST", ["logout"]) => {
+                self.authenticate(request)?;
+                if let Some(auth_token) = request.cookie(SESSION_COOKIE_NAME) {
+                    self.logins.remove(&AuthToken::from(auth_token));
+                }
+                Ok(Response::empty(StatusCode::OK).with_cookie(format!(
+                    "{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0",
+                    SESSION_COOKIE_NAME
+                )))
+            }
+            ("POST", ["renew"]) => {
+                // Expired tokens can still be renewed, only logged out ones cannot
+                let login = request
+                    .cookie(SESSION_COOKIE_NAME)
+                    .and_then(|auth_token| self.logins.remove(&AuthToken::from(auth_token)))
+                    .ok_or_else(|| unauthorized("not logged in"))?;
+                Ok(Response::empty(StatusCode::OK).with_cookie(self.issue_login(login.username)))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    fn sensors(&mut self, request: &Request) -> Result<Response, Response> {
+        self.authenticate(request)?;
+
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", []) => {
+                let sensor: Sensor = parse_body(request)?;
+                let record = SensorRecord {
+                    id: SensorId::new(self.next_id()),
+                    sensor_type: sensor.sensor_type,
+                };
+                self.sensors.push(record.clone());
+                Ok(Response::json(StatusCode::CREATED, &record))
+            }
+            ("GET", []) => Ok(Response::json(StatusCode::OK, &self.sensors)),
+            ("GET", [id]) => {
+                let index = self.sensor_index(id)?;
+                Ok(Response::json(StatusCode::OK, &self.sensors[index]))
+            }
+            ("PATCH", [id]) => {
+                let sensor: Sensor = parse_body(request)?;
+                let index = self.sensor_index(id)?;
+                self.sensors[index].sensor_type = sensor.sensor_type;
+                Ok(Response::json(StatusCode::OK, &self.sensors[index]))
+            }
+            ("DELETE", [id]) => {
+                let index = self.sensor_index(id)?;
+                if self.links.iter().any(|link| link.sensor_id.as_str() == *id) {
+                    return Err(conflict("sensor is linked to a session"));
+                }
+                self.sensors.remove(index);
+                Ok(Response::empty(StatusCode::NO_CONTENT))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    fn sessions(&mut self, request: &Request) -> Result<Response, Response> {
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", []) => {
+                let session: Session = parse_body(request)?;
+                self.find_user(session.username.as_str())?;
+                let record = SessionRecord {
+                    id: SessionId::new(self.next_id()),
+                    username: session.username,
+                };
+                self.sessions.push(record.clone());
+                Ok(Response::json(StatusCode::CREATED, &record))
+            }
+            ("GET", []) => Ok(Response::json(StatusCode::OK, &self.sessions)),
+            ("GET", ["user", username]) => {
+                let sessions: Vec<&SessionRecord> = self
+                    .sessions
+                    .iter()
+                    .filter(|session| session.username.as_str() == *username)
+                    .collect();
+                Ok(Response::json(StatusCode::OK, &sessions))
+            }
+            ("GET", ["id", id]) => {
+                self.authenticate(request)?;
+                let index = self.session_index(id)?;
+                Ok(Response::json(StatusCode::OK, &self.sessions[index]))
+            }
+            ("PATCH", [id]) => {
+                self.authenticate(request)?;
+                let session: Session = parse_body(request)?;
+                let index = self.session_index(id)?;
+                self.find_user(session.username.as_str())?;
+                self.sessions[index].username = session.username;
+                Ok(Response::json(StatusCode::OK, &self.sessions[index]))
+            }
+            ("DELETE", [id]) => {
+                self.authenticate(request)?;
+                let index = self.session_index(id)?;
+                if self
+                    .links
+                    .iter()
+                    .any(|link| link.session_id.as_str() == *id)
+                {
+                    return Err(conflict("session still has sensors"));
+                }
+                self.sessions.remove(index);
+                Ok(Response::empty(StatusCode::NO_CONTENT))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    fn session_sensors(&mut self, request: &Request) -> Result<Response, Response> {
+        self.authenticate(request)?;
+
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", []) => {
+                let link: SessionSensor = parse_body(request)?;
+                self.session_index(link.session_id.as_str())?;
+                self.sensor_index(link.sensor_id.as_str())?;
+                if self.links.iter().any(|l| l.sensor_id == link.sensor_id) {
+                    return Err(conflict("sensor is already linked to a session"));
+                }
+                let record = SessionSensorLink {
+                    id: SessionSensorId::new(self.next_id()),
+                    session_id: link.session_id,
+                    sensor_id: link.sensor_id,
+                };
+                self.links.push(record.clone());
+                Ok(Response::json(StatusCode::CREATED, &record))
+            }
+            ("GET", []) => Ok(Response::json(StatusCode::OK, &self.links)),
+            ("GET", ["session", session_id]) => {
+                let links: Vec<&SessionSensorLink> = self
+                    .links
+                    .iter()
+                    .filter(|link| link.session_id.as_str() == *session_id)
+                    .collect();
+                Ok(Response::json(StatusCode::OK, &links))
+            }
+            ("GET", ["session-sensor", sensor_id]) => {
+                let link = self
+                    .links
+                    .iter()
+                    .find(|link| link.sensor_id.as_str() == *sensor_id)
+                    .ok_or_else(|| not_found("session sensor"))?;
+                Ok(Response::json(StatusCode::OK, link))
+            }
+            ("PATCH", [id]) => {
+                let update: PatchSessionSensor = parse_body(request)?;
+                let index = self.link_index(id)?;
+                self.session_index(update.session_id.as_str())?;
+                self.sensor_index(update.sensor_id.as_str())?;
+                if self
+                    .links
+                    .iter()
+                    .any(|l| l.sensor_id == update.sensor_id && l.id.as_str() != *id)
+                {
+                    return Err(conflict("sensor is already linked to a session"));
+                }
+                self.links[index].session_id = update.session_id;
+                self.links[index].sensor_id = update.sensor_id;
+                Ok(Response::json(StatusCode::OK, &self.links[index]))
+            }
+            ("DELETE", [id]) => {
+                let index = self.link_index(id)?;
+                if self.datapoints.iter().any(|d| d.id.as_str() == *id) {
+                    return Err(conflict("session sensor still has datapoints"));
+                }
+                self.links.remove(index);
+                Ok(Response::empty(StatusCode::NO_CONTENT))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    fn datapoints(&mut self, request: &Request) -> Result<Response, Response> {
+        self.authenticate(request)?;
+
+        match (request.method.as_str(), &request.segments()[1..]) {
+            ("POST", []) => {
+                let data: SessionSensorData = parse_body(request)?;
+                let datapoint = self.insert_datapoints(vec![data])?.remove(0);
+                Ok(Response::json(StatusCode::CREATED, &datapoint))
+            }
+            ("POST", ["batch"]) => {
+                let batch: Batch = parse_body(request)?;
+                self.insert_datapoints(batch.datapoints)?;
+                Ok(Response::empty(StatusCode::CREATED))
+            }
+            ("GET", []) => Ok(Response::json(StatusCode::OK, &self.datapoints)),
+            ("GET", ["session", session_id]) => {
+                let datapoints = self.session_datapoints(session_id, None);
+                Ok(Response::json(StatusCode::OK, &datapoints))
+            }
+            ("GET", ["session", session_id, datetime]) => {
+                let datapoints =
+                    self.session_datapoints(session_id, Some(parse_datetime(datetime)?));
+                Ok(Response::json(StatusCode::OK, &datapoints))
+            }
+            ("GET", ["id", id]) => {
+                let datapoints: Vec<&Datapoint> = self
+                    .datapoints
+                    .iter()
+                    .filter(|datapoint| datapoint.id.as_str() == *id)
+                    .collect();
+                Ok(Response::json(StatusCode::OK, &datapoints))
+            }
+            ("GET", [id, datetime]) => {
+                let index = self.datapoint_index(id, parse_datetime(datetime)?)?;
+                Ok(Response::json(StatusCode::OK, &self.datapoints[index]))
+            }
+            ("PATCH", [id, datetime]) => {
+                let data: SessionSensorData = parse_body(request)?;
+                let index = self.datapoint_index(id, parse_datetime(datetime)?)?;
+                self.datapoints[index].data_blob = data.data_blob;
+                Ok(Response::json(StatusCode::OK, &self.datapoints[index]))
+            }
+            ("DELETE", [id, datetime]) => {
+                let index = self.datapoint_index(id, parse_datetime(datetime)?)?;
+                self.datapoints.remove(index);
+                Ok(Response::empty(StatusCode::NO_CONTENT))
+            }
+            _ => Err(not_found("route")),
+        }
+    }
+
+    /// Store new datapoints, rejecting all of them if any is invalid
+    fn insert_datapoints(
+        &mut self,
+        datapoints: Vec<SessionSensorData>,
+    ) -> Result<Vec<Datapoint>, Response> {
+        let mut inserted: Vec<Datapoint> = Vec::with_capacity(datapoints.len());
+        for data in datapoints {
+            self.link_index(data.id.as_str())?;
+            let duplicate = self
+                .datapoints
+                .iter()
+                .chain(&inserted)
+                .any(|d| d.id == data.id && d.datetime == data.datetime);
+            if duplicate {
+                return Err(conflict("datapoint already exists"));
+            }
+            inserted.push(data);
+        }
+
+        self.datapoints.extend(inserted.iter().cloned());
+        Ok(inserted)
+    }
+
+    /// Get the datapoints of all sensors linked to a session, optionally only those after a
+    /// datetime
+    fn session_datapoints(&self, session_id: &str, after: Option<Timestamp>) -> Vec<&Datapoint> {
+        self.datapoints
+            .iter()
+            .filter(|datapoint| {
+                self.links
+                    .iter()
+                    .any(|link| link.id == datapoint.id && link.session_id.as_str() == session_id)
+            })
+            .filter(|datapoint| after.is_none_or(|after| datapoint.datetime > after))
+            .collect()
+    }
+
+    /// Get the user owning the request's auth token
+    fn authenticate(&self, request: &Request) -> Result<Username, Response> {
+        let auth_token = request
+            .cookie(SESSION_COOKIE_NAME)
+            .ok_or_else(|| unauthorized("not logged in"))?;
+        let login = self
+            .logins
+            .get(&AuthToken::from(auth_token))
+            .ok_or_else(|| unauthorized("invalid session"))?;
+        if login.expires_at <= SystemTime::now() {
+            return Err(unauthorized("session expired"));
+        }
+        Ok(login.username.clone())
+    }
+
+    /// Create a login session and return the `Set-Cookie` header value for it
+    fn issue_login(&mut self, username: Username) -> String {
+        let auth_token = AuthToken::new(format!("{:032x}", fastrand::u128(..)));
+        let cookie = format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+            SESSION_COOKIE_NAME,
+            auth_token.as_str(),
+            self.session_ttl.as_secs()
+        );
+        self.logins.insert(
+            auth_token,
+            LoginSession {
+                username,
+                expires_at: SystemTime::now() + self.session_ttl,
+            },
+        );
+        cookie
+    }
+
+    fn next_id(&mut self) -> String {
+        let id = self.next_id;
+        self.next_id += 1;
+        id.to_string()
+    }
+
+    fn find_user(&self, username: &str) -> Result<&User, Response> {
+        self.users
+            .iter()
+            .find(|user| user.username.as_str() == username)
+            .ok_or_else(|| not_found("user"))
+    }
+
+    fn user_index(&self, username: &str) -> Result<usize, Response> {
+        self.users
+            .iter()
+            .position(|user| user.username.as_str() == username)
+            .ok_or_else(|| not_found("user"))
+    }
+
+    fn sensor_index(&self, id: &str) -> Result<usize, Response> {
+        self.sensors
+            .iter()
+            .position(|sensor| sensor.id.as_str() == id)
+            .ok_or_else(|| not_found("sensor"))
+    }
+
+    fn session_index(&self, id: &str) -> Result<usize, Response> {
+        self.sessions
+            .iter()
+            .position(|session| session.id.as_str() == id)
+            .ok_or_else(|| not_found("session"))
+    }
+
+    fn link_index(&self, id: &str) -> Result<usize, Response> {
+        self.links
+            .iter()
+            .position(|link| link.id.as_str() == id)
+            .ok_or_else(|| not_found("session sensor"))
+    }
+
+    fn datapoint_index(&self, id: &str, datetime: Timestamp) -> Result<usize, Response> {
+        self.datapoints
+            .iter()
+            .position(|d| d.id.as_str() == id && d.datetime == datetime)
+            .ok_or_else(|| not_found("datapoint"))
+    }
+}
+
+/// Parse a datetime path segment, answering `400 Bad Request` if it is not a timestamp
+fn parse_datetime(segment: &str) -> Result<Timestamp, Response> {
+    Timestamp::parse(segment).map_err(|e| Response::error(StatusCode::BAD_REQUEST, &e.to_string()))
+}
+
+/// Decode the JSON body of a request, answering `400 Bad Request` if it does not match
+fn parse_body<T: DeserializeOwned>(request: &Request) -> Result<T, Response> {
+    serde_json::from_slice(&request.body)
+        .map_err(|e| Response::error(StatusCode::BAD_REQUEST, &e.to_string()))
+}
+
+fn unauthorized(message: &str) -> Response {
+    Response::error(StatusCode::UNAUTHORIZED, message)
+}
+
+fn not_found(what: &str) -> Response {
+    Response::error(StatusCode::NOT_FOUND, &format!("{} not found", what))
+}
+
+fn conflict(message: &str) -> Response {
+    Response::error(StatusCode::CONFLICT, message)
+}
@@ -0,0 +1,575 @@
+//! Durable queue for datapoint uploads that failed because the backend was unavailable
+
+use crate::api::session_sensor_data::{Datapoint, SessionSensorData};
+use crate::api_client::ApiClient;
+use crate::error::ApiError;
+use crate::requests::limits::Priority;
+use crate::requests::retry;
+use crate::reqwest::StatusCode;
+use crate::timestamp::Timestamp;
+use crate::types::SessionSensorId;
+use futures::lock::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{self, Debug};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Mutating request waiting in the outbox
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PendingRequest {
+    CreateDatapoint { datapoint: SessionSensorData },
+    BatchCreateDatapoint { datapoints: Vec<SessionSensorData> },
+}
+
+impl PendingRequest {
+    /// Get the datapoints the request creates
+    pub fn datapoints(&self) -> &[SessionSensorData] {
+        match self {
+            PendingRequest::CreateDatapoint { datapoint } => std::slice::from_ref(datapoint),
+            PendingRequest::BatchCreateDatapoint { datapoints } => datapoints,
+        }
+    }
+}
+
+/// Storage for the requests in an `Outbox`
+///
+/// Requests are appended as they fail and the whole content is replaced after a replay.
+pub trait OutboxStorage: Debug + Send + Sync {
+    /// Add a request after all stored ones
+    fn append(&self, request: &PendingRequest) -> io::Result<()>;
+
+    /// Load all stored requests, oldest first
+    fn load(&self) -> io::Result<Vec<PendingRequest>>;
+
+    /// Replace all stored requests
+    fn replace(&self, requests: &[PendingRequest]) -> io::Result<()>;
+}
+
+/// Outbox storage that keeps the requests in memory only
+#[derive(Debug, Default)]
+pub struct MemoryOutboxStorage {
+    requests: std::sync::Mutex<Vec<PendingRequest>>,
+}
+
+impl MemoryOutboxStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl OutboxStorage for MemoryOutboxStorage {
+    fn append(&self, request: &PendingRequest) -> io::Result<()> {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        requests.push(request.clone());
+        Ok(())
+    }
+
+    fn load(&self) -> io::Result<Vec<PendingRequest>> {
+        Ok(self
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn replace(&self, requests: &[PendingRequest]) -> io::Result<()> {
+        *self.requests.lock().unwrap_or_else(|e| e.into_inner()) = requests.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::FileOutboxStorage;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod file {
+    use super::{OutboxStorage, PendingRequest};
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+    use std::path::{Path, PathBuf};
+
+    /// Outbox storage that appends the requests to a file, one JSON object per line
+    #[derive(Debug, Clone)]
+    pub struct FileOutboxStorage {
+        path: PathBuf,
+    }
+
+    impl FileOutboxStorage {
+        /// Create a storage that reads and writes the given file
+        ///
+        /// The file and its parent directories are created on the first append.
+        pub fn new(path: impl Into<PathBuf>) -> Self {
+            Self { path: path.into() }
+        }
+
+        /// Get the path of the file
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl OutboxStorage for FileOutboxStorage {
+        fn append(&self, request: &PendingRequest) -> io::Result<()> {
+            let mut line = serde_json::to_vec(request)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            line.push(b'\n');
+
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(&self.path)?;
+            let created = drop_torn_line(&mut file)? == 0;
+            file.write_all(&line)?;
+            file.sync_data()?;
+            if created {
+                sync_dir(&self.path)?;
+            }
+            Ok(())
+        }
+
+        fn load(&self) -> io::Result<Vec<PendingRequest>> {
+            let contents = match fs::read(&self.path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e),
+            };
+
+            let lines: Vec<&[u8]> = contents.split(|byte| *byte == b'\n').collect();
+            let mut requests = Vec::with_capacity(lines.len());
+            for (index, line) in lines.iter().enumerate() {
+                if line.is_empty() {
+                    continue;
+                }
+                match serde_json::from_slice(line) {
+                    Ok(request) => requests.push(request),
+                    // Only the last line, without its newline, can be cut short by a crash
+                    // during `append`
+                    Err(_) if index + 1 == lines.len() => {}
+                    Err(e) => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("line {} of {}: {}", index + 1, self.path.display(), e),
+                        ))
+                    }
+                }
+            }
+            Ok(requests)
+        }
+
+        fn replace(&self, requests: &[PendingRequest]) -> io::Result<()> {
+            if requests.is_empty() {
+                match fs::remove_file(&self.path) {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+                    result => result?,
+                }
+                return sync_dir(&self.path);
+            }
+
+            let mut contents = Vec::new();
+            for request in requests {
+                serde_json::to_writer(&mut contents, request)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                contents.push(b'\n');
+            }
+
+            // Write and sync a temporary file first so a crash never loses the queued requests
+            let tmp_path = self.path.with_extension("tmp");
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&contents)?;
+            file.sync_data()?;
+            fs::rename(&tmp_path, &self.path)?;
+            sync_dir(&self.path)
+        }
+    }
+
+    /// Cut off a last line left without its newline by a crash, so the next line does not run
+    /// into it, and return the length of the file
+    fn drop_torn_line(file: &mut File) -> io::Result<u64> {
+        let len = file.metadata()?.len();
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut last = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] == b'\n' {
+            return Ok(len);
+        }
+
+        let mut contents = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut contents)?;
+        let kept = contents
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |newline| newline + 1) as u64;
+        file.set_len(kept)?;
+        Ok(kept)
+    }
+
+    /// Make the creation, rename or removal of the file at `path` survive a power loss
+    #[cfg(unix)]
+    fn sync_dir(path: &Path) -> io::Result<()> {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    fn sync_dir(_path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Error returned by the outbox
+#[derive(Debug)]
+pub enum OutboxError {
+    /// The server rejected the request, or it failed for another reason than the backend being
+    /// unavailable
+    Api(ApiError),
+    /// The outbox storage could not be read or written
+    Storage(io::Error),
+}
+
+impl fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutboxError::Api(e) => write!(f, "{}", e),
+            OutboxError::Storage(e) => write!(f, "failed to access the outbox: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OutboxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OutboxError::Api(e) => Some(e),
+            OutboxError::Storage(e) => Some(e),
+        }
+    }
+}
+
+impl From<ApiError> for OutboxError {
+    fn from(e: ApiError) -> Self {
+        OutboxError::Api(e)
+    }
+}
+
+impl From<io::Error> for OutboxError {
+    fn from(e: io::Error) -> Self {
+        OutboxError::Storage(e)
+    }
+}
+
+/// Result of a request sent through the outbox
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery<T> {
+    /// The server accepted the request
+    Sent(T),
+    /// The backend was unavailable or earlier requests are still queued, so the request was
+    /// stored for a later replay
+    Queued,
+}
+
+/// Datapoint the server rejected during a replay
+#[derive(Debug)]
+pub struct FailedDatapoint {
+    pub datapoint: SessionSensorData,
+    pub error: ApiError,
+}
+
+/// Outcome of replaying the outbox
+#[derive(Debug, Default)]
+pub struct ReplayReport {
+    /// Number of datapoints the server accepted
+    pub sent: usize,
+    /// Number of datapoints skipped because they were queued twice or already stored
+    pub duplicates: usize,
+    /// Datapoints the server rejected, which are removed from the outbox
+    pub failed: Vec<FailedDatapoint>,
+    /// Number of requests still queued because the backend became unavailable again
+    pub remaining: usize,
+}
+
+impl ReplayReport {
+    fn merge(&mut self, other: ReplayReport) {
+        self.sent += other.sent;
+        self.duplicates += other.duplicates;
+        self.failed.extend(other.failed);
+        self.remaining = other.remaining;
+    }
+}
+
+/// Outcome of sending one datapoint during a replay
+enum Outcome {
+    Sent,
+    Duplicate,
+    Unavailable,
+    Failed(ApiError),
+}
+
+/// Sends datapoint uploads, keeping the ones that fail while the backend is unavailable
+///
+/// A request is queued when the backend cannot be reached (`ApiError::Transport` or
+/// `ApiError::Timeout`) or answers with a status that may pass: a server error (`5xx`, e.g.
+/// from a gateway while the backend restarts), `408 Request Timeout`, `429 Too Many Requests`
+/// or `401 Unauthorized` when the session could not be renewed. Only other `4xx` statuses,
+/// such as `400`, `404` or `422`, reject a request for good. While
+/// requests are queued, new ones are queued behind them without being sent, so that datapoints
+/// reach the server in the order they were recorded. Call `replay` or `replay_until_empty` once
+/// the backend is reachable again, e.g. on startup and when the device comes back online.
+#[derive(Debug)]
+pub struct Outbox {
+    client: ApiClient,
+    storage: Arc<dyn OutboxStorage>,
+    // Number of queued requests, read from the storage on first use. Held while sending so
+    // that replays and new requests do not interleave
+    queued: Mutex<Option<usize>>,
+}
+
+impl Outbox {
+    /// Create an outbox sending through `client` and queueing into `storage`
+    pub fn new(client: ApiClient, storage: Arc<dyn OutboxStorage>) -> Self {
+        Self {
+            client,
+            storage,
+            queued: Mutex::new(None),
+        }
+    }
+
+    /// Get the requests waiting to be replayed
+    pub fn pending(&self) -> io::Result<Vec<PendingRequest>> {
+        self.storage.load()
+    }
+
+    /// Send request to create a new datapoint, queueing it if the backend is unavailable
+    pub async fn create_datapoint(
+        &self,
+        id: &SessionSensorId,
+        datetime: Timestamp,
+        data_blob: &str,
+    ) -> Result<Delivery<Datapoint>, OutboxError> {
+        let datapoint = SessionSensorData {
+            id: id.clone(),
+            datetime,
+            data_blob: data_blob.to_string(),
+        };
+        let mut queued = self.queued.lock().await;
+
+        if self.queued_len(&mut queued)? > 0 {
+            self.queue(&mut queued, PendingRequest::CreateDatapoint { datapoint })?;
+            return Ok(Delivery::Queued);
+        }
+
+        match self
+            .client
+            .datapoints()
+            .create(id, datetime, data_blob)
+            .await
+        {
+            Ok(created) => Ok(Delivery::Sent(created)),
+            Err(e) if is_unavailable(&e) => {
+                self.queue(&mut queued, PendingRequest::CreateDatapoint { datapoint })?;
+                Ok(Delivery::Queued)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Send request to batch create new datapoints, queueing them if the backend is unavailable
+    pub async fn batch_create_datapoint(
+        &self,
+        datapoints: Vec<SessionSensorData>,
+    ) -> Result<Delivery<()>, OutboxError> {
+        let mut queued = self.queued.lock().await;
+
+        if self.queued_len(&mut queued)? > 0 {
+            self.queue(
+                &mut queued,
+                PendingRequest::BatchCreateDatapoint { datapoints },
+            )?;
+            return Ok(Delivery::Queued);
+        }
+
+        match self
+            .client
+            .datapoints()
+            .send_batch(datapoints.clone())
+            .await
+        {
+            Ok(()) => Ok(Delivery::Sent(())),
+            Err(e) if is_unavailable(&e) => {
+                self.queue(
+                    &mut queued,
+                    PendingRequest::BatchCreateDatapoint { datapoints },
+                )?;
+                Ok(Delivery::Queued)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Send all queued requests in order
+    ///
+    /// Replayed requests are sent with `Priority::Background`, behind interactive calls when
+    /// the client is rate limited.
+    /// Datapoints with an `(id, datetime)` pair seen before in the outbox, or that the server
+    /// already has (`409 Conflict`), are skipped. If the backend becomes unavailable again, the
+    /// unsent requests stay queued.
+    pub async fn replay(&self) -> Result<ReplayReport, OutboxError> {
+        let mut queued = self.queued.lock().await;
+        self.replay_locked(&mut queued).await
+    }
+
+    /// Replay the outbox every `interval` until it is empty
+    pub async fn replay_until_empty(
+        &self,
+        interval: Duration,
+    ) -> Result<ReplayReport, OutboxError> {
+        let mut report = ReplayReport::default();
+        loop {
+            report.merge(self.replay().await?);
+            if report.remaining == 0 {
+                return Ok(report);
+            }
+            retry::sleep(interval).await;
+        }
+    }
+
+    /// Get the number of queued requests, reading the storage the first time
+    fn queued_len(&self, queued: &mut Option<usize>) -> io::Result<usize> {
+        match *queued {
+            Some(len) => Ok(len),
+            None => Ok(*queued.insert(self.storage.load()?.len())),
+        }
+    }
+
+    /// Add a request after the queued ones
+    fn queue(&self, queued: &mut Option<usize>, request: PendingRequest) -> io::Result<()> {
+        let len = self.queued_len(queued)?;
+        self.storage.append(&request)?;
+        *queued = Some(len + 1);
+        Ok(())
+    }
+
+    async fn replay_locked(&self, queued: &mut Option<usize>) -> Result<ReplayReport, OutboxError> {
+        let pending = self.storage.load()?;
+        let mut report = ReplayReport::default();
+        let mut seen = HashSet::new();
+
+        for (index, request) in pending.iter().enumerate() {
+            let mut datapoints: Vec<SessionSensorData> = Vec::new();
+            for datapoint in request.datapoints() {
+                if seen.insert((datapoint.id.clone(), datapoint.datetime)) {
+                    datapoints.push(datapoint.clone());
+                } else {
+                    report.duplicates += 1;
+                }
+            }
+
+            let unsent = self.send_datapoints(datapoints, &mut report).await;
+            if let Some(unsent) = unsent {
+                // The backend is unavailable again: keep what is left, in order
+                let mut remaining =
+                    vec![PendingRequest::BatchCreateDatapoint { datapoints: unsent }];
+                remaining.extend_from_slice(&pending[index + 1..]);
+                self.storage.replace(&remaining)?;
+                *queued = Some(remaining.len());
+                report.remaining = remaining.len();
+                return Ok(report);
+            }
+        }
+
+        self.storage.replace(&[])?;
+        *queued = Some(0);
+        Ok(report)
+    }
+
+    /// Get the handle replayed requests are sent through
+    fn replay_client(&self) -> ApiClient {
+        self.client.with_priority(Priority::Background)
+    }
+
+    /// Send the datapoints of one queued request
+    ///
+    /// A rejected batch is sent again one datapoint at a time, so that a single duplicate or
+    /// invalid datapoint does not fail the others. Returns the datapoints not yet sent if the
+    /// backend became unavailable.
+    async fn send_datapoints(
+        &self,
+        datapoints: Vec<SessionSensorData>,
+        report: &mut ReplayReport,
+    ) -> Option<Vec<SessionSensorData>> {
+        match datapoints.len() {
+            0 => return None,
+            1 => {}
+            len => match self
+                .replay_client()
+                .datapoints()
+                .send_batch(datapoints.clone())
+                .await
+            {
+                Ok(()) => {
+                    report.sent += len;
+                    return None;
+                }
+                Err(e) if is_unavailable(&e) => return Some(datapoints),
+                Err(_) => {}
+            },
+        }
+
+        for (index, datapoint) in datapoints.iter().enumerate() {
+            match self.send_datapoint(datapoint).await {
+                Outcome::Sent => report.sent += 1,
+                Outcome::Duplicate => report.duplicates += 1,
+                Outcome::Failed(error) => report.failed.push(FailedDatapoint {
+                    datapoint: datapoint.clone(),
+                    error,
+                }),
+                Outcome::Unavailable => return Some(datapoints[index..].to_vec()),
+            }
+        }
+        None
+    }
+
+    async fn send_datapoint(&self, datapoint: &SessionSensorData) -> Outcome {
+        let result = self
+            .replay_client()
+            .datapoints()
+            .create(&datapoint.id, datapoint.datetime, &datapoint.data_blob)
+            .await;
+
+        match result {
+            Ok(_) => Outcome::Sent,
+            Err(e) if is_unavailable(&e) => Outcome::Unavailable,
+            Err(e) if e.status() == Some(StatusCode::CONFLICT) => Outcome::Duplicate,
+            Err(e) => Outcome::Failed(e),
+        }
+    }
+}
+
+/// Check whether a request failed because the backend is unavailable for now, rather than
+/// because the server rejected it for good
+fn is_unavailable(e: &ApiError) -> bool {
+    match e {
+        ApiError::Status { status, .. } => {
+            !status.is_client_error()
+                || matches!(
+                    *status,
+                    StatusCode::UNAUTHORIZED
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                )
+        }
+        _ => matches!(e, ApiError::Transport(_) | ApiError::Timeout(_)),
+    }
+}
@@ -1,122 +1,435 @@
-use std::env;
+use crate::reqwest::{Method, Url};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::fmt;
 
-/// Get the API base URL from an environment variable
-fn get_base_url() -> String {
-    dotenv::dotenv().ok();
-    env::var("API_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:7878".to_string())
+/// Characters left as they are in a path segment: the unreserved characters of RFC 3986
+///
+/// Everything else is percent-encoded, including `/`, `?` and `#`, which would change the
+/// route, and `+` and `:`, which some servers decode differently.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Validated base URL that all endpoint URLs are built from
+///
+/// The base URL must be an absolute `http` or `https` URL without query or fragment. It may
+/// contain a path, e.g. `https://example.com/api`, which is kept in front of every endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BaseUrl {
+    // Serialized URL without a trailing slash
+    url: String,
+}
+
+/// Error returned when a base URL cannot be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBaseUrl {
+    input: String,
+    reason: String,
+}
+
+impl fmt::Display for InvalidBaseUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid base URL `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidBaseUrl {}
+
+impl BaseUrl {
+    /// Parse and validate a base URL
+    pub fn parse(input: &str) -> Result<Self, InvalidBaseUrl> {
+        let invalid = |reason: &str| InvalidBaseUrl {
+            input: input.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let url = Url::parse(input.trim()).map_err(|e| invalid(&e.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(invalid("scheme must be http or https"));
+        }
+        if url.host_str().is_none() {
+            return Err(invalid("missing host"));
+        }
+        if url.query().is_some() || url.fragment().is_some() {
+            return Err(invalid("must not have a query or fragment"));
+        }
+
+        Ok(Self {
+            url: url.as_str().trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Get the base URL without a trailing slash
+    pub fn as_str(&self) -> &str {
+        &self.url
+    }
+
+    /// Build the URL of an endpoint from its path segments, percent-encoding each of them
+    pub fn join<'a>(&self, segments: impl IntoIterator<Item = &'a str>) -> String {
+        let mut url = self.url.clone();
+        for segment in segments {
+            url.push('/');
+            // Dot segments would be removed when the URL is normalized
+            match segment {
+                "." => url.push_str("%2E"),
+                ".." => url.push_str("%2E%2E"),
+                _ => url.extend(utf8_percent_encode(segment, PATH_SEGMENT)),
+            }
+        }
+        url
+    }
+}
+
+impl fmt::Display for BaseUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.url)
+    }
+}
+
+/// Endpoint of the API, named after its `api::*` function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Route {
+    pub name: &'static str,
+    pub method: &'static str,
+    /// Path below the base URL, with a `{...}` placeholder for each parameter
+    pub template: &'static str,
+}
+
+const fn route(name: &'static str, method: &'static str, template: &'static str) -> Route {
+    Route {
+        name,
+        method,
+        template,
+    }
+}
+
+/// Every endpoint, with fixed paths listed before the parameterized paths they overlap
+pub const ROUTES: &[Route] = &[
+    route("create_user", "POST", "/users"),
+    route("view_all_users", "GET", "/users"),
+    route("view_user_profile", "GET", "/users/profile"),
+    route("view_user_by_username", "GET", "/users/{username}"),
+    route("update_user", "PATCH", "/users/{username}"),
+    route("delete_user", "DELETE", "/users/{username}"),
+    route("user_login", "POST", "/authentication/login"),
+    route("user_logout", "POST", "/authentication/logout"),
+    route("renew_session", "POST", "/authentication/renew"),
+    route("create_sensor", "POST", "/sensors"),
+    route("view_all_sensors", "GET", "/sensors"),
+    route("view_sensor_by_id", "GET", "/sensors/{id}"),
+    route("update_sensor", "PATCH", "/sensors/{id}"),
+    route("delete_sensor", "DELETE", "/sensors/{id}"),
+    route("create_session", "POST", "/sessions"),
+    route("view_all_sessions", "GET", "/sessions"),
+    route("view_sessions_by_user", "GET", "/sessions/user/{username}"),
+    route("view_session_by_id", "GET", "/sessions/id/{id}"),
+    route("update_session", "PATCH", "/sessions/{id}"),
+    route("delete_session", "DELETE", "/sessions/{id}"),
+    route("create_session_sensor", "POST", "/sessions-sensors"),
+    route("view_all_sensor_sessions", "GET", "/sessions-sensors"),
+    route(
+        "view_sensors_by_session_id",
+        "GET",
+        "/sessions-sensors/session/{session_id}",
+    ),
+    route(
+        "view_session_sensor_by_sensor_id",
+        "GET",
+        "/sessions-sensors/session-sensor/{sensor_id}",
+    ),
+    route("update_sensor_session", "PATCH", "/sessions-sensors/{id}"),
+    route("delete_sensor_session", "DELETE", "/sessions-sensors/{id}"),
+    route("create_datapoint", "POST", "/sessions-sensors-data"),
+    route(
+        "batch_create_datapoint",
+        "POST",
+        "/sessions-sensors-data/batch",
+    ),
+    route("view_all_datapoints", "GET", "/sessions-sensors-data"),
+    route(
+        "view_datapoints_by_session_id",
+        "GET",
+        "/sessions-sensors-data/session/{session_id}",
+    ),
+    route(
+        "view_datapoints_by_session_sensor",
+        "GET",
+        "/sessions-sensors-data/id/{id}",
+    ),
+    route(
+        "view_all_datapoints_by_id_datetime",
+        "GET",
+        "/sessions-sensors-data/session/{session_id}/{datetime}",
+    ),
+    route(
+        "view_datapoints_by_id_datetime",
+        "GET",
+        "/sessions-sensors-data/{id}/{datetime}",
+    ),
+    route(
+        "update_datapoint",
+        "PATCH",
+        "/sessions-sensors-data/{id}/{datetime}",
+    ),
+    route(
+        "delete_datapoint",
+        "DELETE",
+        "/sessions-sensors-data/{id}/{datetime}",
+    ),
+];
+
+impl Route {
+    /// Find the endpoint a request built from `base_url` goes to
+    pub fn find(base_url: &BaseUrl, method: &Method, url: &str) -> Option<&'static Route> {
+        let path = url.strip_prefix(base_url.as_str())?;
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+        ROUTES
+            .iter()
+            .find(|route| route.method == method.as_str() && route.matches(path))
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let mut segments = path.split('/');
+        let mut template = self.template.split('/');
+        loop {
+            match (segments.next(), template.next()) {
+                (None, None) => return true,
+                (Some(segment), Some(expected)) if expected.starts_with('{') => {
+                    if segment.is_empty() {
+                        return false;
+                    }
+                }
+                (Some(segment), Some(expected)) if segment == expected => {}
+                _ => return false,
+            }
+        }
+    }
 }
 
 /// Module for obtaining User related endpoint URLs
 pub mod user {
-    use super::get_base_url;
+    use super::BaseUrl;
 
-    pub fn get_user_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/users", base_url)
+    pub fn get_user_url(base_url: &BaseUrl) -> String {
+        base_url.join(["users"])
     }
 
-    pub fn get_profile_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/users/profile", base_url)
+    pub fn get_profile_url(base_url: &BaseUrl) -> String {
+        base_url.join(["users", "profile"])
     }
 
-    pub fn get_username_url(username: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/users/{}", base_url, username)
+    pub fn get_username_url(base_url: &BaseUrl, username: &str) -> String {
+        base_url.join(["users", username])
     }
 }
 
 /// Module for obtaining authentication related endpoint URLs
 pub mod auth {
-    use super::get_base_url;
+    use super::BaseUrl;
 
-    pub fn get_login_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/authentication/login", base_url)
+    pub fn get_login_url(base_url: &BaseUrl) -> String {
+        base_url.join(["authentication", "login"])
     }
 
-    pub fn get_logout_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/authentication/logout", base_url)
+    pub fn get_logout_url(base_url: &BaseUrl) -> String {
+        base_url.join(["authentication", "logout"])
     }
 
-    pub fn get_renew_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/authentication/renew", base_url)
+    pub fn get_renew_url(base_url: &BaseUrl) -> String {
+        base_url.join(["authentication", "renew"])
     }
 }
 
 /// Module for obtaining sensor related endpoint URLs
 pub mod sensor {
-    use super::get_base_url;
+    use super::BaseUrl;
 
-    pub fn get_sensor_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/sensors", base_url)
+    pub fn get_sensor_url(base_url: &BaseUrl) -> String {
+        base_url.join(["sensors"])
     }
 
-    pub fn get_sensor_id_url(sensor_id: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sensors/{}", base_url, sensor_id)
+    pub fn get_sensor_id_url(base_url: &BaseUrl, sensor_id: &str) -> String {
+        base_url.join(["sensors", sensor_id])
     }
 }
 
 /// Module for obtaining session related endpoint URLs
 pub mod session {
-    use super::get_base_url;
+    use super::BaseUrl;
 
-    pub fn get_sessions_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions", base_url)
+    pub fn get_sessions_url(base_url: &BaseUrl) -> String {
+        base_url.join(["sessions"])
     }
 
-    pub fn get_sessions_exp_url(endpoint: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions/{}", base_url, endpoint)
+    pub fn get_sessions_exp_url(base_url: &BaseUrl, endpoint: &str) -> String {
+        base_url.join(["sessions", endpoint])
     }
 
-    pub fn get_sessions_subpath_url(subpath: &str, endpoint: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions/{}/{}", base_url, subpath, endpoint)
+    pub fn get_sessions_subpath_url(base_url: &BaseUrl, subpath: &str, endpoint: &str) -> String {
+        base_url.join(["sessions", subpath, endpoint])
     }
 
-    pub fn get_session_sensors_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors", base_url)
+    pub fn get_session_sensors_url(base_url: &BaseUrl) -> String {
+        base_url.join(["sessions-sensors"])
     }
 
-    pub fn get_session_sensors_id_url(id: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors/{}", base_url, id)
+    pub fn get_session_sensors_id_url(base_url: &BaseUrl, id: &str) -> String {
+        base_url.join(["sessions-sensors", id])
     }
 
-    pub fn get_session_sensors_subpath_url(subpath: &str, id: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors/{}/{}", base_url, subpath, id)
+    pub fn get_session_sensors_subpath_url(base_url: &BaseUrl, subpath: &str, id: &str) -> String {
+        base_url.join(["sessions-sensors", subpath, id])
     }
 }
 
 /// Module for obtaining datapoint related endpoint URLs
 pub mod datapoint {
-    use super::get_base_url;
+    use super::BaseUrl;
 
-    pub fn get_datapoint_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors-data", base_url)
+    pub fn get_datapoint_url(base_url: &BaseUrl) -> String {
+        base_url.join(["sessions-sensors-data"])
     }
 
-    pub fn get_batch_url() -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors-data/batch", base_url)
+    pub fn get_batch_url(base_url: &BaseUrl) -> String {
+        base_url.join(["sessions-sensors-data", "batch"])
     }
 
-    pub fn get_datapoint_subpath_url(subpath: &str, id: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors-data/{}/{}", base_url, subpath, id)
+    pub fn get_datapoint_subpath_url(base_url: &BaseUrl, subpath: &str, id: &str) -> String {
+        base_url.join(["sessions-sensors-data", subpath, id])
+    }
+
+    pub fn get_datapoint_datetime_url(base_url: &BaseUrl, id: &str, datetime: &str) -> String {
+        base_url.join(["sessions-sensors-data", "session", id, datetime])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{datapoint, session, user, BaseUrl, Route, ROUTES};
+    use crate::reqwest::Method;
+
+    fn base_url() -> BaseUrl {
+        BaseUrl::parse("http://127.0.0.1:7878").unwrap()
+    }
+
+    #[test]
+    fn validates_base_url() {
+        assert_eq!(base_url().as_str(), "http://127.0.0.1:7878");
+        assert_eq!(
+            BaseUrl::parse(" https://example.com/api/ ")
+                .unwrap()
+                .as_str(),
+            "https://example.com/api"
+        );
+        for input in [
+            "",
+            "127.0.0.1:7878",
+            "/api",
+            "ftp://example.com",
+            "http://",
+            "http://example.com/?page=1",
+            "http://example.com/#top",
+        ] {
+            assert!(BaseUrl::parse(input).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn keeps_base_path() {
+        let base_url = BaseUrl::parse("https://example.com/api/v1").unwrap();
+        assert_eq!(
+            user::get_profile_url(&base_url),
+            "https://example.com/api/v1/users/profile"
+        );
+    }
+
+    #[test]
+    fn encodes_awkward_segments() {
+        let base_url = base_url();
+        assert_eq!(
+            user::get_username_url(&base_url, "jane doe"),
+            "http://127.0.0.1:7878/users/jane%20doe"
+        );
+        assert_eq!(
+            user::get_username_url(&base_url, "a/b?c#d"),
+            "http://127.0.0.1:7878/users/a%2Fb%3Fc%23d"
+        );
+        assert_eq!(
+            user::get_username_url(&base_url, "100%"),
+            "http://127.0.0.1:7878/users/100%25"
+        );
+        assert_eq!(
+            user::get_username_url(&base_url, "zoë"),
+            "http://127.0.0.1:7878/users/zo%C3%AB"
+        );
+        assert_eq!(
+            user::get_username_url(&base_url, ".."),
+            "http://127.0.0.1:7878/users/%2E%2E"
+        );
+        assert_eq!(
+            session::get_sessions_subpath_url(&base_url, "user", "a.b-c_d~e"),
+            "http://127.0.0.1:7878/sessions/user/a.b-c_d~e"
+        );
+        assert_eq!(
+            datapoint::get_datapoint_datetime_url(&base_url, "7", "2025-01-01T01:00:00+01:00"),
+            "http://127.0.0.1:7878/sessions-sensors-data/session/7/2025-01-01T01%3A00%3A00%2B01%3A00"
+        );
+    }
+
+    #[test]
+    fn encoded_urls_parse_to_the_same_segments() {
+        let url = user::get_username_url(&base_url(), "a/b c+d");
+        let url = crate::reqwest::Url::parse(&url).unwrap();
+        let segments: Vec<&str> = url.path_segments().unwrap().collect();
+        assert_eq!(segments, ["users", "a%2Fb%20c%2Bd"]);
+    }
+
+    #[test]
+    fn finds_the_route_of_every_url() {
+        let base_url = BaseUrl::parse("https://example.com/api").unwrap();
+        let find = |method: Method, url: &str| Route::find(&base_url, &method, url).map(|r| r.name);
+
+        assert_eq!(
+            find(Method::GET, &user::get_profile_url(&base_url)),
+            Some("view_user_profile")
+        );
+        assert_eq!(
+            find(Method::GET, &user::get_username_url(&base_url, "a/b")),
+            Some("view_user_by_username")
+        );
+        assert_eq!(
+            find(Method::POST, &datapoint::get_batch_url(&base_url)),
+            Some("batch_create_datapoint")
+        );
+        assert_eq!(
+            find(
+                Method::GET,
+                &datapoint::get_datapoint_datetime_url(&base_url, "7", "2025-01-01T00:00:00Z")
+            ),
+            Some("view_all_datapoints_by_id_datetime")
+        );
+        assert_eq!(
+            find(
+                Method::DELETE,
+                &datapoint::get_datapoint_subpath_url(&base_url, "7", "2025-01-01T00:00:00Z")
+            ),
+            Some("delete_datapoint")
+        );
+        assert_eq!(find(Method::PUT, &user::get_user_url(&base_url)), None);
+        assert_eq!(find(Method::GET, "https://example.com/users"), None);
+        assert_eq!(find(Method::GET, "https://example.com/api/users/"), None);
     }
 
-    pub fn get_datapoint_datetime_url(id: &str, datetime: &str) -> String {
-        let base_url = get_base_url();
-        format!("{}/sessions-sensors-data/session/{}/{}", base_url, id, datetime)
+    #[test]
+    fn route_names_are_unique() {
+        for (i, route) in ROUTES.iter().enumerate() {
+            assert!(
+                ROUTES[..i].iter().all(|other| other.name != route.name),
+                "{} is listed twice",
+                route.name
+            );
+        }
     }
 }
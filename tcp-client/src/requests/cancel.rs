@@ -0,0 +1,153 @@
+//! Cancelling requests that are in flight
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Handle for cancelling the requests made through an `ApiClient::with_cancel_handle` handle
+///
+/// Clones share the same state, so one clone can be kept by the UI and cancelled when the
+/// results are no longer needed. Cancelled requests fail with `ApiError::Cancelled`. Once
+/// cancelled, a handle stays cancelled and later requests using it fail immediately.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    // Tasks waiting for the cancellation
+    wakers: Mutex<Wakers>,
+}
+
+/// Wakers of the pending `Cancelled` futures, by the key each future removes on drop
+#[derive(Debug, Default)]
+struct Wakers {
+    next_key: u64,
+    waiting: HashMap<u64, Waker>,
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all requests using this handle
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        let wakers = std::mem::take(
+            &mut self
+                .inner
+                .wakers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .waiting,
+        );
+        for waker in wakers.into_values() {
+            waker.wake();
+        }
+    }
+
+    /// Check whether `cancel` has been called
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the handle is cancelled
+    pub(crate) fn cancelled(&self) -> Cancelled<'_> {
+        Cancelled {
+            handle: self,
+            key: None,
+        }
+    }
+}
+
+/// Future returned by `CancelHandle::cancelled`
+pub(crate) struct Cancelled<'a> {
+    handle: &'a CancelHandle,
+    // Key of the registered waker, if any
+    key: Option<u64>,
+}
+
+impl Future for Cancelled<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.handle.is_cancelled() {
+            return Poll::Ready(());
+        }
+
+        let handle = self.handle;
+        let mut wakers = handle
+            .inner
+            .wakers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // Check again under the lock so a concurrent `cancel` cannot be missed
+        if handle.is_cancelled() {
+            return Poll::Ready(());
+        }
+        let key = *self.key.get_or_insert_with(|| {
+            wakers.next_key += 1;
+            wakers.next_key
+        });
+        match wakers.waiting.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => {
+                wakers.waiting.insert(key, cx.waker().clone());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Cancelled<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.handle
+                .inner
+                .wakers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .waiting
+                .remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::noop_waker;
+
+    fn waiting(handle: &CancelHandle) -> usize {
+        handle.inner.wakers.lock().unwrap().waiting.len()
+    }
+
+    #[test]
+    fn dropped_futures_remove_their_waker() {
+        let handle = CancelHandle::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        for _ in 0..3 {
+            let mut cancelled = Box::pin(handle.cancelled());
+            assert_eq!(cancelled.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(cancelled.as_mut().poll(&mut cx), Poll::Pending);
+            assert_eq!(waiting(&handle), 1);
+        }
+        assert_eq!(waiting(&handle), 0);
+
+        let mut cancelled = Box::pin(handle.cancelled());
+        assert_eq!(cancelled.as_mut().poll(&mut cx), Poll::Pending);
+        handle.cancel();
+        assert_eq!(waiting(&handle), 0);
+        assert_eq!(cancelled.as_mut().poll(&mut cx), Poll::Ready(()));
+    }
+}
@@ -0,0 +1,476 @@
+//! Rate limits and caps on requests in flight, per group of routes
+//!
+//! Each `RouteGroup` has a token bucket refilled at `rate` requests per second, holding up to
+//! `burst` tokens, and a cap of `max_in_flight` requests waiting for their response. A request
+//! that would exceed a limit waits in a queue instead of failing. Interactive requests are
+//! let through before background ones, such as uploads of the `DatapointUploader`; requests of
+//! the same priority go in the order they arrived.
+
+use crate::path::Route;
+use crate::reqwest::Method;
+use futures::channel::oneshot;
+use futures::future;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use web_time::Instant;
+
+/// Group of routes sharing the same limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    /// Login, logout and renewal
+    Auth,
+    /// GET requests
+    Reads,
+    /// Requests changing data, except batch uploads
+    Writes,
+    /// Batch datapoint uploads
+    Batch,
+}
+
+impl RouteGroup {
+    /// Get the group of a request to `route`, or of an unknown route with `method`
+    pub fn of(method: &Method, route: Option<&Route>) -> Self {
+        match route {
+            Some(route) if route.template.starts_with("/authentication/") => RouteGroup::Auth,
+            Some(route) if route.name == "batch_create_datapoint" => RouteGroup::Batch,
+            _ if *method == Method::GET || *method == Method::HEAD => RouteGroup::Reads,
+            _ => RouteGroup::Writes,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            RouteGroup::Auth => 0,
+            RouteGroup::Reads => 1,
+            RouteGroup::Writes => 2,
+            RouteGroup::Batch => 3,
+        }
+    }
+}
+
+/// Priority of a request waiting for its turn
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    /// Requests a user is waiting for, let through first
+    #[default]
+    Interactive,
+    /// Requests nobody is waiting for, such as uploads in the background
+    Background,
+}
+
+/// Limits of one route group, unlimited by default
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupLimits {
+    rate: Option<f64>,
+    burst: Option<u32>,
+    max_in_flight: Option<usize>,
+}
+
+impl GroupLimits {
+    /// Create limits that let every request through at once
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Allow `per_second` requests per second on average
+    ///
+    /// Rates that are not positive are raised to one request per hour.
+    pub fn rate(mut self, per_second: f64) -> Self {
+        self.rate = Some(per_second.max(1.0 / 3600.0));
+        self
+    }
+
+    /// Allow up to `burst` requests at once after a quiet period (the rate rounded up by default)
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = Some(burst.max(1));
+        self
+    }
+
+    /// Allow at most `max_in_flight` requests waiting for their response at the same time
+    pub fn max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight.max(1));
+        self
+    }
+
+    /// Get the number of tokens the bucket holds
+    fn burst_size(&self) -> u32 {
+        self.burst
+            .unwrap_or_else(|| self.rate.map_or(1, |rate| rate.ceil() as u32).max(1))
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.rate.is_none() && self.max_in_flight.is_none()
+    }
+}
+
+/// Limits of every route group, unlimited by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RateLimits {
+    pub auth: GroupLimits,
+    pub reads: GroupLimits,
+    pub writes: GroupLimits,
+    pub batch: GroupLimits,
+}
+
+impl RateLimits {
+    /// Get the limits of a group
+    pub fn group(&self, group: RouteGroup) -> &GroupLimits {
+        match group {
+            RouteGroup::Auth => &self.auth,
+            RouteGroup::Reads => &self.reads,
+            RouteGroup::Writes => &self.writes,
+            RouteGroup::Batch => &self.batch,
+        }
+    }
+
+    /// Replace the limits of a group
+    pub fn set(mut self, group: RouteGroup, limits: GroupLimits) -> Self {
+        match group {
+            RouteGroup::Auth => self.auth = limits,
+            RouteGroup::Reads => self.reads = limits,
+            RouteGroup::Writes => self.writes = limits,
+            RouteGroup::Batch => self.batch = limits,
+        }
+        self
+    }
+}
+
+/// Queue enforcing `RateLimits`, shared by all clones of an `ApiClient`
+pub struct RateLimiter {
+    limits: RateLimits,
+    groups: [Mutex<GroupState>; 4],
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("limits", &self.limits)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(RateLimits::default())
+    }
+}
+
+/// Position in the queue: first by priority, then by arrival
+type Ticket = (Priority, u64);
+
+struct GroupState {
+    tokens: f64,
+    refilled_at: Instant,
+    in_flight: usize,
+    next_ticket: u64,
+    /// Waiting requests, with the sender waking each one if it is asleep
+    queue: BTreeMap<Ticket, Option<oneshot::Sender<()>>>,
+}
+
+impl fmt::Debug for GroupState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GroupState")
+            .field("tokens", &self.tokens)
+            .field("in_flight", &self.in_flight)
+            .field("queued", &self.queue.len())
+            .finish()
+    }
+}
+
+impl GroupState {
+    fn new(limits: &GroupLimits) -> Self {
+        Self {
+            tokens: f64::from(limits.burst_size()),
+            refilled_at: Instant::now(),
+            in_flight: 0,
+            next_ticket: 0,
+            queue: BTreeMap::new(),
+        }
+    }
+
+    /// Take a token, or return how long until the next one
+    fn take_token(&mut self, limits: &GroupLimits) -> Result<(), Duration> {
+        let Some(rate) = limits.rate else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(f64::from(limits.burst_size()));
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+
+    /// Wake the request at the head of the queue so it checks whether it can go
+    fn wake_head(&mut self) {
+        if let Some(sender) = self.queue.values_mut().next().and_then(Option::take) {
+            let _ = sender.send(());
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Create a limiter enforcing `limits`
+    pub fn new(limits: RateLimits) -> Self {
+        let state = |group| Mutex::new(GroupState::new(limits.group(group)));
+        Self {
+            groups: [
+                state(RouteGroup::Auth),
+                state(RouteGroup::Reads),
+                state(RouteGroup::Writes),
+                state(RouteGroup::Batch),
+            ],
+            limits,
+        }
+    }
+
+    /// Get the limits this limiter enforces
+    pub fn limits(&self) -> &RateLimits {
+        &self.limits
+    }
+
+    /// Get the number of requests of a group waiting for their turn
+    pub fn queued(&self, group: RouteGroup) -> usize {
+        self.groups[group.index()]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .queue
+            .len()
+    }
+
+    /// Get the number of requests of a group waiting for their response
+    pub fn in_flight(&self, group: RouteGroup) -> usize {
+        self.groups[group.index()]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .in_flight
+    }
+
+    /// Wait until a request of `group` may be sent
+    ///
+    /// The request counts as in flight until the returned permit is dropped.
+    pub async fn acquire(&self, group: RouteGroup, priority: Priority) -> Permit<'_> {
+        let limits = self.limits.group(group);
+        if limits.is_unlimited() {
+            return Permit { state: None };
+        }
+        let state = &self.groups[group.index()];
+
+        let mut waiting = {
+            let mut locked = state.lock().unwrap_or_else(|e| e.into_inner());
+            let ticket = (priority, locked.next_ticket);
+            locked.next_ticket += 1;
+            locked.queue.insert(ticket, None);
+            Waiting {
+                state,
+                ticket,
+                done: false,
+            }
+        };
+
+        loop {
+            let (receiver, token_delay) = {
+                let mut locked = state.lock().unwrap_or_else(|e| e.into_inner());
+                let at_head = locked.queue.keys().next() == Some(&waiting.ticket);
+                let slot_free = limits
+                    .max_in_flight
+                    .is_none_or(|max| locked.in_flight < max);
+
+                let token_delay = if at_head && slot_free {
+                    match locked.take_token(limits) {
+                        Ok(()) => {
+                            locked.queue.remove(&waiting.ticket);
+                            locked.in_flight += 1;
+                            waiting.done = true;
+                            // The next request may be able to go as well
+                            locked.wake_head();
+                            return Permit { state: Some(state) };
+                        }
+                        Err(delay) => Some(delay),
+                    }
+                } else {
+                    // Woken when a request ahead leaves the queue or finishes
+                    None
+                };
+
+                let (sender, receiver) = oneshot::channel();
+                locked.queue.insert(waiting.ticket, Some(sender));
+                (receiver, token_delay)
+            };
+
+            match token_delay {
+                Some(delay) => {
+                    // Woken once the next token is due, or earlier when the queue moves
+                    future::select(receiver, futures_timer::Delay::new(delay)).await;
+                }
+                None => {
+                    let _ = receiver.await;
+                }
+            }
+        }
+    }
+}
+
+/// Request waiting in the queue, removed from it if the wait is abandoned
+struct Waiting<'a> {
+    state: &'a Mutex<GroupState>,
+    ticket: Ticket,
+    done: bool,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            let mut locked = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            locked.queue.remove(&self.ticket);
+            locked.wake_head();
+        }
+    }
+}
+
+/// Permission to send a request, counted as in flight until dropped
+#[derive(Debug)]
+#[must_use = "the request stops counting as in flight when the permit is dropped"]
+pub struct Permit<'a> {
+    state: Option<&'a Mutex<GroupState>>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(state) = self.state {
+            let mut locked = state.lock().unwrap_or_else(|e| e.into_inner());
+            locked.in_flight -= 1;
+            locked.wake_head();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::ROUTES;
+    use futures::executor::block_on;
+    use futures::FutureExt;
+
+    fn route(name: &str) -> &'static Route {
+        ROUTES.iter().find(|route| route.name == name).unwrap()
+    }
+
+    #[test]
+    fn groups_routes() {
+        let group = |name: &str| {
+            let route = route(name);
+            RouteGroup::of(
+                &Method::from_bytes(route.method.as_bytes()).unwrap(),
+                Some(route),
+            )
+        };
+        assert_eq!(group("user_login"), RouteGroup::Auth);
+        assert_eq!(group("renew_session"), RouteGroup::Auth);
+        assert_eq!(group("view_sensor_by_id"), RouteGroup::Reads);
+        assert_eq!(group("create_datapoint"), RouteGroup::Writes);
+        assert_eq!(group("delete_sensor"), RouteGroup::Writes);
+        assert_eq!(group("batch_create_datapoint"), RouteGroup::Batch);
+        assert_eq!(RouteGroup::of(&Method::GET, None), RouteGroup::Reads);
+    }
+
+    #[test]
+    fn caps_requests_in_flight() {
+        let limiter = RateLimiter::new(
+            RateLimits::default().set(RouteGroup::Reads, GroupLimits::unlimited().max_in_flight(2)),
+        );
+        block_on(async {
+            let first = limiter
+                .acquire(RouteGroup::Reads, Priority::Interactive)
+                .await;
+            let _second = limiter
+                .acquire(RouteGroup::Reads, Priority::Interactive)
+                .await;
+            let mut third = Box::pin(limiter.acquire(RouteGroup::Reads, Priority::Interactive));
+            assert!((&mut third).now_or_never().is_none());
+            assert_eq!(limiter.queued(RouteGroup::Reads), 1);
+
+            // Other groups are not affected
+            let _write = limiter
+                .acquire(RouteGroup::Writes, Priority::Interactive)
+                .await;
+
+            drop(first);
+            let _third = third.await;
+            assert_eq!(limiter.in_flight(RouteGroup::Reads), 2);
+            assert_eq!(limiter.queued(RouteGroup::Reads), 0);
+        });
+    }
+
+    #[test]
+    fn interactive_requests_go_first() {
+        let limiter = RateLimiter::new(RateLimits::default().set(
+            RouteGroup::Writes,
+            GroupLimits::unlimited().max_in_flight(1),
+        ));
+        block_on(async {
+            let first = limiter
+                .acquire(RouteGroup::Writes, Priority::Background)
+                .await;
+            let mut background =
+                Box::pin(limiter.acquire(RouteGroup::Writes, Priority::Background));
+            let mut interactive =
+                Box::pin(limiter.acquire(RouteGroup::Writes, Priority::Interactive));
+            assert!((&mut background).now_or_never().is_none());
+            assert!((&mut interactive).now_or_never().is_none());
+
+            drop(first);
+            assert!((&mut background).now_or_never().is_none());
+            let permit = (&mut interactive).now_or_never().unwrap();
+            drop(permit);
+            assert!(background.now_or_never().is_some());
+        });
+    }
+
+    #[test]
+    fn abandoned_waits_leave_the_queue() {
+        let limiter = RateLimiter::new(
+            RateLimits::default().set(RouteGroup::Reads, GroupLimits::unlimited().max_in_flight(1)),
+        );
+        block_on(async {
+            let first = limiter
+                .acquire(RouteGroup::Reads, Priority::Interactive)
+                .await;
+            let mut abandoned = Box::pin(limiter.acquire(RouteGroup::Reads, Priority::Interactive));
+            assert!((&mut abandoned).now_or_never().is_none());
+            let mut next = Box::pin(limiter.acquire(RouteGroup::Reads, Priority::Interactive));
+            assert!((&mut next).now_or_never().is_none());
+
+            drop(abandoned);
+            drop(first);
+            assert!(next.now_or_never().is_some());
+        });
+    }
+
+    #[test]
+    fn spaces_requests_at_the_rate() {
+        let limiter = RateLimiter::new(RateLimits::default().set(
+            RouteGroup::Auth,
+            GroupLimits::unlimited().rate(50.0).burst(2),
+        ));
+        block_on(async {
+            let started = Instant::now();
+            for _ in 0..4 {
+                let _permit = limiter
+                    .acquire(RouteGroup::Auth, Priority::Interactive)
+                    .await;
+            }
+            // Two requests from the burst, then two more at 20 ms intervals
+            assert!(started.elapsed() >= Duration::from_millis(35));
+        });
+    }
+}
@@ -1 +1,7 @@
+pub mod cancel;
+pub mod limits;
+pub mod renewal;
+pub mod retry;
 pub mod send_request;
+pub mod timeout;
+pub mod transport;
@@ -0,0 +1,258 @@
+//! Tracking of the auth token's expiry and shared renewal of the login session
+
+use crate::cookie::SessionCookie;
+use crate::error::ApiError;
+use crate::session_store::{SessionStore, StoredSession};
+use crate::types::AuthToken;
+use futures::lock::Mutex;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use web_time::SystemTime;
+
+/// Auth token together with the time the server said it expires
+#[derive(Debug, Default)]
+struct Credentials {
+    auth_token: Option<AuthToken>,
+    expires_at: Option<SystemTime>,
+    // Incremented every time the credentials change, so a request can tell whether the token
+    // it was sent with has already been replaced by another request's renewal
+    generation: u64,
+}
+
+/// Credentials shared by all clones of an `ApiClient`
+///
+/// The credentials are cached in memory and written through to the session store whenever
+/// they change.
+#[derive(Debug)]
+pub(crate) struct SessionState {
+    credentials: RwLock<Credentials>,
+    renewal: Mutex<()>,
+    store: Arc<dyn SessionStore>,
+    // Generation of the credentials last written to the store. Held while writing so that
+    // concurrent changes reach the store in order
+    persisted: std::sync::Mutex<u64>,
+}
+
+impl SessionState {
+    /// Start with the given auth token, or with the session saved in the store if there is none
+    pub(crate) fn new(
+        store: Arc<dyn SessionStore>,
+        auth_token: Option<AuthToken>,
+        expires_at: Option<SystemTime>,
+    ) -> Self {
+        let state = Self {
+            credentials: RwLock::new(Credentials::default()),
+            renewal: Mutex::new(()),
+            store,
+            persisted: std::sync::Mutex::new(0),
+        };
+
+        if auth_token.is_some() {
+            state.set(auth_token, expires_at);
+        } else if let Ok(Some(stored)) = state.store.load() {
+            // A session that has already expired is of no use, so start logged out instead
+            let expired = stored
+                .expires_at
+                .is_some_and(|expires_at| expires_at <= SystemTime::now());
+            if expired {
+                let _ = state.store.clear();
+            } else {
+                let mut credentials = state.credentials.write().unwrap_or_else(|e| e.into_inner());
+                credentials.auth_token = Some(stored.auth_token);
+                credentials.expires_at = stored.expires_at;
+            }
+        }
+
+        state
+    }
+
+    /// Get the current auth token and the generation it belongs to
+    pub(crate) fn snapshot(&self) -> (Option<AuthToken>, u64) {
+        let credentials = self.credentials.read().unwrap_or_else(|e| e.into_inner());
+        (credentials.auth_token.clone(), credentials.generation)
+    }
+
+    pub(crate) fn auth_token(&self) -> Option<AuthToken> {
+        self.snapshot().0
+    }
+
+    pub(crate) fn expires_at(&self) -> Option<SystemTime> {
+        let credentials = self.credentials.read().unwrap_or_else(|e| e.into_inner());
+        credentials.expires_at
+    }
+
+    /// Replace the auth token and its expiry, saving them to the session store
+    ///
+    /// The store is written after the lock is released, so a slow store does not hold up
+    /// requests reading the token. If a newer change has been written by then, this one is
+    /// skipped so the store never goes back to an older token. Failing to persist the session
+    /// does not affect the current login, so store errors are ignored here.
+    pub(crate) fn set(&self, auth_token: Option<AuthToken>, expires_at: Option<SystemTime>) {
+        let generation = {
+            let mut credentials = self.credentials.write().unwrap_or_else(|e| e.into_inner());
+            credentials.auth_token = auth_token.clone();
+            credentials.expires_at = expires_at;
+            credentials.generation += 1;
+            credentials.generation
+        };
+
+        let mut persisted = self.persisted.lock().unwrap_or_else(|e| e.into_inner());
+        if *persisted > generation {
+            return;
+        }
+        *persisted = generation;
+        let _ = match auth_token {
+            Some(auth_token) => self.store.save(&StoredSession {
+                auth_token,
+                expires_at,
+            }),
+            None => self.store.clear(),
+        };
+    }
+
+    /// Get the current generation if the token expires within `margin`
+    pub(crate) fn expiring_within(&self, margin: Duration) -> Option<u64> {
+        let credentials = self.credentials.read().unwrap_or_else(|e| e.into_inner());
+        credentials.auth_token.as_ref()?;
+        let expires_at = credentials.expires_at?;
+
+        let remaining = expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO);
+        (remaining <= margin).then_some(credentials.generation)
+    }
+
+    /// Renew the session unless another request already did so
+    ///
+    /// `seen` is the generation of the token the caller found stale. If the credentials have
+    /// moved on since then, the renewal already happened and `renew` is not called. Pass `None`
+    /// to always renew. Concurrent callers wait on the same renewal instead of each sending one.
+    pub(crate) async fn renew_with<F, Fut>(
+        &self,
+        seen: Option<u64>,
+        renew: F,
+    ) -> Result<SessionCookie, ApiError>
+    where
+        F: FnOnce(Option<AuthToken>) -> Fut,
+        Fut: Future<Output = Result<SessionCookie, ApiError>>,
+    {
+        let _guard = self.renewal.lock().await;
+
+        let (auth_token, generation) = self.snapshot();
+        if let (Some(seen), Some(auth_token)) = (seen, &auth_token) {
+            if seen != generation {
+                let mut cookie = SessionCookie::from_auth_token(auth_token);
+                cookie.expires = self.expires_at();
+                return Ok(cookie);
+            }
+        }
+
+        let cookie = renew(auth_token).await?;
+        self.set(Some(cookie.auth_token()), cookie.expires_at());
+
+        Ok(cookie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session_store::MemorySessionStore;
+
+    fn stored(expires_at: SystemTime) -> Arc<MemorySessionStore> {
+        let store = Arc::new(MemorySessionStore::new());
+        store
+            .save(&StoredSession {
+                auth_token: AuthToken::from("token-1"),
+                expires_at: Some(expires_at),
+            })
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn starts_with_the_stored_session() {
+        let expires_at = SystemTime::now() + Duration::from_secs(3600);
+        let store = stored(expires_at);
+
+        let state = SessionState::new(store.clone(), None, None);
+        assert_eq!(state.auth_token(), Some(AuthToken::from("token-1")));
+        assert_eq!(state.expires_at(), Some(expires_at));
+        assert!(store.load().unwrap().is_some());
+    }
+
+    #[test]
+    fn discards_an_expired_stored_session() {
+        let store = stored(SystemTime::now() - Duration::from_secs(1));
+
+        let state = SessionState::new(store.clone(), None, None);
+        assert_eq!(state.auth_token(), None);
+        assert_eq!(store.load().unwrap(), None);
+    }
+
+    #[test]
+    fn set_writes_through_to_the_store() {
+        let store = Arc::new(MemorySessionStore::new());
+        let state = SessionState::new(store.clone(), None, None);
+
+        state.set(Some(AuthToken::from("token-2")), None);
+        let (auth_token, generation) = state.snapshot();
+        assert_eq!(auth_token, Some(AuthToken::from("token-2")));
+        assert_eq!(generation, 1);
+        assert_eq!(
+            store.load().unwrap().map(|stored| stored.auth_token),
+            Some(AuthToken::from("token-2"))
+        );
+
+        state.set(None, None);
+        assert_eq!(store.load().unwrap(), None);
+    }
+
+    /// Memory store that takes a while to save `token-slow`
+    #[derive(Debug, Default)]
+    struct SlowStore {
+        inner: MemorySessionStore,
+    }
+
+    impl SessionStore for SlowStore {
+        fn load(&self) -> std::io::Result<Option<StoredSession>> {
+            self.inner.load()
+        }
+
+        fn save(&self, session: &StoredSession) -> std::io::Result<()> {
+            if session.auth_token == AuthToken::from("token-slow") {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            self.inner.save(session)
+        }
+
+        fn clear(&self) -> std::io::Result<()> {
+            self.inner.clear()
+        }
+    }
+
+    #[test]
+    fn racing_changes_reach_the_store_in_order() {
+        let store = Arc::new(SlowStore::default());
+        let state = Arc::new(SessionState::new(store.clone(), None, None));
+
+        let slow = {
+            let state = state.clone();
+            std::thread::spawn(move || state.set(Some(AuthToken::from("token-slow")), None))
+        };
+        // Let the slow change start saving, then replace it
+        while state.snapshot().1 == 0 {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        state.set(Some(AuthToken::from("token-new")), None);
+        slow.join().unwrap();
+
+        assert_eq!(state.auth_token(), Some(AuthToken::from("token-new")));
+        assert_eq!(
+            store.load().unwrap().map(|stored| stored.auth_token),
+            Some(AuthToken::from("token-new"))
+        );
+    }
+}
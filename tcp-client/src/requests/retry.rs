@@ -0,0 +1,297 @@
+//! Retrying failed requests with exponential backoff and jitter
+
+use crate::error::ApiError;
+use crate::reqwest::{Method, StatusCode};
+use std::time::Duration;
+
+/// Name of the header carrying the idempotency key of a request
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// Rules for retrying a request that failed
+///
+/// The delay before retry `n` is `base_delay * 2^(n - 1)`, capped at `max_delay`, with up to
+/// `jitter` of it removed at random so that many clients do not retry in lockstep. On `429 Too
+/// Many Requests` and `503 Service Unavailable` a `Retry-After` header from the server is used
+/// instead.
+///
+/// By default only idempotent methods (GET, HEAD, PUT, DELETE, PATCH and OPTIONS) are retried.
+/// POST requests are retried only when `idempotency_keys` is enabled, so the server can tell a
+/// retry from a new request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: f64,
+    statuses: Vec<StatusCode>,
+    retry_transport_errors: bool,
+    retry_timeouts: bool,
+    methods: Vec<Method>,
+    idempotency_keys: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            statuses: vec![
+                StatusCode::REQUEST_TIMEOUT,
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            retry_transport_errors: true,
+            retry_timeouts: true,
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::PUT,
+                Method::DELETE,
+                Method::PATCH,
+                Method::OPTIONS,
+            ],
+            idempotency_keys: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create the default policy: 3 attempts, 200 ms base delay, 10 s max delay, 50% jitter
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a policy that never retries
+    pub fn none() -> Self {
+        Self::default().max_attempts(1)
+    }
+
+    /// Set the total number of attempts, including the first one (at least 1)
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry
+    pub fn base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    /// Set the longest delay between two attempts
+    ///
+    /// If the server asks to wait longer than this with `Retry-After`, the request is not
+    /// retried.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set the fraction of each delay that is randomized, from 0 (none) to 1 (full jitter)
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the response statuses that are retried
+    pub fn statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Retry requests that could not reach the server (enabled by default)
+    pub fn retry_transport_errors(mut self, retry: bool) -> Self {
+        self.retry_transport_errors = retry;
+        self
+    }
+
+    /// Retry requests that timed out (enabled by default)
+    pub fn retry_timeouts(mut self, retry: bool) -> Self {
+        self.retry_timeouts = retry;
+        self
+    }
+
+    /// Set the idempotent methods that are retried
+    ///
+    /// POST is ignored here, see `idempotency_keys`.
+    pub fn methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods
+            .into_iter()
+            .filter(|method| *method != Method::POST)
+            .collect();
+        self
+    }
+
+    /// Send a random `Idempotency-Key` header with every request and also retry POST requests
+    ///
+    /// All attempts of one request carry the same key. The server must recognize the header
+    /// for retried POST requests to be safe.
+    pub fn idempotency_keys(mut self, idempotency_keys: bool) -> Self {
+        self.idempotency_keys = idempotency_keys;
+        self
+    }
+
+    /// Check whether requests are sent with an idempotency key
+    pub fn uses_idempotency_keys(&self) -> bool {
+        self.idempotency_keys
+    }
+
+    /// Get the delay before the next attempt, or `None` if the request should not be retried
+    ///
+    /// `attempt` is the number of attempts made so far, starting at 1.
+    pub fn retry_delay(&self, method: &Method, attempt: u32, error: &ApiError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.retries_method(method) {
+            return None;
+        }
+
+        match error {
+            ApiError::Transport(_) if self.retry_transport_errors => {}
+            ApiError::Timeout(_) if self.retry_timeouts => {}
+            ApiError::Status { status, .. } if self.statuses.contains(status) => {
+                let honors_retry_after = *status == StatusCode::TOO_MANY_REQUESTS
+                    || *status == StatusCode::SERVICE_UNAVAILABLE;
+                if let Some(retry_after) = error.retry_after().filter(|_| honors_retry_after) {
+                    return (retry_after <= self.max_delay).then_some(retry_after);
+                }
+            }
+            _ => return None,
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    fn retries_method(&self, method: &Method) -> bool {
+        self.idempotency_keys || self.methods.contains(method)
+    }
+
+    /// Exponential delay after the given attempt, with jitter applied
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self
+            .base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        delay.mul_f64(1.0 - self.jitter * fastrand::f64())
+    }
+}
+
+/// Generate a random idempotency key
+pub(crate) fn new_idempotency_key() -> String {
+    format!("{:032x}", fastrand::u128(..))
+}
+
+/// Wait before the next attempt
+pub(crate) async fn sleep(delay: Duration) {
+    futures_timer::Delay::new(delay).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status: StatusCode, retry_after: Option<u64>) -> ApiError {
+        ApiError::Status {
+            status,
+            body: None,
+            retry_after: retry_after.map(Duration::from_secs),
+        }
+    }
+
+    fn timeout() -> ApiError {
+        ApiError::Timeout(crate::requests::timeout::TimeoutKind::Total)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_max_delay() {
+        let policy = RetryPolicy::new()
+            .max_attempts(10)
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(1000))
+            .jitter(0.0);
+
+        let delays: Vec<_> = (1..10)
+            .map(|attempt| policy.retry_delay(&Method::GET, attempt, &timeout()))
+            .map(|delay| delay.unwrap().as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000, 1000, 1000, 1000]);
+        assert_eq!(policy.retry_delay(&Method::GET, 10, &timeout()), None);
+
+        // Does not overflow on absurd attempt counts
+        let policy = policy.max_attempts(u32::MAX);
+        let delay = policy.retry_delay(&Method::GET, 200, &timeout());
+        assert_eq!(delay, Some(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(1000))
+            .jitter(0.25);
+
+        for _ in 0..1000 {
+            let delay = policy.retry_delay(&Method::GET, 1, &timeout()).unwrap();
+            assert!(delay > Duration::from_millis(750), "{:?}", delay);
+            assert!(delay <= Duration::from_millis(1000), "{:?}", delay);
+        }
+        // Full jitter may go down to nothing but never above the delay
+        let policy = policy.jitter(2.0);
+        for _ in 0..1000 {
+            let delay = policy.retry_delay(&Method::GET, 1, &timeout()).unwrap();
+            assert!(delay <= Duration::from_millis(1000), "{:?}", delay);
+        }
+    }
+
+    #[test]
+    fn retry_after_is_honored_on_429_and_503() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(10))
+            .jitter(0.0);
+
+        for code in [
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            let delay = policy.retry_delay(&Method::GET, 1, &status(code, Some(3)));
+            assert_eq!(delay, Some(Duration::from_secs(3)));
+            // Asking to wait longer than the max delay gives up instead
+            let delay = policy.retry_delay(&Method::GET, 1, &status(code, Some(60)));
+            assert_eq!(delay, None);
+            // Without the header the usual backoff applies
+            let delay = policy.retry_delay(&Method::GET, 1, &status(code, None));
+            assert_eq!(delay, Some(Duration::from_millis(100)));
+        }
+
+        // Other statuses keep the backoff even with a Retry-After header
+        let delay = policy.retry_delay(&Method::GET, 1, &status(StatusCode::BAD_GATEWAY, Some(3)));
+        assert_eq!(delay, Some(Duration::from_millis(100)));
+        // Statuses that are not retried stay that way
+        let error = status(StatusCode::BAD_REQUEST, Some(3));
+        assert_eq!(policy.retry_delay(&Method::GET, 1, &error), None);
+    }
+
+    #[test]
+    fn post_is_retried_only_with_idempotency_keys() {
+        let policy = RetryPolicy::new().methods([Method::GET, Method::POST]);
+        assert!(!policy.uses_idempotency_keys());
+        assert_eq!(policy.retry_delay(&Method::POST, 1, &timeout()), None);
+        assert!(policy.retry_delay(&Method::GET, 1, &timeout()).is_some());
+
+        let policy = policy.idempotency_keys(true);
+        assert!(policy.uses_idempotency_keys());
+        assert!(policy.retry_delay(&Method::POST, 1, &timeout()).is_some());
+    }
+
+    #[test]
+    fn idempotency_keys_are_random() {
+        let key = new_idempotency_key();
+        assert_eq!(key.len(), 32);
+        assert_ne!(key, new_idempotency_key());
+    }
+}
@@ -1,84 +1,175 @@
-/// Main logic for building and sending requests to the TCP server
+//! Main logic for building and sending requests to the TCP server
 
-use reqwest_wasm::{
+use crate::error::ApiError;
+use crate::requests::transport::{HttpRequest, HttpResponse, Transport};
+use crate::reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, COOKIE},
-    Client, Method,
+    Method, StatusCode,
 };
-use serde::Serialize;
-use serde_json;
+use crate::types::AuthToken;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{self, Value};
+use std::time::Duration;
+use tracing::{field, Instrument, Level};
+use web_time::Instant;
 
+/// Target of the events logging request and response bodies
+///
+/// Bodies are only logged when this target is enabled at the `TRACE` level, e.g. with
+/// `RUST_LOG=client::body=trace`.
+pub const BODY_TARGET: &str = "client::body";
+
+/// JSON fields whose values are replaced with `<redacted>` in logged bodies
+const SECRET_FIELDS: &[&str] = &["password_hash"];
+
+/// Build a request, send it through `transport` and check the response
+///
+/// Returns the status, the JSON body if there is one and the headers of a successful
+/// response, or `ApiError::Status` with the error body sent by the server. Each call runs in a
+/// `send_request` span recording the status, latency and body sizes.
 pub async fn send_request<T>(
-    client: &Client,
+    transport: &dyn Transport,
     method: &Method,
     url: String,
-    session_id: Option<&str>,
+    auth_token: Option<&AuthToken>,
+    extra_headers: HeaderMap,
     body: Option<T>,
-) -> (reqwest_wasm::StatusCode, Option<serde_json::Value>, HeaderMap)
+    read_timeout: Option<Duration>,
+) -> Result<(StatusCode, Option<serde_json::Value>, HeaderMap), ApiError>
 where
     T: Serialize,
 {
-    let mut request = client.request(method.clone(), url);
+    let span = tracing::info_span!(
+        "send_request",
+        status = field::Empty,
+        latency_ms = field::Empty,
+        request_bytes = field::Empty,
+        response_bytes = field::Empty,
+    );
+    send_request_in_span(
+        transport,
+        method,
+        url,
+        auth_token,
+        extra_headers,
+        body,
+        read_timeout,
+    )
+    .instrument(span)
+    .await
+}
+
+/// Body of `send_request`, run inside its span
+async fn send_request_in_span<T>(
+    transport: &dyn Transport,
+    method: &Method,
+    url: String,
+    auth_token: Option<&AuthToken>,
+    extra_headers: HeaderMap,
+    body: Option<T>,
+    read_timeout: Option<Duration>,
+) -> Result<(StatusCode, Option<serde_json::Value>, HeaderMap), ApiError>
+where
+    T: Serialize,
+{
+    let span = tracing::Span::current();
+    let mut headers = extra_headers;
 
     // Add content-type header for POST and PATCH methods
     if *method == Method::POST || *method == Method::PATCH {
-        request = request.header(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     }
 
-    // Add session_id in cookie header if provided
-    if let Some(session_id) = session_id {
-        match HeaderValue::from_str(&format!("session_id={}", session_id)) {
-            Ok(value) => {
-                request = request.header(COOKIE, value);
-            }
-            Err(e) => {
-                eprintln!("Failed to parse session_id header: {}", e);
-            }
-        }
+    // Add the auth token as the session_id cookie if provided
+    if let Some(auth_token) = auth_token {
+        let value = HeaderValue::from_str(&format!("session_id={}", auth_token.as_str()))?;
+        headers.insert(COOKIE, value);
     }
 
-    // Check if there is a body to send in the request
-    if let Some(body) = body {
-        // Serialize the body and get the length
-        let serialized_body = serde_json::to_vec(&body).unwrap();
-        let content_length = serialized_body.len();
-        
-        // Add content-length header
-        request = request.header(CONTENT_LENGTH, HeaderValue::from_str(&content_length.to_string()).unwrap());
+    // Serialize the body if there is one, and set the content length (zero without a body)
+    let body = match body {
+        Some(body) => serde_json::to_vec(&body).map_err(ApiError::Serialize)?,
+        None => Vec::new(),
+    };
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+    span.record("request_bytes", body.len());
+    log_body("request body", &body);
 
-        // Add the json body to the request
-        request = request.json(&body);
-    } else {
-        // Set content length to zero if there is no body
-        request = request.header(CONTENT_LENGTH, 0);
+    // Send request and wait for the whole response
+    let request = HttpRequest {
+        method: method.clone(),
+        url,
+        headers,
+        body,
+    };
+    let started = Instant::now();
+    let result = transport.send(request, read_timeout).await;
+    span.record("latency_ms", started.elapsed().as_millis() as u64);
+    let HttpResponse {
+        status,
+        headers,
+        body: bytes,
+    } = result?;
+    span.record("status", status.as_u16());
+    span.record("response_bytes", bytes.len());
+    log_body("response body", &bytes);
+
+    // Return the status and any error body sent by the server
+    if !status.is_success() {
+        let body = serde_json::from_slice(&bytes).ok();
+        return Err(ApiError::Status {
+            status,
+            body,
+            retry_after: ApiError::parse_retry_after(&headers),
+        });
     }
 
-    // Send request
-    let res = match request.send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return (
-                // Return an internal server error if sending the request fails
-                reqwest_wasm::StatusCode::INTERNAL_SERVER_ERROR,
-                None,
-                HeaderMap::new(),
-            );
-        }
+    let json = if bytes.is_empty() {
+        None
+    } else {
+        Some(serde_json::from_slice(&bytes).map_err(ApiError::Decode)?)
     };
 
-    // Get status and headers from the response
-    let status = res.status();
-    let headers = res.headers().clone();
+    // Return the response status, body, and headers
+    Ok((status, json, headers))
+}
 
-    // Receive json body if not No Content
-    let json = if status != reqwest_wasm::StatusCode::NO_CONTENT {
-        match res.json::<serde_json::Value>().await {
-            Ok(body) => Some(body),
-            Err(_) => None,
+/// Log a body at the `TRACE` level of `BODY_TARGET`, with secrets redacted
+fn log_body(message: &str, bytes: &[u8]) {
+    if bytes.is_empty() || !tracing::enabled!(target: BODY_TARGET, Level::TRACE) {
+        return;
+    }
+    let body = match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut json) => {
+            redact(&mut json);
+            json.to_string()
         }
-    } else {
-        None
+        Err(_) => String::from_utf8_lossy(bytes).into_owned(),
     };
+    tracing::trace!(target: BODY_TARGET, body = %body, "{}", message);
+}
 
-    // Return the response status, body, and headers
-    (status, json, headers)
-}
\ No newline at end of file
+/// Replace the values of `SECRET_FIELDS` with `<redacted>`, at any depth
+fn redact(json: &mut Value) {
+    match json {
+        Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Decode a JSON response body into the expected response type
+pub fn parse_json<R>(json: Option<serde_json::Value>) -> Result<R, ApiError>
+where
+    R: DeserializeOwned,
+{
+    serde_json::from_value(json.unwrap_or(serde_json::Value::Null)).map_err(ApiError::Decode)
+}
@@ -0,0 +1,101 @@
+//! Connect, read and total timeouts for requests
+
+use futures::future::{self, Either};
+use std::fmt;
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+
+/// Which timeout a request ran into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutKind {
+    /// The connection to the server could not be established in time
+    Connect,
+    /// The server stopped sending the response for too long
+    Read,
+    /// The request, including its retries, took too long overall
+    Total,
+}
+
+impl fmt::Display for TimeoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeoutKind::Connect => write!(f, "connect"),
+            TimeoutKind::Read => write!(f, "read"),
+            TimeoutKind::Total => write!(f, "total"),
+        }
+    }
+}
+
+/// Time limits for a request, each disabled when `None`
+///
+/// - `connect` limits establishing a connection. It is applied to the HTTP client when the
+///   `ApiClient` creates it, so it cannot be changed per call. On wasm the browser decides,
+///   and waiting for the connection counts towards `read` instead.
+/// - `read` limits waiting for the response headers and for each part of the response body.
+/// - `total` limits the whole request, including retries and the delays between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub read: Option<Duration>,
+    pub total: Option<Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Some(Duration::from_secs(10)),
+            read: Some(Duration::from_secs(30)),
+            total: None,
+        }
+    }
+}
+
+impl Timeouts {
+    /// Create the default timeouts: 10 s to connect, 30 s to read and no total limit
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create timeouts that never expire
+    pub fn none() -> Self {
+        Self {
+            connect: None,
+            read: None,
+            total: None,
+        }
+    }
+
+    /// Set the connect timeout
+    pub fn connect(mut self, connect: impl Into<Option<Duration>>) -> Self {
+        self.connect = connect.into();
+        self
+    }
+
+    /// Set the read timeout
+    pub fn read(mut self, read: impl Into<Option<Duration>>) -> Self {
+        self.read = read.into();
+        self
+    }
+
+    /// Set the total timeout
+    pub fn total(mut self, total: impl Into<Option<Duration>>) -> Self {
+        self.total = total.into();
+        self
+    }
+}
+
+/// Run a future, giving up with `Err(())` if it does not finish within `duration`
+pub(crate) async fn with_timeout<F>(duration: Option<Duration>, future: F) -> Result<F::Output, ()>
+where
+    F: Future,
+{
+    let Some(duration) = duration else {
+        return Ok(future.await);
+    };
+
+    match future::select(pin!(future), futures_timer::Delay::new(duration)).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(()),
+    }
+}
@@ -0,0 +1,134 @@
+//! The layer that moves requests and responses, by default over HTTP with reqwest
+
+use crate::error::ApiError;
+use crate::requests::timeout::{with_timeout, TimeoutKind};
+use crate::reqwest::{header::HeaderMap, Client, Method, StatusCode};
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Request ready to be sent, with all headers set and the body serialized
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: Method,
+    pub url: String,
+    pub headers: HeaderMap,
+    /// Serialized JSON body, empty if there is none
+    pub body: Vec<u8>,
+}
+
+/// Response received for an `HttpRequest`, whatever its status
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+/// Future returned by `Transport::send`
+///
+/// It is `Send` except in the browser, where reqwest's futures are not.
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<HttpResponse, ApiError>> + Send + 'a>>;
+
+/// Future returned by `Transport::send`
+///
+/// It is `Send` except in the browser, where reqwest's futures are not.
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> = Pin<Box<dyn Future<Output = Result<HttpResponse, ApiError>> + 'a>>;
+
+/// Sends requests and returns the responses
+///
+/// `send_request` builds the request and interprets the response, so a transport only moves
+/// bytes. `ReqwestTransport` is the default; `ApiClientBuilder::transport` swaps in another
+/// one, such as the scripted `FakeTransport` used in tests.
+pub trait Transport: Debug + Send + Sync {
+    /// Send a request
+    ///
+    /// `read_timeout` bounds the wait for the response headers and for each part of the body.
+    /// Any status is a successful exchange; only failures to talk to the server are errors.
+    fn send(&self, request: HttpRequest, read_timeout: Option<Duration>) -> TransportFuture<'_>;
+}
+
+/// Transport sending requests with a reqwest `Client`
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    /// Send requests with an existing client, sharing its connection pool
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Get the underlying client
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest, read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let request = self
+                .client
+                .request(request.method, request.url)
+                .headers(request.headers)
+                .body(request.body);
+
+            // Send request and wait for the response headers
+            let res = with_timeout(read_timeout, request.send())
+                .await
+                .map_err(|()| ApiError::Timeout(TimeoutKind::Read))?
+                .map_err(ApiError::from_reqwest)?;
+
+            let status = res.status();
+            let headers = res.headers().clone();
+            let body = if status != StatusCode::NO_CONTENT {
+                read_body(res, read_timeout).await?
+            } else {
+                Vec::new()
+            };
+            Ok(HttpResponse {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Read the response body, allowing `read_timeout` between two parts of it
+#[cfg(not(target_arch = "wasm32"))]
+async fn read_body(
+    mut res: crate::reqwest::Response,
+    read_timeout: Option<Duration>,
+) -> Result<Vec<u8>, ApiError> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = with_timeout(read_timeout, res.chunk())
+        .await
+        .map_err(|()| ApiError::Timeout(TimeoutKind::Read))?
+        .map_err(ApiError::from_reqwest)?
+    {
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Read the response body, allowing `read_timeout` for all of it
+///
+/// The browser does not hand out the body in parts, so the timeout covers the whole body.
+#[cfg(target_arch = "wasm32")]
+async fn read_body(
+    res: crate::reqwest::Response,
+    read_timeout: Option<Duration>,
+) -> Result<Vec<u8>, ApiError> {
+    let bytes = with_timeout(read_timeout, res.bytes())
+        .await
+        .map_err(|()| ApiError::Timeout(TimeoutKind::Read))?
+        .map_err(ApiError::from_reqwest)?;
+    Ok(bytes.to_vec())
+}
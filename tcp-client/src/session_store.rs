@@ -0,0 +1,235 @@
+//! Storage for the auth token so a login survives restarts of the application
+
+use crate::types::AuthToken;
+use std::fmt::Debug;
+use std::io;
+use std::sync::Mutex;
+use web_time::SystemTime;
+
+/// Auth token saved by a `SessionStore`, together with its expiry
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredSession {
+    pub auth_token: AuthToken,
+    pub expires_at: Option<SystemTime>,
+}
+
+/// Storage for the auth token of an `ApiClient`
+///
+/// The client loads the stored session when it is built, saves it after login and renewal,
+/// and clears it on logout.
+pub trait SessionStore: Debug + Send + Sync {
+    /// Load the saved session, if there is one
+    fn load(&self) -> io::Result<Option<StoredSession>>;
+
+    /// Save the session, replacing any previous one
+    fn save(&self, session: &StoredSession) -> io::Result<()>;
+
+    /// Remove the saved session
+    fn clear(&self) -> io::Result<()>;
+}
+
+/// Session store that keeps the auth token in memory only
+///
+/// This is the default store, so a new client always starts logged out.
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    session: Mutex<Option<StoredSession>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    fn load(&self) -> io::Result<Option<StoredSession>> {
+        Ok(self
+            .session
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone())
+    }
+
+    fn save(&self, session: &StoredSession) -> io::Result<()> {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner()) = Some(session.clone());
+        Ok(())
+    }
+
+    fn clear(&self) -> io::Result<()> {
+        *self.session.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use file::FileSessionStore;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod file {
+    use super::{SessionStore, StoredSession};
+    use crate::types::AuthToken;
+    use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Session store that saves the auth token to a JSON file
+    #[derive(Debug, Clone)]
+    pub struct FileSessionStore {
+        path: PathBuf,
+    }
+
+    /// Layout of the JSON file
+    #[derive(Serialize, Deserialize)]
+    struct SessionFile {
+        auth_token: AuthToken,
+        /// Expiry in seconds since the Unix epoch
+        expires_at: Option<u64>,
+    }
+
+    impl FileSessionStore {
+        /// Create a store that reads and writes the given file
+        ///
+        /// The file and its parent directories are created on the first save.
+        pub fn new(path: impl Into<PathBuf>) -> Self {
+            Self { path: path.into() }
+        }
+
+        /// Get the path of the JSON file
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+    }
+
+    impl SessionStore for FileSessionStore {
+        fn load(&self) -> io::Result<Option<StoredSession>> {
+            let contents = match fs::read(&self.path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            let file: SessionFile = serde_json::from_slice(&contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            Ok(Some(StoredSession {
+                auth_token: file.auth_token,
+                expires_at: file
+                    .expires_at
+                    .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            }))
+        }
+
+        fn save(&self, session: &StoredSession) -> io::Result<()> {
+            let file = SessionFile {
+                auth_token: session.auth_token.clone(),
+                expires_at: session.expires_at.map(|expires_at: SystemTime| {
+                    expires_at
+                        .duration_since(UNIX_EPOCH)
+                        .map(|since_epoch| since_epoch.as_secs())
+                        .unwrap_or(0)
+                }),
+            };
+            let contents = serde_json::to_vec_pretty(&file)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+            if let Some(parent) = self.path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Write to a temporary file first so a crash never leaves a half-written session
+            let tmp_path = self.path.with_extension("tmp");
+            write_private(&tmp_path, &contents)?;
+            fs::rename(&tmp_path, &self.path)
+        }
+
+        fn clear(&self) -> io::Result<()> {
+            remove_if_exists(&self.path)
+        }
+    }
+
+    /// Write a new file that only the current user can read, as it holds the auth token
+    fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+        // The mode only applies to new files, so do not reuse a leftover one
+        remove_if_exists(path)?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options.open(path)?.write_all(contents)
+    }
+
+    fn remove_if_exists(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        /// Path of a session file no other test uses
+        fn session_path(name: &str) -> PathBuf {
+            std::env::temp_dir()
+                .join(format!("tcp-client-sessions-{}", std::process::id()))
+                .join(format!("{}.json", name))
+        }
+
+        fn session(expires_at: Option<u64>) -> StoredSession {
+            StoredSession {
+                auth_token: AuthToken::from("token-1"),
+                expires_at: expires_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            }
+        }
+
+        #[test]
+        fn saves_loads_and_clears() {
+            let store = FileSessionStore::new(session_path("round-trip"));
+            assert_eq!(store.load().unwrap(), None);
+
+            store.save(&session(Some(1_735_689_600))).unwrap();
+            assert_eq!(store.load().unwrap(), Some(session(Some(1_735_689_600))));
+
+            store.save(&session(None)).unwrap();
+            assert_eq!(store.load().unwrap(), Some(session(None)));
+
+            store.clear().unwrap();
+            assert_eq!(store.load().unwrap(), None);
+            assert!(!store.path().exists());
+            // Clearing twice is fine
+            store.clear().unwrap();
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn only_the_owner_can_read_the_file() {
+            use std::os::unix::fs::PermissionsExt;
+
+            let store = FileSessionStore::new(session_path("permissions"));
+            // A leftover temporary file with wider permissions is replaced
+            fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+            fs::write(store.path().with_extension("tmp"), "{}").unwrap();
+            store.save(&session(None)).unwrap();
+
+            let mode = fs::metadata(store.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            store.clear().unwrap();
+        }
+
+        #[test]
+        fn corrupt_files_are_an_error() {
+            let store = FileSessionStore::new(session_path("corrupt"));
+            fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+            fs::write(store.path(), "{ not json").unwrap();
+
+            let error = store.load().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            store.clear().unwrap();
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Timestamp type for datapoint datetimes
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+compile_error!("`Timestamp` needs the `chrono` or the `time` feature");
+
+const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// Point in time in UTC with microsecond precision
+///
+/// Timestamps are sent to the server in the canonical format `YYYY-MM-DDTHH:MM:SS.ffffffZ`,
+/// e.g. `2025-01-31T13:45:00.250000Z`. Parsing accepts any RFC 3339 timestamp and converts it
+/// to UTC. A missing offset is read as UTC, and a space may separate the date and the time.
+/// Digits beyond microseconds are truncated. Years must lie between 0000 and 9999.
+///
+/// The timestamp is a `chrono::DateTime<Utc>` with the `chrono` feature (enabled by default),
+/// or a `time::OffsetDateTime` with only the `time` feature. Either way it converts to and
+/// from the types of every enabled feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(backend::DateTime);
+
+/// Error returned when a string is not a valid timestamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimestampError {
+    input: String,
+    reason: String,
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid timestamp `{}`: {}", self.input, self.reason)
+    }
+}
+
+impl std::error::Error for TimestampError {}
+
+/// Earliest supported timestamp, 0000-01-01T00:00:00Z
+const MIN_MICROS: i64 = -62_167_219_200 * MICROS_PER_SECOND;
+/// Latest supported timestamp, 9999-12-31T23:59:59.999999Z
+const MAX_MICROS: i64 = 253_402_300_800 * MICROS_PER_SECOND - 1;
+
+impl Timestamp {
+    /// Get the current time
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// Create a timestamp from microseconds since the Unix epoch
+    ///
+    /// Returns `None` outside of the years 0000 to 9999.
+    pub fn from_unix_micros(micros: i64) -> Option<Self> {
+        if !(MIN_MICROS..=MAX_MICROS).contains(&micros) {
+            return None;
+        }
+        backend::from_unix_micros(micros).map(Self)
+    }
+
+    /// Create a timestamp from seconds since the Unix epoch
+    pub fn from_unix_seconds(seconds: i64) -> Option<Self> {
+        Self::from_unix_micros(seconds.checked_mul(MICROS_PER_SECOND)?)
+    }
+
+    /// Get the number of microseconds since the Unix epoch
+    pub fn unix_micros(&self) -> i64 {
+        backend::unix_micros(&self.0)
+    }
+
+    /// Parse a timestamp, converting it to UTC
+    pub fn parse(input: &str) -> Result<Self, TimestampError> {
+        let micros = backend::parse_rfc3339(&strict_rfc3339(input.trim()));
+        micros
+            .and_then(|micros| {
+                Self::from_unix_micros(micros)
+                    .ok_or_else(|| "year out of range after converting to UTC".to_string())
+            })
+            .map_err(|reason| TimestampError {
+                input: input.to_string(),
+                reason,
+            })
+    }
+
+    /// Create a timestamp, clamping `micros` to the supported years
+    fn from_unix_micros_clamped(micros: i64) -> Self {
+        Self::from_unix_micros(micros.clamp(MIN_MICROS, MAX_MICROS))
+            .expect("supported years are within range")
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        backend::format(&self.0, f)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = TimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let input = String::deserialize(deserializer)?;
+        Self::parse(&input).map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    /// Convert a system time, clamping it to the supported years
+    fn from(time: SystemTime) -> Self {
+        let micros = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_micros()).unwrap_or(i64::MAX),
+            Err(e) => i64::try_from(e.duration().as_micros()).map_or(i64::MIN, |micros| -micros),
+        };
+        Self::from_unix_micros_clamped(micros)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        let micros = timestamp.unix_micros();
+        let since = Duration::from_micros(micros.unsigned_abs());
+        if micros >= 0 {
+            UNIX_EPOCH + since
+        } else {
+            UNIX_EPOCH - since
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Timestamp {
+    /// Convert a chrono date time, clamping it to the supported years
+    fn from(time: chrono::DateTime<Tz>) -> Self {
+        Self::from_unix_micros_clamped(time.timestamp_micros())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<Timestamp> for chrono::DateTime<chrono::Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        chrono::DateTime::from_timestamp_micros(timestamp.unix_micros())
+            .expect("supported years are within chrono's range")
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    /// Convert a time date time, clamping it to the supported years
+    fn from(time: time::OffsetDateTime) -> Self {
+        let micros = time.unix_timestamp_nanos() / 1000;
+        Self::from_unix_micros_clamped(micros.clamp(MIN_MICROS.into(), MAX_MICROS.into()) as i64)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<Timestamp> for time::OffsetDateTime {
+    fn from(timestamp: Timestamp) -> Self {
+        time::OffsetDateTime::from_unix_timestamp_nanos(i128::from(timestamp.unix_micros()) * 1000)
+            .expect("supported years are within time's range")
+    }
+}
+
+/// Bring the variations `Timestamp::parse` accepts into strict RFC 3339: an upper case `T`
+/// between the date and the time, an upper case `Z`, and `Z` for a missing offset
+fn strict_rfc3339(input: &str) -> String {
+    let mut strict: String = input
+        .char_indices()
+        .map(|(i, c)| match c {
+            ' ' | 't' if i == 10 => 'T',
+            'z' => 'Z',
+            c => c,
+        })
+        .collect();
+
+    let bytes = strict.as_bytes();
+    let has_offset = bytes.last() == Some(&b'Z')
+        || bytes.len() >= 6
+            && matches!(bytes[bytes.len() - 6], b'+' | b'-')
+            && bytes[bytes.len() - 3] == b':';
+    if !has_offset {
+        strict.push('Z');
+    }
+    strict
+}
+
+#[cfg(feature = "chrono")]
+mod backend {
+    use std::fmt;
+
+    pub(super) type DateTime = chrono::DateTime<chrono::Utc>;
+
+    pub(super) fn from_unix_micros(micros: i64) -> Option<DateTime> {
+        DateTime::from_timestamp_micros(micros)
+    }
+
+    pub(super) fn unix_micros(time: &DateTime) -> i64 {
+        time.timestamp_micros()
+    }
+
+    /// Parse a strict RFC 3339 timestamp into microseconds since the Unix epoch
+    pub(super) fn parse_rfc3339(input: &str) -> Result<i64, String> {
+        let time = chrono::DateTime::parse_from_rfc3339(input).map_err(|e| e.to_string())?;
+        // A leap second is folded into the last second of the minute
+        let micros = time.timestamp_subsec_micros() % 1_000_000;
+        Ok(time.timestamp() * 1_000_000 + i64::from(micros))
+    }
+
+    pub(super) fn format(time: &DateTime, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", time.format("%Y-%m-%dT%H:%M:%S%.6fZ"))
+    }
+}
+
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+mod backend {
+    use std::fmt;
+    use time::format_description::well_known::Rfc3339;
+
+    pub(super) type DateTime = time::OffsetDateTime;
+
+    pub(super) fn from_unix_micros(micros: i64) -> Option<DateTime> {
+        DateTime::from_unix_timestamp_nanos(i128::from(micros) * 1000).ok()
+    }
+
+    pub(super) fn unix_micros(time: &DateTime) -> i64 {
+        (time.unix_timestamp_nanos() / 1000) as i64
+    }
+
+    /// Parse a strict RFC 3339 timestamp into microseconds since the Unix epoch
+    pub(super) fn parse_rfc3339(input: &str) -> Result<i64, String> {
+        let time = DateTime::parse(input, &Rfc3339).map_err(|e| e.to_string())?;
+        Ok(time.unix_timestamp() * 1_000_000 + i64::from(time.microsecond()))
+    }
+
+    pub(super) fn format(time: &DateTime, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            time.year(),
+            u8::from(time.month()),
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second(),
+            time.microsecond()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(input: &str) -> String {
+        Timestamp::parse(input).unwrap().to_string()
+    }
+
+    #[test]
+    fn formats_in_canonical_utc() {
+        assert_eq!(
+            canonical("1970-01-01T00:00:00Z"),
+            "1970-01-01T00:00:00.000000Z"
+        );
+        assert_eq!(
+            Timestamp::parse("1970-01-01T00:00:00Z")
+                .unwrap()
+                .unix_micros(),
+            0
+        );
+        assert_eq!(
+            canonical("2024-02-29T23:59:59.5Z"),
+            "2024-02-29T23:59:59.500000Z"
+        );
+        assert_eq!(
+            canonical("1969-12-31T23:59:59.999999Z"),
+            "1969-12-31T23:59:59.999999Z"
+        );
+    }
+
+    #[test]
+    fn normalizes_offsets_to_utc() {
+        assert_eq!(
+            canonical("2025-01-01T01:30:00+01:30"),
+            "2025-01-01T00:00:00.000000Z"
+        );
+        assert_eq!(
+            canonical("2024-12-31T23:00:00-01:00"),
+            "2025-01-01T00:00:00.000000Z"
+        );
+        assert_eq!(
+            canonical("2025-01-01 00:00:00"),
+            "2025-01-01T00:00:00.000000Z"
+        );
+        assert_eq!(
+            canonical("2025-01-01t00:00:00.123456789z"),
+            "2025-01-01T00:00:00.123456Z"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        for input in [
+            "",
+            "2025-01-01",
+            "2025-13-01T00:00:00Z",
+            "2025-02-29T00:00:00Z",
+            "2025-01-01T24:00:00Z",
+            "2025-01-01T00:00:00.Z",
+            "2025-01-01T00:00:00+0100",
+            "2025-01-01T00:00:00Zjunk",
+            "0000-01-01T00:00:00+01:00",
+        ] {
+            assert!(Timestamp::parse(input).is_err(), "{} was accepted", input);
+        }
+    }
+
+    #[test]
+    fn knows_the_leap_years() {
+        for (input, valid) in [
+            ("1600-02-29T00:00:00Z", true),
+            ("1900-02-29T00:00:00Z", false),
+            ("2000-02-29T00:00:00Z", true),
+            ("2023-02-29T00:00:00Z", false),
+            ("2024-02-29T00:00:00Z", true),
+            ("2100-02-29T00:00:00Z", false),
+        ] {
+            assert_eq!(Timestamp::parse(input).is_ok(), valid, "{}", input);
+        }
+        // The day after a leap day
+        assert_eq!(
+            canonical("2024-02-28T12:00:00-12:00"),
+            "2024-02-29T00:00:00.000000Z"
+        );
+        assert_eq!(
+            canonical("2023-02-28T12:00:00-12:00"),
+            "2023-03-01T00:00:00.000000Z"
+        );
+    }
+
+    #[test]
+    fn handles_years_before_1970() {
+        let micros = |input| Timestamp::parse(input).unwrap().unix_micros();
+        assert_eq!(micros("1969-12-31T23:59:59Z"), -MICROS_PER_SECOND);
+        assert_eq!(
+            micros("1900-01-01T00:00:00Z"),
+            -2_208_988_800 * MICROS_PER_SECOND
+        );
+        assert_eq!(
+            micros("0001-01-01T00:00:00Z"),
+            -62_135_596_800 * MICROS_PER_SECOND
+        );
+        // Digits beyond microseconds round towards the past, also before the epoch
+        assert_eq!(
+            canonical("1969-12-31T23:59:59.9999999Z"),
+            "1969-12-31T23:59:59.999999Z"
+        );
+        assert_eq!(
+            canonical("1969-12-31T23:59:59.000000999Z"),
+            "1969-12-31T23:59:59.000000Z"
+        );
+
+        let earliest = Timestamp::from_unix_micros(MIN_MICROS).unwrap();
+        assert_eq!(earliest.to_string(), "0000-01-01T00:00:00.000000Z");
+        assert_eq!(Timestamp::from_unix_micros(MIN_MICROS - 1), None);
+        let latest = Timestamp::from_unix_micros(MAX_MICROS).unwrap();
+        assert_eq!(latest.to_string(), "9999-12-31T23:59:59.999999Z");
+        assert_eq!(Timestamp::from_unix_micros(MAX_MICROS + 1), None);
+    }
+
+    #[test]
+    fn round_trips_through_serde() {
+        let timestamp = Timestamp::parse("2025-06-15T08:09:10.011Z").unwrap();
+        let json = serde_json::to_string(&timestamp).unwrap();
+        assert_eq!(json, "\"2025-06-15T08:09:10.011000Z\"");
+        assert_eq!(serde_json::from_str::<Timestamp>(&json).unwrap(), timestamp);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn converts_chrono_date_times() {
+        let timestamp = Timestamp::parse("2025-06-15T08:09:10.011Z").unwrap();
+        let date_time = chrono::DateTime::<chrono::Utc>::from(timestamp);
+        assert_eq!(date_time.timestamp_micros(), timestamp.unix_micros());
+        assert_eq!(Timestamp::from(date_time), timestamp);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn converts_time_date_times() {
+        let timestamp = Timestamp::parse("2025-06-15T08:09:10.011Z").unwrap();
+        let date_time = time::OffsetDateTime::from(timestamp);
+        assert_eq!(
+            date_time.unix_timestamp_nanos(),
+            timestamp.unix_micros() as i128 * 1000
+        );
+        assert_eq!(Timestamp::from(date_time), timestamp);
+    }
+}
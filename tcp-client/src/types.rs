@@ -0,0 +1,103 @@
+//! Newtypes for the auth token and resource identifiers
+//!
+//! Each identifier wraps a `String` but is a distinct type, so the login cookie cannot be
+//! passed where the ID of a recording session is expected, and vice versa.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+macro_rules! string_newtype {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            /// Wrap a string value
+            pub fn new(value: impl Into<String>) -> Self {
+                Self(value.into())
+            }
+
+            /// Get the wrapped value as a string slice
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+
+            /// Unwrap the inner string
+            pub fn into_inner(self) -> String {
+                self.0
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                Self(value.to_string())
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+    };
+}
+
+macro_rules! display_newtype {
+    ($($name:ident),*) => {
+        $(
+            impl fmt::Debug for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.debug_tuple(stringify!($name)).field(&self.0).finish()
+                }
+            }
+
+            impl fmt::Display for $name {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+        )*
+    };
+}
+
+string_newtype!(
+    /// Value of the `session_id` login cookie used to authenticate requests
+    AuthToken
+);
+
+string_newtype!(
+    /// ID of a recording session
+    SessionId
+);
+
+string_newtype!(
+    /// ID of a sensor
+    SensorId
+);
+
+string_newtype!(
+    /// ID of a link between a recording session and a sensor
+    SessionSensorId
+);
+
+string_newtype!(
+    /// Name of a user account
+    Username
+);
+
+display_newtype!(SessionId, SensorId, SessionSensorId, Username);
+
+// The auth token is a credential, so keep it out of logs and debug output
+impl fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuthToken(***)")
+    }
+}
@@ -0,0 +1,316 @@
+//! Buffered upload of datapoints through the batch endpoint
+
+use crate::api::session_sensor_data::SessionSensorData;
+use crate::api_client::ApiClient;
+use crate::batch::BatchSize;
+use crate::error::ApiError;
+use crate::requests::limits::Priority;
+use futures::channel::{mpsc, oneshot};
+use futures::future::{self, Either};
+use futures::{SinkExt, StreamExt};
+use std::fmt;
+use std::time::Duration;
+
+/// Limits that decide when buffered datapoints are uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploaderConfig {
+    /// Upload once this many datapoints are buffered
+    pub max_batch_len: usize,
+    /// Upload before the JSON body of the batch would exceed this many bytes
+    pub max_batch_bytes: usize,
+    /// Upload datapoints at the latest this long after the first one was buffered
+    pub flush_interval: Duration,
+    /// Number of datapoints that can wait to be buffered before `send` waits for room
+    pub channel_capacity: usize,
+    /// Number of flush reports kept until they are read; further reports are dropped
+    pub report_capacity: usize,
+}
+
+impl Default for UploaderConfig {
+    fn default() -> Self {
+        Self {
+            max_batch_len: 500,
+            max_batch_bytes: 1024 * 1024,
+            flush_interval: Duration::from_secs(1),
+            channel_capacity: 1024,
+            report_capacity: 64,
+        }
+    }
+}
+
+/// What caused a batch to be uploaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushReason {
+    /// `max_batch_len` datapoints were buffered
+    BatchLen,
+    /// The next datapoint would have exceeded `max_batch_bytes`
+    BatchBytes,
+    /// `flush_interval` passed since the first datapoint was buffered
+    Interval,
+    /// `DatapointUploader::flush` was called
+    Requested,
+    /// The uploader was shut down
+    Shutdown,
+}
+
+/// Outcome of uploading one batch
+#[derive(Debug)]
+pub struct FlushReport {
+    pub reason: FlushReason,
+    /// The datapoints of the batch, so failed ones can be stored or sent again
+    pub datapoints: Vec<SessionSensorData>,
+    pub result: Result<(), ApiError>,
+}
+
+/// Error returned when the upload worker is no longer running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploaderClosed;
+
+impl fmt::Display for UploaderClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "datapoint uploader has shut down")
+    }
+}
+
+impl std::error::Error for UploaderClosed {}
+
+/// Error returned by `DatapointUploader::try_send`, handing back the datapoint
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrySendError {
+    /// The channel to the worker is full; the datapoint can be sent again later
+    Full(SessionSensorData),
+    /// The upload worker is no longer running
+    Closed(SessionSensorData),
+}
+
+impl TrySendError {
+    /// Check whether the channel to the worker was full
+    pub fn is_full(&self) -> bool {
+        matches!(self, TrySendError::Full(_))
+    }
+
+    /// Check whether the upload worker is no longer running
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TrySendError::Closed(_))
+    }
+
+    /// Get back the datapoint that was not sent
+    pub fn into_datapoint(self) -> SessionSensorData {
+        match self {
+            TrySendError::Full(datapoint) | TrySendError::Closed(datapoint) => datapoint,
+        }
+    }
+}
+
+impl fmt::Display for TrySendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => write!(f, "datapoint uploader channel is full"),
+            TrySendError::Closed(_) => UploaderClosed.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TrySendError {}
+
+/// Message from an uploader handle to the worker
+enum Command {
+    Datapoint(SessionSensorData),
+    Flush(oneshot::Sender<()>),
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Handle for feeding single datapoints to an `UploadWorker`
+///
+/// Datapoints are buffered and uploaded in one request to the batch endpoint when the buffer
+/// reaches `max_batch_len` datapoints or `max_batch_bytes` bytes, or `flush_interval` after the
+/// first datapoint was buffered. Handles can be cloned to feed the same worker from several places.
+#[derive(Debug, Clone)]
+pub struct DatapointUploader {
+    sender: mpsc::Sender<Command>,
+}
+
+/// Background task that buffers and uploads the datapoints, created with
+/// `DatapointUploader::new`
+///
+/// Spawn `run` on the application's executor, e.g. `tokio::spawn` or
+/// `wasm_bindgen_futures::spawn_local`.
+#[derive(Debug)]
+#[must_use = "the uploader does nothing unless the worker is run"]
+pub struct UploadWorker {
+    client: ApiClient,
+    config: UploaderConfig,
+    receiver: mpsc::Receiver<Command>,
+    reports: mpsc::Sender<FlushReport>,
+}
+
+impl DatapointUploader {
+    /// Create an uploader sending through `client`
+    ///
+    /// Uploads are sent with `Priority::Background`, so they wait behind interactive calls
+    /// when the client is rate limited.
+    /// Returns the handle, the worker to spawn, and a stream with the outcome of every upload.
+    /// The stream can be dropped if the outcomes are not needed. Otherwise up to
+    /// `report_capacity` reports are kept until read, and reports arriving while it is full
+    /// are dropped rather than holding on to ever more datapoints.
+    pub fn new(
+        client: ApiClient,
+        config: UploaderConfig,
+    ) -> (Self, UploadWorker, mpsc::Receiver<FlushReport>) {
+        let (sender, receiver) = mpsc::channel(config.channel_capacity);
+        let (reports, report_receiver) = mpsc::channel(config.report_capacity);
+
+        let worker = UploadWorker {
+            client: client.with_priority(Priority::Background),
+            config,
+            receiver,
+            reports,
+        };
+        (Self { sender }, worker, report_receiver)
+    }
+
+    /// Add a datapoint, waiting while the channel to the worker is full
+    pub async fn send(&mut self, datapoint: SessionSensorData) -> Result<(), UploaderClosed> {
+        self.sender
+            .send(Command::Datapoint(datapoint))
+            .await
+            .map_err(|_| UploaderClosed)
+    }
+
+    /// Add a datapoint without waiting, handing it back if the channel to the worker is full
+    /// or the worker is no longer running
+    pub fn try_send(&mut self, datapoint: SessionSensorData) -> Result<(), TrySendError> {
+        self.sender
+            .try_send(Command::Datapoint(datapoint))
+            .map_err(|e| {
+                let full = e.is_full();
+                match (e.into_inner(), full) {
+                    (Command::Datapoint(datapoint), true) => TrySendError::Full(datapoint),
+                    (Command::Datapoint(datapoint), false) => TrySendError::Closed(datapoint),
+                    _ => unreachable!("only datapoints are sent here"),
+                }
+            })
+    }
+
+    /// Upload all datapoints sent so far, waiting until the upload has finished
+    pub async fn flush(&mut self) -> Result<(), UploaderClosed> {
+        let (done, finished) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .await
+            .map_err(|_| UploaderClosed)?;
+        finished.await.map_err(|_| UploaderClosed)
+    }
+
+    /// Upload all datapoints sent so far and stop the worker
+    ///
+    /// Other handles fail with `UploaderClosed` afterwards. The worker also shuts down on its
+    /// own once every handle has been dropped.
+    pub async fn shutdown(mut self) -> Result<(), UploaderClosed> {
+        let (done, finished) = oneshot::channel();
+        self.sender
+            .send(Command::Shutdown(done))
+            .await
+            .map_err(|_| UploaderClosed)?;
+        finished.await.map_err(|_| UploaderClosed)
+    }
+}
+
+impl UploadWorker {
+    /// Buffer and upload datapoints until the uploader is shut down
+    pub async fn run(mut self) {
+        let mut buffer = Vec::new();
+        let mut buffer_size = BatchSize::default();
+        let mut deadline = None;
+        // Handles waiting for the shutdown to finish
+        let mut shutdown_waiters = Vec::new();
+
+        loop {
+            // Wait for the next command, or for the interval to pass if datapoints are waiting
+            let command = match deadline.as_mut() {
+                None => self.receiver.next().await,
+                Some(delay) => match future::select(self.receiver.next(), delay).await {
+                    Either::Left((command, _)) => command,
+                    Either::Right(_) => {
+                        self.flush(&mut buffer, FlushReason::Interval).await;
+                        buffer_size = BatchSize::default();
+                        deadline = None;
+                        continue;
+                    }
+                },
+            };
+
+            match command {
+                Some(Command::Datapoint(datapoint)) => {
+                    let bytes = BatchSize::datapoint_bytes(&datapoint);
+                    if buffer_size.exceeds(bytes, self.config.max_batch_bytes) {
+                        self.flush(&mut buffer, FlushReason::BatchBytes).await;
+                        buffer_size = BatchSize::default();
+                    }
+
+                    if buffer.is_empty() {
+                        deadline = Some(futures_timer::Delay::new(self.config.flush_interval));
+                    }
+                    buffer.push(datapoint);
+                    buffer_size.add(bytes);
+
+                    if buffer.len() >= self.config.max_batch_len {
+                        self.flush(&mut buffer, FlushReason::BatchLen).await;
+                        buffer_size = BatchSize::default();
+                        deadline = None;
+                    }
+                }
+                Some(Command::Flush(done)) => {
+                    self.flush(&mut buffer, FlushReason::Requested).await;
+                    buffer_size = BatchSize::default();
+                    deadline = None;
+                    let _ = done.send(());
+                }
+                Some(Command::Shutdown(done)) => {
+                    // Refuse new commands but still handle the ones already queued
+                    self.receiver.close();
+                    shutdown_waiters.push(done);
+                }
+                None => {
+                    self.flush(&mut buffer, FlushReason::Shutdown).await;
+                    for done in shutdown_waiters {
+                        let _ = done.send(());
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Upload the buffered datapoints, if any, and report the outcome
+    async fn flush(&mut self, buffer: &mut Vec<SessionSensorData>, reason: FlushReason) {
+        if buffer.is_empty() {
+            return;
+        }
+
+        let datapoints = std::mem::take(buffer);
+        let result = self
+            .client
+            .datapoints()
+            .send_batch(datapoints.clone())
+            .await;
+
+        let report = FlushReport {
+            reason,
+            datapoints,
+            result,
+        };
+        // Nobody listening for reports is fine
+        if let Err(e) = self.reports.try_send(report) {
+            if e.is_full() {
+                let report = e.into_inner();
+                tracing::warn!(
+                    reason = ?report.reason,
+                    datapoints = report.datapoints.len(),
+                    failed = report.result.is_err(),
+                    "report channel of the datapoint uploader is full, dropping a flush report"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Runs every `api::*` function against the mock server
+
+mod common;
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::api::{auth, sensor, session, session_sensor, session_sensor_data, user};
+use client::reqwest::{Client, StatusCode};
+use client::{ApiError, AuthToken, SensorId, SessionId, SessionSensorId, Timestamp, Username};
+use common::{login_new_user, shared_server, unique_username, PASSWORD};
+
+fn assert_status<T: std::fmt::Debug>(result: Result<T, ApiError>, expected: StatusCode) {
+    match result {
+        Err(e) => assert_eq!(e.status(), Some(expected), "unexpected error: {}", e),
+        Ok(value) => panic!("expected {}, got {:?}", expected, value),
+    }
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: u32) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap()
+}
+
+#[tokio::test]
+async fn users() {
+    shared_server();
+    let client = Client::new();
+    let username = unique_username("users");
+
+    let created = user::create_user(&client, &username, PASSWORD)
+        .await
+        .unwrap();
+    assert_eq!(created.username, username);
+    assert_status(
+        user::create_user(&client, &username, PASSWORD).await,
+        StatusCode::CONFLICT,
+    );
+
+    let found = user::view_user_by_username(&client, &username)
+        .await
+        .unwrap();
+    assert_eq!(found.username, username);
+
+    let auth_token = auth::user_login(&client, &username, PASSWORD)
+        .await
+        .unwrap()
+        .unwrap()
+        .auth_token();
+    let all = user::view_all_users(&client, &auth_token).await.unwrap();
+    assert!(all.users.iter().any(|user| user.username == username));
+    let profile = user::view_user_profile(&client, &auth_token).await.unwrap();
+    assert_eq!(profile.username, username);
+
+    let updated = user::update_user(&client, &username, "new password")
+        .await
+        .unwrap();
+    assert_eq!(updated.username, username);
+    assert_status(
+        auth::user_login(&client, &username, PASSWORD).await,
+        StatusCode::UNAUTHORIZED,
+    );
+
+    user::delete_user(&client, &username).await.unwrap();
+    assert_status(
+        user::view_user_by_username(&client, &username).await,
+        StatusCode::NOT_FOUND,
+    );
+    assert_status(
+        user::delete_user(&client, &username).await,
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[tokio::test]
+async fn authentication() {
+    let client = Client::new();
+    let (username, _) = login_new_user(&client, "auth").await;
+
+    assert_status(
+        auth::user_login(&client, &username, "wrong password").await,
+        StatusCode::UNAUTHORIZED,
+    );
+
+    let cookie = auth::user_login(&client, &username, PASSWORD)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(cookie.http_only);
+    assert!(cookie.expires_at().is_some());
+    let auth_token = cookie.auth_token();
+
+    let renewed = auth::renew_session(&client, &auth_token).await.unwrap();
+    assert_ne!(renewed.auth_token(), auth_token);
+    assert_status(
+        user::view_user_profile(&client, &auth_token).await,
+        StatusCode::UNAUTHORIZED,
+    );
+
+    let logout_cookie = auth::user_logout(&client, &renewed.auth_token())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(logout_cookie.is_deletion());
+    assert_status(
+        user::view_user_profile(&client, &renewed.auth_token()).await,
+        StatusCode::UNAUTHORIZED,
+    );
+    assert_status(
+        auth::renew_session(&client, &renewed.auth_token()).await,
+        StatusCode::UNAUTHORIZED,
+    );
+}
+
+#[tokio::test]
+async fn sensors() {
+    let client = Client::new();
+    let (_, auth_token) = login_new_user(&client, "sensors").await;
+
+    let created = sensor::create_sensor(&client, &auth_token, "thermometer")
+        .await
+        .unwrap();
+    assert_eq!(created.sensor_type, "thermometer");
+
+    let all = sensor::view_all_sensors(&client, &auth_token)
+        .await
+        .unwrap();
+    assert!(all.sensors.contains(&created));
+    let found = sensor::view_sensor_by_id(&client, &auth_token, &created.id)
+        .await
+        .unwrap();
+    assert_eq!(found, created);
+
+    let updated = sensor::update_sensor(&client, &auth_token, &created.id, "barometer")
+        .await
+        .unwrap();
+    assert_eq!(updated.id, created.id);
+    assert_eq!(updated.sensor_type, "barometer");
+
+    sensor::delete_sensor(&client, &auth_token, &created.id)
+        .await
+        .unwrap();
+    assert_status(
+        sensor::view_sensor_by_id(&client, &auth_token, &created.id).await,
+        StatusCode::NOT_FOUND,
+    );
+    assert_status(
+        sensor::view_all_sensors(&client, &AuthToken::from("bogus")).await,
+        StatusCode::UNAUTHORIZED,
+    );
+}
+
+#[tokio::test]
+async fn sessions() {
+    let client = Client::new();
+    let (username, auth_token) = login_new_user(&client, "sessions").await;
+    let (other_username, _) = login_new_user(&client, "sessions-other").await;
+
+    let created = session::create_session(&client, &username).await.unwrap();
+    assert_eq!(created.username, username);
+    assert_status(
+        session::create_session(&client, &Username::from("nobody")).await,
+        StatusCode::NOT_FOUND,
+    );
+
+    let all = session::view_all_sessions(&client).await.unwrap();
+    assert!(all.sessions.contains(&created));
+    let by_user = session::view_sessions_by_user(&client, &username)
+        .await
+        .unwrap();
+    assert_eq!(by_user.sessions, vec![created.clone()]);
+    let found = session::view_session_by_id(&client, &auth_token, &created.id)
+        .await
+        .unwrap();
+    assert_eq!(found, created);
+
+    let updated = session::update_session(&client, &auth_token, &created.id, &other_username)
+        .await
+        .unwrap();
+    assert_eq!(updated.username, other_username);
+
+    session::delete_session(&client, &auth_token, &created.id)
+        .await
+        .unwrap();
+    assert_status(
+        session::view_session_by_id(&client, &auth_token, &created.id).await,
+        StatusCode::NOT_FOUND,
+    );
+    assert_status(
+        session::delete_session(&client, &auth_token, &SessionId::from("missing")).await,
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[tokio::test]
+async fn session_sensors() {
+    let client = Client::new();
+    let (username, auth_token) = login_new_user(&client, "links").await;
+    let session = session::create_session(&client, &username).await.unwrap();
+    let other_session = session::create_session(&client, &username).await.unwrap();
+    let sensor = sensor::create_sensor(&client, &auth_token, "gyroscope")
+        .await
+        .unwrap();
+
+    let link = session_sensor::create_session_sensor(&client, &auth_token, &session.id, &sensor.id)
+        .await
+        .unwrap();
+    assert_eq!(link.session_id, session.id);
+    assert_eq!(link.sensor_id, sensor.id);
+    assert_status(
+        session_sensor::create_session_sensor(&client, &auth_token, &session.id, &sensor.id).await,
+        StatusCode::CONFLICT,
+    );
+    assert_status(
+        session_sensor::create_session_sensor(
+            &client,
+            &auth_token,
+            &session.id,
+            &SensorId::from("missing"),
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+    );
+    assert_status(
+        session::delete_session(&client, &auth_token, &session.id).await,
+        StatusCode::CONFLICT,
+    );
+
+    let all = session_sensor::view_all_sensor_sessions(&client, &auth_token)
+        .await
+        .unwrap();
+    assert!(all.links.contains(&link));
+    let by_session = session_sensor::view_sensors_by_session_id(&client, &auth_token, &session.id)
+        .await
+        .unwrap();
+    assert_eq!(by_session.links, vec![link.clone()]);
+    let by_sensor =
+        session_sensor::view_session_sensor_by_sensor_id(&client, &auth_token, &sensor.id)
+            .await
+            .unwrap();
+    assert_eq!(by_sensor, link);
+
+    let updated = session_sensor::update_sensor_session(
+        &client,
+        &auth_token,
+        &link.id,
+        &other_session.id,
+        &sensor.id,
+    )
+    .await
+    .unwrap();
+    assert_eq!(updated.session_id, other_session.id);
+
+    session_sensor::delete_sensor_session(&client, &auth_token, &link.id)
+        .await
+        .unwrap();
+    assert_status(
+        session_sensor::view_session_sensor_by_sensor_id(&client, &auth_token, &sensor.id).await,
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[tokio::test]
+async fn datapoints() {
+    let client = Client::new();
+    let (username, auth_token) = login_new_user(&client, "data").await;
+    let session = session::create_session(&client, &username).await.unwrap();
+    let sensor = sensor::create_sensor(&client, &auth_token, "accelerometer")
+        .await
+        .unwrap();
+    let link = session_sensor::create_session_sensor(&client, &auth_token, &session.id, &sensor.id)
+        .await
+        .unwrap();
+
+    let first = session_sensor_data::create_datapoint(&client, &auth_token, &link.id, at(0), "1.0")
+        .await
+        .unwrap();
+    assert_eq!(first.data_blob, "1.0");
+    assert_status(
+        session_sensor_data::create_datapoint(&client, &auth_token, &link.id, at(0), "again").await,
+        StatusCode::CONFLICT,
+    );
+    assert_status(
+        session_sensor_data::create_datapoint(
+            &client,
+            &auth_token,
+            &SessionSensorId::from("missing"),
+            at(0),
+            "1.0",
+        )
+        .await,
+        StatusCode::NOT_FOUND,
+    );
+
+    let batch = [at(1), at(2)]
+        .into_iter()
+        .map(|datetime| SessionSensorData {
+            id: link.id.clone(),
+            datetime,
+            data_blob: "2.0".to_string(),
+        })
+        .collect();
+    session_sensor_data::batch_create_datapoint(&client, &auth_token, batch)
+        .await
+        .unwrap();
+
+    let all = session_sensor_data::view_all_datapoints(&client, &auth_token)
+        .await
+        .unwrap();
+    assert!(all.datapoints.contains(&first));
+    let by_session =
+        session_sensor_data::view_datapoints_by_session_id(&client, &auth_token, &session.id)
+            .await
+            .unwrap();
+    assert_eq!(by_session.datapoints.len(), 3);
+    let by_link =
+        session_sensor_data::view_datapoints_by_session_sensor(&client, &auth_token, &link.id)
+            .await
+            .unwrap();
+    assert_eq!(by_link.datapoints, by_session.datapoints);
+    // Timestamps with an offset address the same datapoint as their UTC equivalent
+    let found = session_sensor_data::view_datapoints_by_id_datetime(
+        &client,
+        &auth_token,
+        &link.id,
+        "2025-01-01T01:00:00+01:00".parse().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(found, first);
+    let after = session_sensor_data::view_all_datapoints_by_id_datetime(
+        &client,
+        &auth_token,
+        &session.id,
+        at(0),
+    )
+    .await
+    .unwrap();
+    assert_eq!(after.datapoints.len(), 2);
+
+    let updated =
+        session_sensor_data::update_datapoint(&client, &auth_token, &link.id, at(0), "3.0")
+            .await
+            .unwrap();
+    assert_eq!(updated.data_blob, "3.0");
+
+    session_sensor_data::delete_datapoint(&client, &auth_token, &link.id, at(0))
+        .await
+        .unwrap();
+    assert_status(
+        session_sensor_data::view_datapoints_by_id_datetime(&client, &auth_token, &link.id, at(0))
+            .await,
+        StatusCode::NOT_FOUND,
+    );
+}
@@ -0,0 +1,98 @@
+//! Runs `ApiClient` against the mock server
+
+use client::mock_server::MockServer;
+use client::reqwest::StatusCode;
+use client::{ApiClient, Username};
+
+const PASSWORD: &str = "hunter2";
+
+async fn logged_in_client(server: &MockServer) -> ApiClient {
+    let client = ApiClient::builder().base_url(server.base_url()).build();
+    let username = Username::from("alice");
+    client.users().create(&username, PASSWORD).await.unwrap();
+    client.auth().login(&username, PASSWORD).await.unwrap();
+    client
+}
+
+#[tokio::test]
+async fn login_stores_the_auth_token() {
+    let server = MockServer::start().unwrap();
+    let client = logged_in_client(&server).await;
+
+    assert!(client.auth_token().is_some());
+    assert!(client.auth_token_expires_at().is_some());
+    assert_eq!(
+        client.users().profile().await.unwrap().username,
+        Username::from("alice")
+    );
+
+    client.auth().logout().await.unwrap();
+    assert!(client.auth_token().is_none());
+    let error = client.users().profile().await.unwrap_err();
+    assert_eq!(error.status(), Some(StatusCode::UNAUTHORIZED));
+}
+
+#[tokio::test]
+async fn expired_session_is_renewed_and_request_replayed() {
+    let server = MockServer::start().unwrap();
+    let client = logged_in_client(&server).await;
+    let old_token = client.auth_token();
+
+    server.expire_sessions();
+    let sensor = client.sensors().create("thermometer").await.unwrap();
+
+    assert_eq!(sensor.sensor_type, "thermometer");
+    assert_ne!(client.auth_token(), old_token);
+    assert_eq!(server.active_sessions(), 1);
+}
+
+#[tokio::test]
+async fn session_close_to_expiry_is_renewed_ahead_of_time() {
+    let server = MockServer::start().unwrap();
+    // Every token issued expires within the default renewal margin
+    server.set_session_ttl(std::time::Duration::from_secs(30));
+    let client = logged_in_client(&server).await;
+    let old_token = client.auth_token();
+
+    client.sensors().list().await.unwrap();
+
+    assert_ne!(client.auth_token(), old_token);
+}
+
+#[tokio::test]
+async fn server_errors_keep_the_status_and_message() {
+    let server = MockServer::start().unwrap();
+    let client = logged_in_client(&server).await;
+
+    let error = client
+        .users()
+        .create(&Username::from("alice"), PASSWORD)
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.status(), Some(StatusCode::CONFLICT));
+    assert_eq!(error.server_message(), Some("user already exists"));
+}
+
+#[tokio::test]
+async fn awkward_usernames_reach_the_right_route() {
+    let server = MockServer::start().unwrap();
+    let client = ApiClient::builder().base_url(server.base_url()).build();
+    let username = Username::from("jane doe/../users?+1");
+
+    client.users().create(&username, PASSWORD).await.unwrap();
+    client.auth().login(&username, PASSWORD).await.unwrap();
+    assert_eq!(
+        client.users().get(&username).await.unwrap().username,
+        username
+    );
+}
+
+#[test]
+fn invalid_base_url_is_rejected_when_building() {
+    let error = ApiClient::builder()
+        .base_url("localhost:7878")
+        .try_build()
+        .unwrap_err();
+    assert!(error.to_string().contains("localhost:7878"));
+}
@@ -0,0 +1,116 @@
+//! Checks that large batch uploads are split into chunks and reported per datapoint
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::fake_transport::{Exchange, FakeTransport};
+use client::reqwest::{Method, StatusCode};
+use client::{
+    ApiClient, AuthToken, BatchLimits, DatapointKey, RetryPolicy, SessionSensorId, Timestamp,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+const BATCH_URL: &str = "http://backend.test/sessions-sensors-data/batch";
+const COOKIE: &str = "session_id=token-1";
+
+fn client(fake: &Arc<FakeTransport>, batch_limits: BatchLimits) -> ApiClient {
+    ApiClient::builder()
+        .base_url("http://backend.test")
+        .auth_token(AuthToken::from("token-1"))
+        .retry_policy(RetryPolicy::none())
+        .transport(fake.clone())
+        .batch_limits(batch_limits)
+        .build()
+}
+
+fn datapoint(second: u32) -> SessionSensorData {
+    SessionSensorData {
+        id: SessionSensorId::from("7"),
+        datetime: Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap(),
+        data_blob: second.to_string(),
+    }
+}
+
+fn chunk(datapoints: &[SessionSensorData]) -> Exchange {
+    Exchange::new(Method::POST, BATCH_URL)
+        .cookie(COOKIE)
+        .json_body(json!({ "datapoints": datapoints }))
+}
+
+#[tokio::test]
+async fn large_batches_are_split_into_chunks() {
+    let datapoints: Vec<_> = (0..5).map(datapoint).collect();
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(chunk(&datapoints[0..2]).respond(StatusCode::CREATED))
+        .expect(chunk(&datapoints[2..4]).respond(StatusCode::CREATED))
+        .expect(chunk(&datapoints[4..]).respond(StatusCode::CREATED));
+    let client = client(
+        &fake,
+        BatchLimits {
+            max_len: 2,
+            max_concurrency: 2,
+            ..BatchLimits::default()
+        },
+    );
+
+    let report = client
+        .datapoints()
+        .batch_create(datapoints.clone())
+        .await
+        .unwrap();
+
+    fake.assert_done();
+    assert_eq!(report.chunks, 3);
+    assert!(report.is_complete());
+    let keys: Vec<_> = datapoints.iter().map(DatapointKey::from).collect();
+    assert_eq!(report.created, keys);
+}
+
+#[tokio::test]
+async fn failed_chunks_are_reported_by_datapoint() {
+    let datapoints: Vec<_> = (0..4).map(datapoint).collect();
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(chunk(&datapoints[0..2]).respond(StatusCode::CREATED))
+        .expect(chunk(&datapoints[2..]).respond_json(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            json!({ "message": "body too large" }),
+        ));
+    let client = client(
+        &fake,
+        BatchLimits {
+            max_len: 2,
+            ..BatchLimits::default()
+        },
+    );
+
+    let error = client
+        .datapoints()
+        .batch_create(datapoints.clone())
+        .await
+        .unwrap_err();
+
+    fake.assert_done();
+    let report = &error.report;
+    assert_eq!(report.chunks, 2);
+    assert_eq!(
+        report.created,
+        [
+            DatapointKey::from(&datapoints[0]),
+            DatapointKey::from(&datapoints[1])
+        ]
+    );
+    let failed: Vec<_> = report.failed_datapoints().cloned().collect();
+    assert_eq!(
+        failed,
+        [
+            DatapointKey::from(&datapoints[2]),
+            DatapointKey::from(&datapoints[3])
+        ]
+    );
+    assert_eq!(
+        report.failed[0].error.status(),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+    assert!(error
+        .to_string()
+        .starts_with("2 of 4 datapoints were not created"));
+}
@@ -0,0 +1,230 @@
+//! Runs every `blocking::*` function against the mock server, without an async runtime
+
+// `login_new_user` is async, so it is not used here
+#[allow(dead_code)]
+mod common;
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::blocking::{auth, sensor, session, session_sensor, session_sensor_data, user};
+use client::reqwest::{Client, StatusCode};
+use client::{ApiError, Timestamp};
+use common::{shared_server, unique_username, PASSWORD};
+
+fn assert_status<T: std::fmt::Debug>(result: Result<T, ApiError>, expected: StatusCode) {
+    match result {
+        Err(e) => assert_eq!(e.status(), Some(expected), "unexpected error: {}", e),
+        Ok(value) => panic!("expected {}, got {:?}", expected, value),
+    }
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: u32) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap()
+}
+
+#[test]
+fn users_and_authentication() {
+    shared_server();
+    let client = Client::new();
+    let username = unique_username("blocking-users");
+
+    let created = user::create_user(&client, &username, PASSWORD).unwrap();
+    assert_eq!(created.username, username);
+    assert_eq!(
+        user::view_user_by_username(&client, &username).unwrap(),
+        created
+    );
+
+    let cookie = auth::user_login(&client, &username, PASSWORD)
+        .unwrap()
+        .unwrap();
+    let renewed = auth::renew_session(&client, &cookie.auth_token()).unwrap();
+    let auth_token = renewed.auth_token();
+    assert!(user::view_all_users(&client, &auth_token)
+        .unwrap()
+        .users
+        .contains(&created));
+    assert_eq!(
+        user::view_user_profile(&client, &auth_token).unwrap(),
+        created
+    );
+
+    user::update_user(&client, &username, "new password").unwrap();
+    assert_status(
+        auth::user_login(&client, &username, PASSWORD),
+        StatusCode::UNAUTHORIZED,
+    );
+    let logout = auth::user_logout(&client, &auth_token).unwrap().unwrap();
+    assert!(logout.is_deletion());
+    assert_status(
+        user::view_user_profile(&client, &auth_token),
+        StatusCode::UNAUTHORIZED,
+    );
+
+    user::delete_user(&client, &username).unwrap();
+    assert_status(
+        user::view_user_by_username(&client, &username),
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[test]
+fn sensors_sessions_and_datapoints() {
+    shared_server();
+    let client = Client::new();
+    let username = unique_username("blocking-data");
+    user::create_user(&client, &username, PASSWORD).unwrap();
+    let auth_token = auth::user_login(&client, &username, PASSWORD)
+        .unwrap()
+        .unwrap()
+        .auth_token();
+
+    let created = sensor::create_sensor(&client, &auth_token, "thermometer").unwrap();
+    assert!(sensor::view_all_sensors(&client, &auth_token)
+        .unwrap()
+        .sensors
+        .contains(&created));
+    let sensor = sensor::update_sensor(&client, &auth_token, &created.id, "barometer").unwrap();
+    assert_eq!(
+        sensor::view_sensor_by_id(&client, &auth_token, &created.id).unwrap(),
+        sensor
+    );
+
+    let session = session::create_session(&client, &username).unwrap();
+    assert!(session::view_all_sessions(&client)
+        .unwrap()
+        .sessions
+        .contains(&session));
+    assert_eq!(
+        session::view_sessions_by_user(&client, &username)
+            .unwrap()
+            .sessions,
+        vec![session.clone()]
+    );
+    assert_eq!(
+        session::update_session(&client, &auth_token, &session.id, &username).unwrap(),
+        session::view_session_by_id(&client, &auth_token, &session.id).unwrap()
+    );
+
+    let link = session_sensor::create_session_sensor(&client, &auth_token, &session.id, &sensor.id)
+        .unwrap();
+    assert!(
+        session_sensor::view_all_sensor_sessions(&client, &auth_token)
+            .unwrap()
+            .links
+            .contains(&link)
+    );
+    assert_eq!(
+        session_sensor::view_sensors_by_session_id(&client, &auth_token, &session.id)
+            .unwrap()
+            .links,
+        vec![link.clone()]
+    );
+    assert_eq!(
+        session_sensor::view_session_sensor_by_sensor_id(&client, &auth_token, &sensor.id).unwrap(),
+        link
+    );
+    session_sensor::update_sensor_session(&client, &auth_token, &link.id, &session.id, &sensor.id)
+        .unwrap();
+
+    session_sensor_data::create_datapoint(&client, &auth_token, &link.id, at(0), "1").unwrap();
+    session_sensor_data::batch_create_datapoint(
+        &client,
+        &auth_token,
+        vec![
+            SessionSensorData {
+                id: link.id.clone(),
+                datetime: at(1),
+                data_blob: "2".to_string(),
+            },
+            SessionSensorData {
+                id: link.id.clone(),
+                datetime: at(2),
+                data_blob: "3".to_string(),
+            },
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        session_sensor_data::view_datapoints_by_session_id(&client, &auth_token, &session.id)
+            .unwrap()
+            .datapoints
+            .len(),
+        3
+    );
+    assert_eq!(
+        session_sensor_data::view_datapoints_by_session_sensor(&client, &auth_token, &link.id)
+            .unwrap()
+            .datapoints
+            .len(),
+        3
+    );
+    assert_eq!(
+        session_sensor_data::view_all_datapoints_by_id_datetime(
+            &client,
+            &auth_token,
+            &session.id,
+            at(0)
+        )
+        .unwrap()
+        .datapoints
+        .len(),
+        2
+    );
+    let updated =
+        session_sensor_data::update_datapoint(&client, &auth_token, &link.id, at(1), "20").unwrap();
+    assert_eq!(
+        session_sensor_data::view_datapoints_by_id_datetime(&client, &auth_token, &link.id, at(1))
+            .unwrap(),
+        updated
+    );
+    assert!(
+        session_sensor_data::view_all_datapoints(&client, &auth_token)
+            .unwrap()
+            .datapoints
+            .contains(&updated)
+    );
+    session_sensor_data::delete_datapoint(&client, &auth_token, &link.id, at(1)).unwrap();
+    assert_status(
+        session_sensor_data::view_datapoints_by_id_datetime(&client, &auth_token, &link.id, at(1)),
+        StatusCode::NOT_FOUND,
+    );
+
+    // Records still holding datapoints cannot be deleted, so delete unused ones
+    let spare_sensor = sensor::create_sensor(&client, &auth_token, "spare").unwrap();
+    let spare_session = session::create_session(&client, &username).unwrap();
+    let spare_link = session_sensor::create_session_sensor(
+        &client,
+        &auth_token,
+        &spare_session.id,
+        &spare_sensor.id,
+    )
+    .unwrap();
+    session_sensor::delete_sensor_session(&client, &auth_token, &spare_link.id).unwrap();
+    session::delete_session(&client, &auth_token, &spare_session.id).unwrap();
+    sensor::delete_sensor(&client, &auth_token, &spare_sensor.id).unwrap();
+    assert_status(
+        sensor::view_sensor_by_id(&client, &auth_token, &spare_sensor.id),
+        StatusCode::NOT_FOUND,
+    );
+}
+
+#[test]
+fn calls_from_several_threads() {
+    shared_server();
+    let client = Client::new();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            std::thread::spawn(move || {
+                let username = unique_username("blocking-threads");
+                user::create_user(&client, &username, PASSWORD).unwrap();
+                user::view_user_by_username(&client, &username).unwrap()
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+//! Records traffic with the mock server and replays it without one
+
+use client::cassette::{Cassette, MatchRules, RecordingTransport, ReplayTransport, REDACTED};
+use client::mock_server::MockServer;
+use client::requests::transport::ReqwestTransport;
+use client::{ApiClient, ApiError, Username};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const PASSWORD: &str = "hunter2";
+
+/// Path of a cassette file no other test uses
+fn cassette_path(name: &str) -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("tcp-client-cassettes-{}", std::process::id()))
+        .join(format!("{}.json", name))
+}
+
+/// Create a user, log in as it, create a sensor and list the sensors
+async fn scenario(client: &ApiClient) -> Vec<String> {
+    let username = Username::from("alice");
+    client.users().create(&username, PASSWORD).await.unwrap();
+    client.auth().login(&username, PASSWORD).await.unwrap();
+    client.sensors().create("thermometer").await.unwrap();
+    client
+        .sensors()
+        .list()
+        .await
+        .unwrap()
+        .sensors
+        .into_iter()
+        .map(|sensor| sensor.sensor_type)
+        .collect()
+}
+
+/// Record `scenario` against a fresh mock server, returning its base URL, the cassette path and
+/// the sensor types listed
+async fn record(name: &str) -> (String, PathBuf, Vec<String>) {
+    let server = MockServer::start().unwrap();
+    let recorder = Arc::new(RecordingTransport::new(ReqwestTransport::default()));
+    let client = ApiClient::builder()
+        .base_url(server.base_url())
+        .transport(recorder.clone())
+        .build();
+    let sensors = scenario(&client).await;
+
+    let path = cassette_path(name);
+    recorder.save(&path).unwrap();
+    (server.base_url().to_string(), path, sensors)
+}
+
+#[tokio::test]
+async fn recorded_traffic_is_replayed_without_the_server() {
+    let (base_url, path, recorded) = record("replay").await;
+
+    let replay = Arc::new(ReplayTransport::load(&path).unwrap());
+    let client = ApiClient::builder()
+        .base_url(base_url)
+        .transport(replay.clone())
+        .build();
+
+    assert_eq!(scenario(&client).await, recorded);
+    assert_eq!(recorded, vec!["thermometer".to_string()]);
+    replay.assert_done();
+}
+
+#[tokio::test]
+async fn secrets_are_redacted() {
+    let (_, path, _) = record("redacted").await;
+
+    let json = std::fs::read_to_string(&path).unwrap();
+    assert!(!json.contains(PASSWORD));
+    let cassette = Cassette::load(&path).unwrap();
+    let mut cookies = 0;
+    for interaction in &cassette.interactions {
+        if let Some(cookie) = &interaction.request.cookie {
+            assert_eq!(cookie, &format!("session_id={}", REDACTED));
+            cookies += 1;
+        }
+        for (name, value) in &interaction.response.headers {
+            if name == "set-cookie" {
+                assert!(value.starts_with(&format!("session_id={};", REDACTED)));
+            }
+        }
+    }
+    assert!(cookies > 0);
+}
+
+#[tokio::test]
+async fn unmatched_requests_fail() {
+    let (base_url, path, _) = record("unmatched").await;
+
+    let replay = Arc::new(ReplayTransport::load(&path).unwrap());
+    let client = ApiClient::builder()
+        .base_url(base_url)
+        .transport(replay)
+        .build();
+    // The recorded body has another username
+    let error = client
+        .users()
+        .create(&Username::from("bob"), PASSWORD)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ApiError::NotRecorded(_)), "{:?}", error);
+    assert!(error
+        .to_string()
+        .starts_with("ReplayTransport: no recorded interaction matches POST"));
+}
+
+#[tokio::test]
+async fn match_rules_are_configurable() {
+    let (base_url, path, _) = record("rules").await;
+
+    // Without comparing bodies, another username is answered with the recorded user
+    let replay = Arc::new(
+        ReplayTransport::load(&path)
+            .unwrap()
+            .rules(MatchRules::default().body(false)),
+    );
+    let client = ApiClient::builder()
+        .base_url(base_url.clone())
+        .transport(replay.clone())
+        .build();
+    let user = client
+        .users()
+        .create(&Username::from("bob"), PASSWORD)
+        .await
+        .unwrap();
+    assert_eq!(user.username, Username::from("alice"));
+    assert_eq!(replay.remaining(), 3);
+
+    // Ignored fields are left out of the comparison
+    let replay = Arc::new(
+        ReplayTransport::load(&path)
+            .unwrap()
+            .rules(MatchRules::default().ignore_field("username")),
+    );
+    let client = ApiClient::builder()
+        .base_url(base_url)
+        .transport(replay)
+        .build();
+    client
+        .users()
+        .create(&Username::from("bob"), PASSWORD)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn in_order_rejects_requests_out_of_order() {
+    let (base_url, path, _) = record("in-order").await;
+
+    let replay = Arc::new(
+        ReplayTransport::load(&path)
+            .unwrap()
+            .rules(MatchRules::default().in_order(true)),
+    );
+    let client = ApiClient::builder()
+        .base_url(base_url)
+        .transport(replay)
+        .build();
+    // The login was recorded after creating the user
+    let error = client
+        .auth()
+        .login(&Username::from("alice"), PASSWORD)
+        .await
+        .unwrap_err();
+    assert!(matches!(error, ApiError::NotRecorded(_)), "{:?}", error);
+}
@@ -0,0 +1,274 @@
+//! Runs the `pdap` binary against the mock server
+
+use client::mock_server::MockServer;
+use serde_json::Value;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Invocations of `pdap` that share a login session
+struct Pdap {
+    base_url: String,
+    session_file: PathBuf,
+}
+
+impl Pdap {
+    fn new(server: &MockServer, name: &str) -> Self {
+        let session_file =
+            std::env::temp_dir().join(format!("pdap-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&session_file);
+        Self {
+            base_url: server.base_url(),
+            session_file,
+        }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pdap"));
+        command
+            .args(["--base-url", &self.base_url])
+            .arg("--session-file")
+            .arg(&self.session_file)
+            .args(args)
+            .env_remove("API_CONFIG")
+            .env_remove("API_PROFILE")
+            .env_remove("PDAP_PASSWORD");
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// Run `pdap shell` with the given lines as input
+    fn shell(&self, lines: &[String]) -> Output {
+        let mut child = self
+            .command(&["shell"])
+            .env(
+                "XDG_CONFIG_HOME",
+                std::env::temp_dir().join("pdap-shell-config"),
+            )
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        for line in lines {
+            writeln!(stdin, "{}", line).unwrap();
+        }
+        drop(stdin);
+        child.wait_with_output().unwrap()
+    }
+
+    /// Run a command that must succeed and return its standard output
+    fn ok(&self, args: &[&str]) -> String {
+        succeeded(args, self.run(args))
+    }
+
+    /// Run a command that must succeed with `PDAP_PASSWORD` set
+    fn ok_with_password(&self, args: &[&str], password: &str) -> String {
+        let output = self
+            .command(args)
+            .env("PDAP_PASSWORD", password)
+            .output()
+            .unwrap();
+        succeeded(args, output)
+    }
+
+    /// Run a command that must succeed with `--password-stdin`, writing `password` to its input
+    fn ok_with_password_stdin(&self, args: &[&str], password: &str) -> String {
+        let mut args = args.to_vec();
+        args.push("--password-stdin");
+        let mut child = self
+            .command(&args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        writeln!(child.stdin.take().unwrap(), "{}", password).unwrap();
+        succeeded(&args, child.wait_with_output().unwrap())
+    }
+
+    /// Run a command with JSON output
+    fn json(&self, args: &[&str]) -> Value {
+        let mut args = args.to_vec();
+        args.extend(["--output", "json"]);
+        serde_json::from_str(&self.ok(&args)).unwrap()
+    }
+}
+
+/// Check that a command succeeded and return its standard output
+fn succeeded(args: &[&str], output: Output) -> String {
+    assert!(
+        output.status.success(),
+        "pdap {:?} failed: {}",
+        args,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn login_is_kept_between_invocations() {
+    let server = MockServer::start().unwrap();
+    let pdap = Pdap::new(&server, "login");
+
+    pdap.ok_with_password_stdin(&["user", "create", "alice"], "pw");
+    pdap.ok_with_password(&["login", "alice"], "pw");
+    assert!(pdap.session_file.exists());
+
+    let profile = pdap.json(&["user", "show"]);
+    assert_eq!(profile["username"], "alice");
+    pdap.ok(&["renew"]);
+
+    assert_eq!(pdap.ok(&["logout"]).trim(), "Logged out");
+    assert!(!pdap.session_file.exists());
+    let output = pdap.run(&["user", "show"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("401"));
+}
+
+#[test]
+fn records_datapoints() {
+    let server = MockServer::start().unwrap();
+    let pdap = Pdap::new(&server, "data");
+    pdap.ok_with_password(&["user", "create", "bob"], "pw");
+    pdap.ok_with_password(&["login", "bob"], "pw");
+
+    let sensor = pdap.json(&["sensor", "create", "thermometer"]);
+    let sensor_id = sensor["id"].as_str().unwrap();
+    let session = pdap.json(&["session", "create", "bob"]);
+    let session_id = session["id"].as_str().unwrap();
+    let link = pdap.json(&["link", "create", session_id, sensor_id]);
+    let link_id = link["id"].as_str().unwrap();
+
+    pdap.ok(&[
+        "data",
+        "add",
+        link_id,
+        "20.5",
+        "--at",
+        "2025-01-01T00:00:00Z",
+    ]);
+    let batch = std::env::temp_dir().join(format!("pdap-batch-{}.json", std::process::id()));
+    std::fs::write(
+        &batch,
+        serde_json::json!([
+            { "id": link_id, "datetime": "2025-01-01T00:00:01Z", "data_blob": "21.0" },
+            { "id": link_id, "datetime": "2025-01-01T00:00:02Z", "data_blob": "21.5" },
+        ])
+        .to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+        pdap.ok(&["data", "batch", batch.to_str().unwrap()]).trim(),
+        "Added 2 datapoints"
+    );
+
+    let after = pdap.json(&[
+        "data",
+        "list",
+        "--session",
+        session_id,
+        "--after",
+        "2025-01-01T00:00:00Z",
+    ]);
+    assert_eq!(after.as_array().unwrap().len(), 2);
+    let datapoint = pdap.json(&["data", "get", link_id, "2025-01-01T01:00:02+01:00"]);
+    assert_eq!(datapoint["data_blob"], "21.5");
+
+    let table = pdap.ok(&["sensor", "list"]);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines[0], "ID  TYPE");
+    assert_eq!(lines[2], format!("{}   thermometer", sensor_id));
+
+    pdap.ok(&["data", "delete", link_id, "2025-01-01T00:00:00Z"]);
+    let output = pdap.run(&["data", "get", link_id, "2025-01-01T00:00:00Z"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn rejects_malformed_arguments() {
+    let server = MockServer::start().unwrap();
+    let pdap = Pdap::new(&server, "args");
+
+    let output = pdap.run(&["data", "get", "1", "yesterday"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid timestamp"));
+
+    // Passwords are never taken from the command line
+    let output = pdap.run(&["login", "alice", "--password", "pw"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn shell_runs_commands_in_one_session() {
+    let server = MockServer::start().unwrap();
+    let pdap = Pdap::new(&server, "shell");
+    pdap.ok_with_password(&["user", "create", "carol"], "pw");
+    pdap.ok_with_password(&["login", "carol"], "pw");
+    let gps = pdap.json(&["sensor", "create", "gps"]);
+    let imu = pdap.json(&["sensor", "create", "imu"]);
+    let first = pdap.json(&["session", "create", "carol"]);
+    let second = pdap.json(&["session", "create", "carol"]);
+    let first_link = pdap.json(&[
+        "link",
+        "create",
+        first["id"].as_str().unwrap(),
+        gps["id"].as_str().unwrap(),
+    ]);
+    let second_link = pdap.json(&[
+        "link",
+        "create",
+        second["id"].as_str().unwrap(),
+        imu["id"].as_str().unwrap(),
+    ]);
+
+    let output = pdap.shell(&[
+        format!(
+            "data add {} first --at 2025-01-01T00:00:00Z",
+            first_link["id"].as_str().unwrap()
+        ),
+        format!(
+            "data add {} second --at 2025-01-01T00:00:00Z",
+            second_link["id"].as_str().unwrap()
+        ),
+        format!("use session {}", second["id"].as_str().unwrap()),
+        "output table".to_string(),
+        "data list".to_string(),
+        "sensor show unknown".to_string(),
+        "login carol --password secret".to_string(),
+        "logout".to_string(),
+        "exit".to_string(),
+        "sensor list".to_string(),
+    ]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    // JSON until `output table`, then only the datapoint of the session in use
+    assert!(stdout.contains("\"data_blob\": \"first\""));
+    let table = &stdout[stdout.find("LINK").unwrap()..];
+    assert!(table.contains("second"));
+    assert!(!table.contains("first"));
+    assert!(stderr.contains("error:") && stderr.contains("404"));
+    assert!(stdout.contains("Logged out"));
+    assert!(!pdap.session_file.exists());
+    // Nothing runs after `exit`
+    assert!(!stdout.contains("gps"));
+
+    // The password passed as an argument is neither accepted nor kept in the history
+    assert!(stderr.contains("--password"));
+    let history = std::env::temp_dir().join("pdap-shell-config/pdap/history");
+    assert!(!std::fs::read_to_string(&history)
+        .unwrap()
+        .contains("secret"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&history).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,49 @@
+//! Helpers shared by the integration tests
+
+use client::api::auth;
+use client::api::user;
+use client::mock_server::MockServer;
+use client::reqwest::Client;
+use client::{AuthToken, Username};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+
+pub const PASSWORD: &str = "hunter2";
+
+/// Get the mock server used by the free functions
+///
+/// The free functions read the base URL from `API_BASE_URL`, which is shared by the whole
+/// process, so all tests using them talk to the same server.
+pub fn shared_server() -> &'static MockServer {
+    static SERVER: OnceLock<MockServer> = OnceLock::new();
+
+    SERVER.get_or_init(|| {
+        let server = MockServer::start().expect("failed to start mock server");
+        std::env::set_var("API_BASE_URL", server.base_url());
+        server
+    })
+}
+
+/// Create a username no other test uses
+pub fn unique_username(prefix: &str) -> Username {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    Username::new(format!(
+        "{}-{}",
+        prefix,
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+/// Create a user on the shared server and log in as it
+pub async fn login_new_user(client: &Client, prefix: &str) -> (Username, AuthToken) {
+    shared_server();
+    let username = unique_username(prefix);
+    user::create_user(client, &username, PASSWORD)
+        .await
+        .expect("failed to create user");
+    let cookie = auth::user_login(client, &username, PASSWORD)
+        .await
+        .expect("failed to log in")
+        .expect("no session cookie");
+    (username, cookie.auth_token())
+}
@@ -0,0 +1,88 @@
+//! Checks that rate limited requests wait for their turn instead of failing
+
+use client::fake_transport::{Exchange, FakeTransport};
+use client::reqwest::{Method, StatusCode};
+use client::{
+    ApiClient, AuthToken, ClientConfig, GroupLimits, Priority, RateLimits, RetryPolicy, RouteGroup,
+    SensorId,
+};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const BASE_URL: &str = "http://backend.test";
+const COOKIE: &str = "session_id=token-1";
+
+fn client(fake: &Arc<FakeTransport>, rate_limits: RateLimits) -> ApiClient {
+    ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .retry_policy(RetryPolicy::none())
+        .transport(fake.clone())
+        .rate_limits(rate_limits)
+        .build()
+}
+
+fn expect_sensor(fake: &FakeTransport, id: &str) {
+    fake.expect(
+        Exchange::new(Method::GET, format!("{}/sensors/{}", BASE_URL, id))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!({ "id": id, "type": "gps" })),
+    );
+}
+
+#[tokio::test]
+async fn rate_limited_requests_are_queued() {
+    let fake = Arc::new(FakeTransport::new());
+    // The background request waits behind the interactive one made after it
+    for id in ["1", "3", "2"] {
+        expect_sensor(&fake, id);
+    }
+    let limits = RateLimits::default().set(
+        RouteGroup::Reads,
+        GroupLimits::unlimited()
+            .rate(20.0)
+            .burst(1)
+            .max_in_flight(1),
+    );
+    let client = client(&fake, limits);
+    let background = client.with_priority(Priority::Background);
+    assert_eq!(background.priority(), Priority::Background);
+
+    let ids = ["1", "2", "3"].map(SensorId::from);
+    let started = Instant::now();
+    let sensors = client.sensors();
+    let background_sensors = background.sensors();
+    let (first, second, third) = tokio::join!(
+        sensors.get(&ids[0]),
+        background_sensors.get(&ids[1]),
+        sensors.get(&ids[2]),
+    );
+
+    // The first goes at once, the others one token (50 ms) apart
+    assert!(started.elapsed() >= Duration::from_millis(90));
+    for result in [first, second, third] {
+        result.unwrap();
+    }
+    fake.assert_done();
+    assert_eq!(client.rate_limiter().queued(RouteGroup::Reads), 0);
+    assert_eq!(client.rate_limiter().in_flight(RouteGroup::Reads), 0);
+}
+
+#[tokio::test]
+async fn limits_come_from_the_config() {
+    let config = ClientConfig::loader()
+        .environment(false)
+        .toml("[limits.writes]\nmax_in_flight = 2\n\n[limits.auth]\nrate = 0.5")
+        .load()
+        .unwrap();
+    let client = ApiClient::builder()
+        .config(&config)
+        .transport(Arc::new(FakeTransport::new()))
+        .build();
+
+    let limits = client.rate_limiter().limits();
+    assert_eq!(limits.writes, GroupLimits::unlimited().max_in_flight(2));
+    assert_eq!(limits.auth, GroupLimits::unlimited().rate(0.5));
+    assert_eq!(limits.reads, GroupLimits::unlimited());
+}
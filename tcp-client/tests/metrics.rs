@@ -0,0 +1,157 @@
+//! Checks that requests are counted per route template
+
+mod common;
+
+use client::api::sensor;
+use client::api::session_sensor_data::SessionSensorData;
+use client::fake_transport::{Exchange, FakeTransport};
+use client::metrics::{self, Metrics};
+use client::reqwest::{Client, Method, StatusCode};
+use client::{ApiClient, AuthToken, RetryPolicy, SensorId, SessionSensorId, Timestamp};
+use common::login_new_user;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+const BASE_URL: &str = "http://backend.test";
+const COOKIE: &str = "session_id=token-1";
+
+fn url(path: &str) -> String {
+    format!("{}{}", BASE_URL, path)
+}
+
+fn client(fake: &Arc<FakeTransport>, metrics: &Arc<Metrics>) -> ApiClient {
+    ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .retry_policy(RetryPolicy::default().base_delay(Duration::ZERO))
+        .transport(fake.clone())
+        .metrics(metrics.clone())
+        .build()
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: u32) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap()
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_template() {
+    let fake = Arc::new(FakeTransport::new());
+    let gps = |id: &str| json!({ "id": id, "type": "gps" });
+    for id in ["3", "4"] {
+        fake.expect(
+            Exchange::new(Method::GET, url(&format!("/sensors/{}", id)))
+                .cookie(COOKIE)
+                .respond_json(StatusCode::OK, gps(id)),
+        );
+    }
+    fake.expect(
+        Exchange::new(Method::GET, url("/sensors/5"))
+            .cookie(COOKIE)
+            .respond_json(
+                StatusCode::NOT_FOUND,
+                json!({ "message": "no such sensor" }),
+            ),
+    );
+    let metrics = Arc::new(Metrics::new());
+    let client = client(&fake, &metrics);
+
+    let sensors = client.sensors();
+    sensors.get(&SensorId::from("3")).await.unwrap();
+    sensors.get(&SensorId::from("4")).await.unwrap();
+    sensors.get(&SensorId::from("5")).await.unwrap_err();
+
+    let by_id = metrics.route(&Method::GET, "/sensors/{id}").unwrap();
+    assert_eq!(by_id.count(), 3);
+    assert_eq!(by_id.requests["200"], 2);
+    assert_eq!(by_id.requests["404"], 1);
+    assert_eq!(by_id.errors, 1);
+    assert!(metrics.route(&Method::GET, "/sensors/3").is_none());
+    assert!(Arc::ptr_eq(client.metrics(), &metrics));
+}
+
+#[tokio::test]
+async fn uploads_are_told_apart_and_retries_counted() {
+    let fake = Arc::new(FakeTransport::new());
+    let datapoint = |second: u32, blob: &str| SessionSensorData {
+        id: SessionSensorId::from("7"),
+        datetime: at(second),
+        data_blob: blob.to_string(),
+    };
+    let single = json!(datapoint(0, "1"));
+    let batch = json!({ "datapoints": [datapoint(1, "2"), datapoint(2, "3")] });
+    fake.expect(
+        Exchange::new(Method::POST, url("/sessions-sensors-data"))
+            .cookie(COOKIE)
+            .json_body(single.clone())
+            .respond_json(StatusCode::CREATED, single.clone()),
+    );
+    let batch_exchange = || {
+        Exchange::new(Method::POST, url("/sessions-sensors-data/batch"))
+            .cookie(COOKIE)
+            .json_body(batch.clone())
+    };
+    fake.expect(batch_exchange().respond(StatusCode::SERVICE_UNAVAILABLE))
+        .expect(batch_exchange().respond(StatusCode::CREATED));
+    let metrics = Arc::new(Metrics::new());
+    let client = client(&fake, &metrics).with_retry_policy(
+        RetryPolicy::default()
+            .base_delay(Duration::ZERO)
+            .idempotency_keys(true),
+    );
+
+    let datapoints = client.datapoints();
+    datapoints
+        .create(&SessionSensorId::from("7"), at(0), "1")
+        .await
+        .unwrap();
+    datapoints
+        .batch_create(vec![datapoint(1, "2"), datapoint(2, "3")])
+        .await
+        .unwrap();
+    fake.assert_done();
+
+    let create = metrics
+        .route(&Method::POST, "/sessions-sensors-data")
+        .unwrap();
+    assert_eq!(create.count(), 1);
+    assert_eq!(create.request_bytes, single.to_string().len() as u64);
+    assert_eq!(create.response_bytes, single.to_string().len() as u64);
+
+    let uploads = metrics
+        .route(&Method::POST, "/sessions-sensors-data/batch")
+        .unwrap();
+    assert_eq!(uploads.count(), 2);
+    assert_eq!(uploads.errors, 1);
+    assert_eq!(uploads.request_bytes, 2 * batch.to_string().len() as u64);
+
+    let text = metrics.render_prometheus();
+    assert!(text.contains(
+        "tcp_client_requests_total{method=\"POST\",route=\"/sessions-sensors-data/batch\",\
+         status=\"503\"} 1"
+    ));
+    assert!(text.contains(
+        "tcp_client_request_duration_seconds_count{method=\"POST\",\
+         route=\"/sessions-sensors-data\"} 1"
+    ));
+}
+
+#[tokio::test]
+async fn free_functions_count_in_the_global_registry() {
+    let client = Client::new();
+    let (_username, auth_token) = login_new_user(&client, "metrics").await;
+    let before = metrics::global()
+        .route(&Method::POST, "/sensors")
+        .map_or(0, |route| route.count());
+
+    sensor::create_sensor(&client, &auth_token, "thermometer")
+        .await
+        .unwrap();
+
+    let after = metrics::global()
+        .route(&Method::POST, "/sensors")
+        .unwrap()
+        .count();
+    assert!(after > before);
+}
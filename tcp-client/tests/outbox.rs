@@ -0,0 +1,281 @@
+//! Runs `Outbox` against the mock server and an unreachable backend
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::mock_server::MockServer;
+use client::outbox::{
+    Delivery, FileOutboxStorage, MemoryOutboxStorage, Outbox, OutboxStorage, PendingRequest,
+};
+use client::reqwest::StatusCode;
+use client::{ApiClient, RetryPolicy, SessionSensorId, Timestamp, Username};
+use std::io;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Log in and create a session sensor to upload datapoints for
+async fn setup(server: &MockServer) -> (ApiClient, SessionSensorId) {
+    let client = ApiClient::builder().base_url(server.base_url()).build();
+    let username = Username::from("recorder");
+    client.users().create(&username, "pw").await.unwrap();
+    client.auth().login(&username, "pw").await.unwrap();
+    let session = client.sessions().create(&username).await.unwrap();
+    let sensor = client.sensors().create("accelerometer").await.unwrap();
+    let link = client
+        .session_sensors()
+        .create(&session.id, &sensor.id)
+        .await
+        .unwrap();
+    (client, link.id)
+}
+
+/// Create a client whose requests cannot reach any server
+fn offline_client() -> ApiClient {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    ApiClient::builder()
+        .base_url(format!("http://127.0.0.1:{}", port))
+        .retry_policy(RetryPolicy::none())
+        .build()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("outbox-{}-{}.jsonl", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: u32) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap()
+}
+
+fn reading(id: &SessionSensorId, second: u32) -> SessionSensorData {
+    SessionSensorData {
+        id: id.clone(),
+        datetime: at(second),
+        data_blob: second.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn queues_while_offline_and_replays_in_order() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let path = temp_path("replay");
+
+    let offline = Outbox::new(offline_client(), Arc::new(FileOutboxStorage::new(&path)));
+    let queued = offline.create_datapoint(&id, at(0), "0").await.unwrap();
+    assert_eq!(queued, Delivery::Queued);
+    let queued = offline
+        .batch_create_datapoint(vec![reading(&id, 1), reading(&id, 0), reading(&id, 2)])
+        .await
+        .unwrap();
+    assert_eq!(queued, Delivery::Queued);
+    // Queued requests survive a restart of the application
+    assert_eq!(FileOutboxStorage::new(&path).load().unwrap().len(), 2);
+
+    let online = Outbox::new(client.clone(), Arc::new(FileOutboxStorage::new(&path)));
+    let report = online.replay().await.unwrap();
+
+    assert_eq!(report.sent, 3);
+    assert_eq!(report.duplicates, 1);
+    assert!(report.failed.is_empty());
+    assert_eq!(report.remaining, 0);
+    assert!(online.pending().unwrap().is_empty());
+    let datetimes: Vec<Timestamp> = client
+        .datapoints()
+        .list()
+        .await
+        .unwrap()
+        .datapoints
+        .into_iter()
+        .map(|datapoint| datapoint.datetime)
+        .collect();
+    assert_eq!(datetimes, [at(0), at(1), at(2)]);
+}
+
+#[tokio::test]
+async fn reports_datapoints_that_still_fail() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    client
+        .datapoints()
+        .create(&id, at(0), "stored")
+        .await
+        .unwrap();
+    let path = temp_path("failures");
+
+    let offline = Outbox::new(offline_client(), Arc::new(FileOutboxStorage::new(&path)));
+    let missing = SessionSensorId::from("missing");
+    offline
+        .batch_create_datapoint(vec![reading(&id, 0), reading(&missing, 1), reading(&id, 2)])
+        .await
+        .unwrap();
+
+    let online = Outbox::new(client, Arc::new(FileOutboxStorage::new(&path)));
+    let report = online.replay().await.unwrap();
+
+    assert_eq!(report.sent, 1);
+    assert_eq!(report.duplicates, 1);
+    assert_eq!(report.failed.len(), 1);
+    assert_eq!(report.failed[0].datapoint.id, missing);
+    assert_eq!(report.failed[0].error.status(), Some(StatusCode::NOT_FOUND));
+    assert!(online.pending().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn keeps_datapoints_queued_while_the_backend_is_unavailable() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let client = client.with_retry_policy(RetryPolicy::none());
+    let path = temp_path("unavailable");
+
+    let offline = Outbox::new(offline_client(), Arc::new(FileOutboxStorage::new(&path)));
+    offline.create_datapoint(&id, at(0), "0").await.unwrap();
+    offline
+        .batch_create_datapoint(vec![reading(&id, 1), reading(&id, 2)])
+        .await
+        .unwrap();
+
+    // A gateway answers `503` while the backend restarts
+    server.set_unavailable(true);
+    let online = Outbox::new(client.clone(), Arc::new(FileOutboxStorage::new(&path)));
+    let report = online.replay().await.unwrap();
+    assert_eq!(report.sent, 0);
+    assert!(report.failed.is_empty());
+    assert_eq!(report.remaining, 2);
+    let delivery = online.create_datapoint(&id, at(3), "3").await.unwrap();
+    assert_eq!(delivery, Delivery::Queued);
+    assert_eq!(online.pending().unwrap().len(), 3);
+
+    server.set_unavailable(false);
+    let report = online.replay().await.unwrap();
+    assert_eq!(report.sent, 4);
+    assert_eq!(report.remaining, 0);
+    assert!(online.pending().unwrap().is_empty());
+    assert_eq!(
+        client.datapoints().list().await.unwrap().datapoints.len(),
+        4
+    );
+}
+
+#[tokio::test]
+async fn new_requests_wait_behind_queued_ones() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let path = temp_path("order");
+
+    let offline = Outbox::new(offline_client(), Arc::new(FileOutboxStorage::new(&path)));
+    offline.create_datapoint(&id, at(0), "0").await.unwrap();
+
+    let online = Outbox::new(client, Arc::new(FileOutboxStorage::new(&path)));
+    let delivery = online.create_datapoint(&id, at(1), "1").await.unwrap();
+    assert_eq!(delivery, Delivery::Queued);
+    assert!(matches!(
+        online.pending().unwrap()[1],
+        PendingRequest::CreateDatapoint { .. }
+    ));
+
+    online.replay().await.unwrap();
+    let delivery = online.create_datapoint(&id, at(2), "2").await.unwrap();
+    assert!(matches!(delivery, Delivery::Sent(_)));
+}
+
+/// Memory storage counting how often it is read
+#[derive(Debug, Default)]
+struct CountingStorage {
+    inner: MemoryOutboxStorage,
+    loads: AtomicUsize,
+}
+
+impl OutboxStorage for CountingStorage {
+    fn append(&self, request: &PendingRequest) -> io::Result<()> {
+        self.inner.append(request)
+    }
+
+    fn load(&self) -> io::Result<Vec<PendingRequest>> {
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        self.inner.load()
+    }
+
+    fn replace(&self, requests: &[PendingRequest]) -> io::Result<()> {
+        self.inner.replace(requests)
+    }
+}
+
+#[tokio::test]
+async fn sending_does_not_read_the_storage_again() {
+    let id = SessionSensorId::from("7");
+    let storage = Arc::new(CountingStorage::default());
+    let outbox = Outbox::new(offline_client(), storage.clone());
+
+    outbox.create_datapoint(&id, at(0), "0").await.unwrap();
+    outbox.create_datapoint(&id, at(1), "1").await.unwrap();
+    outbox
+        .batch_create_datapoint(vec![reading(&id, 2)])
+        .await
+        .unwrap();
+    assert_eq!(storage.loads.load(Ordering::SeqCst), 1);
+    assert_eq!(storage.inner.load().unwrap().len(), 3);
+
+    // A replay reads the queue and keeps the count up to date
+    let report = outbox.replay().await.unwrap();
+    assert_eq!(report.remaining, 3);
+    let delivery = outbox.create_datapoint(&id, at(3), "3").await.unwrap();
+    assert_eq!(delivery, Delivery::Queued);
+    assert_eq!(storage.loads.load(Ordering::SeqCst), 2);
+    assert_eq!(storage.inner.load().unwrap().len(), 4);
+}
+
+#[test]
+fn a_line_cut_short_by_a_crash_is_dropped() {
+    let id = SessionSensorId::from("7");
+    let path = temp_path("torn");
+    let storage = FileOutboxStorage::new(&path);
+    let first = PendingRequest::CreateDatapoint {
+        datapoint: reading(&id, 0),
+    };
+    storage.append(&first).unwrap();
+    // A crash in the middle of the next append
+    let mut contents = std::fs::read(&path).unwrap();
+    contents.extend_from_slice(br#"{"type":"create_datapoint","datap"#);
+    std::fs::write(&path, contents).unwrap();
+    assert_eq!(storage.load().unwrap(), std::slice::from_ref(&first));
+
+    // The next append starts on a line of its own
+    let second = PendingRequest::CreateDatapoint {
+        datapoint: reading(&id, 1),
+    };
+    storage.append(&second).unwrap();
+    assert_eq!(storage.load().unwrap(), [first, second]);
+}
+
+#[test]
+fn corrupt_lines_are_an_error() {
+    let id = SessionSensorId::from("7");
+    let path = temp_path("corrupt");
+    let storage = FileOutboxStorage::new(&path);
+    let request = PendingRequest::CreateDatapoint {
+        datapoint: reading(&id, 0),
+    };
+    storage.append(&request).unwrap();
+    let mut contents = std::fs::read(&path).unwrap();
+    contents.extend_from_slice(b"not json\n");
+    std::fs::write(&path, contents).unwrap();
+    storage.append(&request).unwrap();
+
+    let error = storage.load().unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().starts_with("line 2 of "), "{}", error);
+
+    // Replacing the content leaves a complete file behind
+    storage.replace(std::slice::from_ref(&request)).unwrap();
+    assert_eq!(storage.load().unwrap(), [request]);
+    assert!(!path.with_extension("tmp").exists());
+    storage.replace(&[]).unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,151 @@
+//! Checks the spans and body events emitted for each request
+
+use client::fake_transport::{Exchange, FakeTransport};
+use client::reqwest::{Method, StatusCode};
+use client::{ApiClient, AuthToken, RetryPolicy, SensorId, Username};
+use serde_json::json;
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+const BASE_URL: &str = "http://backend.test";
+
+/// Log output shared with the subscriber
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Output {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+/// Run `f` with a subscriber logging closed spans and events up to `level`
+async fn capture<F>(level: Level, f: F) -> String
+where
+    F: std::future::Future<Output = ()>,
+{
+    let output = Output::default();
+    let writer = output.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+    f.await;
+    output.text()
+}
+
+fn client(fake: &Arc<FakeTransport>) -> ApiClient {
+    ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .retry_policy(RetryPolicy::none())
+        .transport(fake.clone())
+        .build()
+}
+
+#[tokio::test]
+async fn spans_name_the_endpoint_and_record_the_exchange() {
+    let sensor = json!({ "id": "3", "type": "gps" });
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(
+        Exchange::new(Method::GET, format!("{}/sensors/3", BASE_URL))
+            .cookie("session_id=token-1")
+            .respond_json(StatusCode::OK, sensor.clone()),
+    );
+    let client = client(&fake);
+
+    let logs = capture(Level::INFO, async {
+        client.sensors().get(&SensorId::from("3")).await.unwrap();
+    })
+    .await;
+
+    let api = logs.lines().find(|line| line.contains("api{")).unwrap();
+    assert!(api.contains("endpoint=\"view_sensor_by_id\""), "{}", api);
+    assert!(api.contains("method=GET"), "{}", api);
+    assert!(api.contains("route=\"/sensors/{id}\""), "{}", api);
+    assert!(api.contains("status=200"), "{}", api);
+    assert!(api.contains("latency_ms="), "{}", api);
+
+    let send = logs
+        .lines()
+        .find(|line| line.contains("send_request{"))
+        .unwrap();
+    assert!(send.contains("request_bytes=0"), "{}", send);
+    let response_bytes = format!("response_bytes={}", sensor.to_string().len());
+    assert!(send.contains(&response_bytes), "{}", send);
+
+    // Bodies are only logged when asked for
+    assert!(!logs.contains("gps"), "{}", logs);
+}
+
+#[tokio::test]
+async fn failed_requests_record_the_status() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(
+        Exchange::new(Method::GET, format!("{}/users/bob", BASE_URL))
+            .cookie("session_id=token-1")
+            .respond_json(StatusCode::NOT_FOUND, json!({ "message": "no such user" })),
+    );
+    let client = client(&fake);
+
+    let logs = capture(Level::DEBUG, async {
+        client
+            .users()
+            .get(&Username::from("bob"))
+            .await
+            .unwrap_err();
+    })
+    .await;
+
+    assert!(logs.contains("request failed"), "{}", logs);
+    let api = logs.lines().rfind(|line| line.contains("api{")).unwrap();
+    assert!(
+        api.contains("endpoint=\"view_user_by_username\""),
+        "{}",
+        api
+    );
+    assert!(api.contains("status=404"), "{}", api);
+}
+
+#[tokio::test]
+async fn logged_bodies_are_redacted() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(
+        Exchange::new(Method::POST, format!("{}/users", BASE_URL))
+            .cookie("session_id=token-1")
+            .json_body(json!({ "username": "alice", "password_hash": "hunter2" }))
+            .respond_json(StatusCode::CREATED, json!({ "username": "alice" })),
+    );
+    let client = client(&fake);
+
+    let logs = capture(Level::TRACE, async {
+        client
+            .users()
+            .create(&Username::from("alice"), "hunter2")
+            .await
+            .unwrap();
+    })
+    .await;
+
+    assert!(logs.contains("request body"), "{}", logs);
+    assert!(logs.contains("response body"), "{}", logs);
+    assert!(logs.contains("<redacted>"), "{}", logs);
+    assert!(!logs.contains("hunter2"), "{}", logs);
+    assert!(!logs.contains("token-1"), "{}", logs);
+}
@@ -0,0 +1,523 @@
+//! Checks the exact requests of every `api::*` function with the scripted fake transport
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::fake_transport::{Exchange, FakeTransport};
+use client::requests::transport::{HttpRequest, Transport, TransportFuture};
+use client::reqwest::{header::SET_COOKIE, Method, StatusCode};
+use client::Username;
+use client::{
+    ApiClient, ApiError, AuthToken, CancelHandle, RetryPolicy, SensorId, SessionId,
+    SessionSensorId, Timestamp,
+};
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const BASE_URL: &str = "http://backend.test";
+const COOKIE: &str = "session_id=token-1";
+
+fn url(path: &str) -> String {
+    format!("{}{}", BASE_URL, path)
+}
+
+/// Client sending through `fake`, logged in with `token-1`
+fn client(fake: &Arc<FakeTransport>) -> ApiClient {
+    ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .transport(fake.clone())
+        .build()
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: u32) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + i64::from(second)).unwrap()
+}
+
+#[tokio::test]
+async fn users() {
+    let fake = Arc::new(FakeTransport::new());
+    let alice = json!({ "username": "alice" });
+    fake.expect(
+        Exchange::new(Method::POST, url("/users"))
+            .cookie(COOKIE)
+            .json_body(json!({ "username": "alice", "password_hash": "pw" }))
+            .respond_json(StatusCode::CREATED, alice.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/users"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([alice])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/users/profile"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, alice.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/users/a%20b%2Fc"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!({ "username": "a b/c" })),
+    )
+    .expect(
+        Exchange::new(Method::PATCH, url("/users/alice"))
+            .cookie(COOKIE)
+            .json_body(json!({ "username": "alice", "password_hash": "new" }))
+            .respond_json(StatusCode::OK, alice.clone()),
+    )
+    .expect(Exchange::new(Method::DELETE, url("/users/alice")).cookie(COOKIE));
+
+    let client = client(&fake);
+    let users = client.users();
+    let alice = Username::from("alice");
+    assert_eq!(users.create(&alice, "pw").await.unwrap().username, alice);
+    assert_eq!(users.list().await.unwrap().users.len(), 1);
+    users.profile().await.unwrap();
+    users.get(&Username::from("a b/c")).await.unwrap();
+    users.update(&alice, "new").await.unwrap();
+    users.delete(&alice).await.unwrap();
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn authentication() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(
+        Exchange::new(Method::POST, url("/authentication/login"))
+            .json_body(json!({ "username": "alice", "password_hash": "pw" }))
+            .respond(StatusCode::OK)
+            .respond_header(SET_COOKIE, "session_id=token-2; HttpOnly; Max-Age=3600"),
+    )
+    .expect(
+        Exchange::new(Method::POST, url("/authentication/renew"))
+            .cookie("session_id=token-2")
+            .respond(StatusCode::OK)
+            .respond_header(SET_COOKIE, "session_id=token-3; HttpOnly; Max-Age=3600"),
+    )
+    .expect(
+        Exchange::new(Method::POST, url("/authentication/logout"))
+            .cookie("session_id=token-3")
+            .respond(StatusCode::OK)
+            .respond_header(SET_COOKIE, "session_id=; Max-Age=0"),
+    );
+
+    let client = ApiClient::builder()
+        .base_url(BASE_URL)
+        .transport(fake.clone())
+        .build();
+    let cookie = client
+        .auth()
+        .login(&Username::from("alice"), "pw")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(cookie.auth_token(), AuthToken::from("token-2"));
+    client.auth().renew().await.unwrap();
+    assert_eq!(client.auth_token(), Some(AuthToken::from("token-3")));
+    let cookie = client.auth().logout().await.unwrap().unwrap();
+    assert!(cookie.is_deletion());
+    assert_eq!(client.auth_token(), None);
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn sensors() {
+    let fake = Arc::new(FakeTransport::new());
+    let gps = json!({ "id": "3", "type": "gps" });
+    fake.expect(
+        Exchange::new(Method::POST, url("/sensors"))
+            .cookie(COOKIE)
+            .json_body(json!({ "type": "gps" }))
+            .respond_json(StatusCode::CREATED, gps.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sensors"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([gps])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sensors/3"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, gps),
+    )
+    .expect(
+        Exchange::new(Method::PATCH, url("/sensors/3"))
+            .cookie(COOKIE)
+            .json_body(json!({ "type": "imu" }))
+            .respond_json(StatusCode::OK, json!({ "id": "3", "type": "imu" })),
+    )
+    .expect(Exchange::new(Method::DELETE, url("/sensors/3")).cookie(COOKIE));
+
+    let client = client(&fake);
+    let sensors = client.sensors();
+    let id = SensorId::from("3");
+    assert_eq!(sensors.create("gps").await.unwrap().id, id);
+    assert_eq!(sensors.list().await.unwrap().sensors.len(), 1);
+    sensors.get(&id).await.unwrap();
+    assert_eq!(sensors.update(&id, "imu").await.unwrap().sensor_type, "imu");
+    sensors.delete(&id).await.unwrap();
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn sessions() {
+    let fake = Arc::new(FakeTransport::new());
+    let session = json!({ "id": "5", "username": "alice" });
+    fake.expect(
+        Exchange::new(Method::POST, url("/sessions"))
+            .cookie(COOKIE)
+            .json_body(json!({ "username": "alice" }))
+            .respond_json(StatusCode::CREATED, session.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([session])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions/user/alice"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([session])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions/id/5"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, session),
+    )
+    .expect(
+        Exchange::new(Method::PATCH, url("/sessions/5"))
+            .cookie(COOKIE)
+            .json_body(json!({ "username": "bob" }))
+            .respond_json(StatusCode::OK, json!({ "id": "5", "username": "bob" })),
+    )
+    .expect(Exchange::new(Method::DELETE, url("/sessions/5")).cookie(COOKIE));
+
+    let client = client(&fake);
+    let sessions = client.sessions();
+    let alice = Username::from("alice");
+    let id = SessionId::from("5");
+    assert_eq!(sessions.create(&alice).await.unwrap().id, id);
+    sessions.list().await.unwrap();
+    sessions.list_by_user(&alice).await.unwrap();
+    sessions.get(&id).await.unwrap();
+    sessions.update(&id, &Username::from("bob")).await.unwrap();
+    sessions.delete(&id).await.unwrap();
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn session_sensors() {
+    let fake = Arc::new(FakeTransport::new());
+    let link = json!({ "id": "7", "session_id": "5", "sensor_id": "3" });
+    fake.expect(
+        Exchange::new(Method::POST, url("/sessions-sensors"))
+            .cookie(COOKIE)
+            .json_body(json!({ "session_id": "5", "sensor_id": "3" }))
+            .respond_json(StatusCode::CREATED, link.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([link])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors/session/5"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, json!([link])),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors/session-sensor/3"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, link.clone()),
+    )
+    .expect(
+        Exchange::new(Method::PATCH, url("/sessions-sensors/7"))
+            .cookie(COOKIE)
+            .json_body(json!({ "id": "7", "session_id": "5", "sensor_id": "3" }))
+            .respond_json(StatusCode::OK, link),
+    )
+    .expect(Exchange::new(Method::DELETE, url("/sessions-sensors/7")).cookie(COOKIE));
+
+    let client = client(&fake);
+    let links = client.session_sensors();
+    let (id, session_id, sensor_id) = (
+        SessionSensorId::from("7"),
+        SessionId::from("5"),
+        SensorId::from("3"),
+    );
+    assert_eq!(links.create(&session_id, &sensor_id).await.unwrap().id, id);
+    links.list().await.unwrap();
+    links.list_by_session(&session_id).await.unwrap();
+    links.get_by_sensor(&sensor_id).await.unwrap();
+    links.update(&id, &session_id, &sensor_id).await.unwrap();
+    links.delete(&id).await.unwrap();
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn datapoints() {
+    let fake = Arc::new(FakeTransport::new());
+    let datetime = "2025-01-01T00:00:00.000000Z";
+    let in_url = "2025-01-01T00%3A00%3A00.000000Z";
+    let datapoint = json!({ "id": "7", "datetime": datetime, "data_blob": "20.5" });
+    let list = json!([datapoint]);
+    fake.expect(
+        Exchange::new(Method::POST, url("/sessions-sensors-data"))
+            .cookie(COOKIE)
+            .json_body(datapoint.clone())
+            .respond_json(StatusCode::CREATED, datapoint.clone()),
+    )
+    .expect(
+        Exchange::new(Method::POST, url("/sessions-sensors-data/batch"))
+            .cookie(COOKIE)
+            .json_body(json!({ "datapoints": [
+                { "id": "7", "datetime": "2025-01-01T00:00:01.000000Z", "data_blob": "1" },
+            ] }))
+            .respond(StatusCode::CREATED),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors-data"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, list.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors-data/session/5"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, list.clone()),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/sessions-sensors-data/id/7"))
+            .cookie(COOKIE)
+            .respond_json(StatusCode::OK, list.clone()),
+    )
+    .expect(
+        Exchange::new(
+            Method::GET,
+            url(&format!("/sessions-sensors-data/7/{}", in_url)),
+        )
+        .cookie(COOKIE)
+        .respond_json(StatusCode::OK, datapoint.clone()),
+    )
+    .expect(
+        Exchange::new(
+            Method::GET,
+            url(&format!("/sessions-sensors-data/session/5/{}", in_url)),
+        )
+        .cookie(COOKIE)
+        .respond_json(StatusCode::OK, list),
+    )
+    .expect(
+        Exchange::new(
+            Method::PATCH,
+            url(&format!("/sessions-sensors-data/7/{}", in_url)),
+        )
+        .cookie(COOKIE)
+        .json_body(json!({ "id": "7", "datetime": datetime, "data_blob": "21" }))
+        .respond_json(StatusCode::OK, datapoint),
+    )
+    .expect(
+        Exchange::new(
+            Method::DELETE,
+            url(&format!("/sessions-sensors-data/7/{}", in_url)),
+        )
+        .cookie(COOKIE),
+    );
+
+    let client = client(&fake);
+    let datapoints = client.datapoints();
+    let (id, session_id) = (SessionSensorId::from("7"), SessionId::from("5"));
+    let created = datapoints.create(&id, at(0), "20.5").await.unwrap();
+    assert_eq!(created.datetime, at(0));
+    datapoints
+        .batch_create(vec![SessionSensorData {
+            id: id.clone(),
+            datetime: at(1),
+            data_blob: "1".to_string(),
+        }])
+        .await
+        .unwrap();
+    datapoints.list().await.unwrap();
+    datapoints.list_by_session(&session_id).await.unwrap();
+    datapoints.list_by_session_sensor(&id).await.unwrap();
+    datapoints.get(&id, at(0)).await.unwrap();
+    datapoints.list_after(&session_id, at(0)).await.unwrap();
+    datapoints.update(&id, at(0), "21").await.unwrap();
+    datapoints.delete(&id, at(0)).await.unwrap();
+    fake.assert_done();
+}
+
+#[tokio::test]
+async fn rejected_token_is_renewed_and_request_replayed() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(
+        Exchange::new(Method::GET, url("/users/profile"))
+            .cookie(COOKIE)
+            .respond(StatusCode::UNAUTHORIZED),
+    )
+    .expect(
+        Exchange::new(Method::POST, url("/authentication/renew"))
+            .cookie(COOKIE)
+            .respond(StatusCode::OK)
+            .respond_header(SET_COOKIE, "session_id=token-2"),
+    )
+    .expect(
+        Exchange::new(Method::GET, url("/users/profile"))
+            .cookie("session_id=token-2")
+            .respond_json(StatusCode::OK, json!({ "username": "alice" })),
+    );
+
+    let client = client(&fake);
+    client.users().profile().await.unwrap();
+    assert_eq!(client.auth_token(), Some(AuthToken::from("token-2")));
+    fake.assert_done();
+}
+
+/// Transport holding back the responses to the first `held` requests until all of them were
+/// sent, so that concurrent requests see the same responses
+#[derive(Debug)]
+struct HoldFirst {
+    inner: Arc<FakeTransport>,
+    held: usize,
+    sent: AtomicUsize,
+}
+
+impl Transport for HoldFirst {
+    fn send(&self, request: HttpRequest, read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        let hold = self.sent.fetch_add(1, Ordering::SeqCst) < self.held;
+        let response = self.inner.send(request, read_timeout);
+        Box::pin(async move {
+            while hold && self.sent.load(Ordering::SeqCst) < self.held {
+                tokio::task::yield_now().await;
+            }
+            response.await
+        })
+    }
+}
+
+#[tokio::test]
+async fn concurrent_rejections_share_one_renewal() {
+    let fake = Arc::new(FakeTransport::new());
+    let profile = |cookie: &str| Exchange::new(Method::GET, url("/users/profile")).cookie(cookie);
+    fake.expect(profile(COOKIE).respond(StatusCode::UNAUTHORIZED))
+        .expect(profile(COOKIE).respond(StatusCode::UNAUTHORIZED))
+        .expect(
+            Exchange::new(Method::POST, url("/authentication/renew"))
+                .cookie(COOKIE)
+                .respond(StatusCode::OK)
+                .respond_header(SET_COOKIE, "session_id=token-2"),
+        );
+    for _ in 0..2 {
+        fake.expect(
+            profile("session_id=token-2")
+                .respond_json(StatusCode::OK, json!({ "username": "alice" })),
+        );
+    }
+    let client = ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .transport(Arc::new(HoldFirst {
+            inner: fake.clone(),
+            held: 2,
+            sent: AtomicUsize::new(0),
+        }))
+        .build();
+
+    let users = client.users();
+    let (first, second) = tokio::join!(users.profile(), users.profile());
+    first.unwrap();
+    second.unwrap();
+    assert_eq!(client.auth_token(), Some(AuthToken::from("token-2")));
+    fake.assert_done();
+}
+
+/// Transport that never responds, counting the requests sent through it
+#[derive(Debug, Default)]
+struct NoResponse {
+    sent: AtomicUsize,
+}
+
+impl Transport for NoResponse {
+    fn send(&self, _request: HttpRequest, _read_timeout: Option<Duration>) -> TransportFuture<'_> {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        Box::pin(std::future::pending())
+    }
+}
+
+#[tokio::test]
+async fn requests_in_flight_can_be_cancelled() {
+    let transport = Arc::new(NoResponse::default());
+    let cancel_handle = CancelHandle::new();
+    let client = ApiClient::builder()
+        .base_url(BASE_URL)
+        .auth_token(AuthToken::from("token-1"))
+        .transport(transport.clone())
+        .build()
+        .with_cancel_handle(&cancel_handle);
+
+    let users = client.users();
+    let cancel = async {
+        while transport.sent.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        cancel_handle.cancel();
+    };
+    let (result, ()) = tokio::join!(users.profile(), cancel);
+
+    assert!(matches!(result, Err(ApiError::Cancelled)), "{:?}", result);
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 1);
+    // Later requests fail without being sent
+    let result = users.profile().await;
+    assert!(matches!(result, Err(ApiError::Cancelled)), "{:?}", result);
+    assert_eq!(transport.sent.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn server_errors_are_retried_with_the_same_idempotency_key() {
+    let fake = Arc::new(FakeTransport::new());
+    let create = || {
+        Exchange::new(Method::POST, url("/sensors"))
+            .cookie(COOKIE)
+            .json_body(json!({ "type": "gps" }))
+    };
+    fake.expect(create().respond(StatusCode::SERVICE_UNAVAILABLE))
+        .expect(create().respond_json(StatusCode::CREATED, json!({ "id": "3", "type": "gps" })));
+
+    let client = client(&fake).with_retry_policy(
+        RetryPolicy::default()
+            .base_delay(std::time::Duration::ZERO)
+            .idempotency_keys(true),
+    );
+    client.sensors().create("gps").await.unwrap();
+    fake.assert_done();
+
+    let requests = fake.requests();
+    let key = |i: usize| requests[i].headers.get("idempotency-key").cloned();
+    assert!(key(0).is_some());
+    assert_eq!(key(0), key(1));
+}
+
+#[tokio::test]
+#[should_panic(expected = "unexpected request")]
+async fn unexpected_requests_panic() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(Exchange::new(Method::GET, url("/sensors/3")).cookie(COOKIE));
+
+    let _ = client(&fake).sensors().get(&SensorId::from("4")).await;
+}
+
+#[tokio::test]
+async fn the_script_stays_readable_after_a_failed_check() {
+    let fake = Arc::new(FakeTransport::new());
+    fake.expect(Exchange::new(Method::GET, url("/sensors/3")).cookie(COOKIE));
+
+    let task_fake = fake.clone();
+    let result = tokio::spawn(async move {
+        let _ = client(&task_fake).sensors().get(&SensorId::from("4")).await;
+    })
+    .await;
+
+    assert!(result.unwrap_err().is_panic());
+    assert_eq!(fake.requests()[0].url.as_str(), url("/sensors/4"));
+    fake.assert_done();
+}
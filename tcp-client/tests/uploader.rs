@@ -0,0 +1,248 @@
+//! Runs `DatapointUploader` against the mock server
+
+use client::api::session_sensor_data::SessionSensorData;
+use client::mock_server::MockServer;
+use client::uploader::{
+    DatapointUploader, FlushReason, TrySendError, UploaderClosed, UploaderConfig,
+};
+use client::{ApiClient, SessionSensorId, Timestamp, Username};
+use futures::StreamExt;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+/// Log in and create a session sensor to upload datapoints for
+async fn setup(server: &MockServer) -> (ApiClient, SessionSensorId) {
+    let client = ApiClient::builder().base_url(server.base_url()).build();
+    let username = Username::from("recorder");
+    client.users().create(&username, "pw").await.unwrap();
+    client.auth().login(&username, "pw").await.unwrap();
+    let session = client.sessions().create(&username).await.unwrap();
+    let sensor = client.sensors().create("accelerometer").await.unwrap();
+    let link = client
+        .session_sensors()
+        .create(&session.id, &sensor.id)
+        .await
+        .unwrap();
+    (client, link.id)
+}
+
+/// Timestamp `second` seconds after the start of 2025
+fn at(second: usize) -> Timestamp {
+    Timestamp::from_unix_seconds(1_735_689_600 + second as i64).unwrap()
+}
+
+fn reading(id: &SessionSensorId, n: usize) -> SessionSensorData {
+    SessionSensorData {
+        id: id.clone(),
+        datetime: at(n),
+        data_blob: n.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn flushes_on_batch_len_and_on_shutdown() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let config = UploaderConfig {
+        max_batch_len: 3,
+        flush_interval: Duration::from_secs(60),
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, reports) = DatapointUploader::new(client.clone(), config);
+    let worker = tokio::spawn(worker.run());
+
+    for n in 0..5 {
+        uploader.send(reading(&id, n)).await.unwrap();
+    }
+    uploader.shutdown().await.unwrap();
+    worker.await.unwrap();
+
+    let reports: Vec<_> = reports.collect().await;
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[0].reason, FlushReason::BatchLen);
+    assert_eq!(reports[0].datapoints.len(), 3);
+    assert_eq!(reports[1].reason, FlushReason::Shutdown);
+    assert_eq!(reports[1].datapoints.len(), 2);
+    assert!(reports.iter().all(|report| report.result.is_ok()));
+    assert_eq!(
+        client.datapoints().list().await.unwrap().datapoints.len(),
+        5
+    );
+}
+
+#[tokio::test]
+async fn flushes_on_batch_bytes_and_interval() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    // Exactly the body of a batch with two datapoints
+    let batch = json!({ "datapoints": [reading(&id, 0), reading(&id, 1)] });
+    let config = UploaderConfig {
+        max_batch_bytes: serde_json::to_vec(&batch).unwrap().len(),
+        flush_interval: Duration::from_millis(50),
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, mut reports) = DatapointUploader::new(client, config);
+    tokio::spawn(worker.run());
+
+    for n in 0..3 {
+        uploader.send(reading(&id, n)).await.unwrap();
+    }
+
+    let first = reports.next().await.unwrap();
+    assert_eq!(first.reason, FlushReason::BatchBytes);
+    assert_eq!(first.datapoints.len(), 2);
+    let second = reports.next().await.unwrap();
+    assert_eq!(second.reason, FlushReason::Interval);
+    assert_eq!(second.datapoints.len(), 1);
+}
+
+#[tokio::test]
+async fn reports_failed_flushes() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let (mut uploader, worker, mut reports) =
+        DatapointUploader::new(client, UploaderConfig::default());
+    tokio::spawn(worker.run());
+
+    uploader.send(reading(&id, 0)).await.unwrap();
+    uploader.send(reading(&id, 0)).await.unwrap();
+    uploader.flush().await.unwrap();
+
+    let report = reports.next().await.unwrap();
+    assert_eq!(report.reason, FlushReason::Requested);
+    assert_eq!(report.datapoints.len(), 2);
+    assert_eq!(
+        report.result.unwrap_err().status(),
+        Some(client::reqwest::StatusCode::CONFLICT)
+    );
+}
+
+#[tokio::test]
+async fn flushes_on_interval() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let config = UploaderConfig {
+        flush_interval: Duration::from_millis(50),
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, mut reports) = DatapointUploader::new(client, config);
+    tokio::spawn(worker.run());
+
+    let started = Instant::now();
+    uploader.send(reading(&id, 0)).await.unwrap();
+    uploader.send(reading(&id, 1)).await.unwrap();
+
+    let report = reports.next().await.unwrap();
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(report.reason, FlushReason::Interval);
+    assert_eq!(report.datapoints.len(), 2);
+    report.result.unwrap();
+
+    // The interval starts again with the next datapoint
+    uploader.send(reading(&id, 2)).await.unwrap();
+    let report = reports.next().await.unwrap();
+    assert_eq!(report.reason, FlushReason::Interval);
+    assert_eq!(report.datapoints.len(), 1);
+}
+
+#[tokio::test]
+async fn shutdown_uploads_the_rest_and_closes_every_handle() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let config = UploaderConfig {
+        flush_interval: Duration::from_secs(60),
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, reports) = DatapointUploader::new(client.clone(), config);
+    let worker = tokio::spawn(worker.run());
+    let mut other = uploader.clone();
+
+    uploader.send(reading(&id, 0)).await.unwrap();
+    other.send(reading(&id, 1)).await.unwrap();
+    uploader.shutdown().await.unwrap();
+
+    assert_eq!(other.send(reading(&id, 2)).await, Err(UploaderClosed));
+    assert_eq!(other.flush().await, Err(UploaderClosed));
+    let error = other.try_send(reading(&id, 3)).unwrap_err();
+    assert!(error.is_closed());
+    assert_eq!(error.into_datapoint(), reading(&id, 3));
+    drop(other);
+    worker.await.unwrap();
+
+    let reports: Vec<_> = reports.collect().await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].reason, FlushReason::Shutdown);
+    assert_eq!(reports[0].datapoints, [reading(&id, 0), reading(&id, 1)]);
+    assert_eq!(
+        client.datapoints().list().await.unwrap().datapoints.len(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn worker_stops_once_every_handle_is_dropped() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let (mut uploader, worker, reports) = DatapointUploader::new(client, UploaderConfig::default());
+    let worker = tokio::spawn(worker.run());
+
+    uploader.send(reading(&id, 0)).await.unwrap();
+    drop(uploader);
+    worker.await.unwrap();
+
+    let reports: Vec<_> = reports.collect().await;
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].reason, FlushReason::Shutdown);
+    reports[0].result.as_ref().unwrap();
+}
+
+#[tokio::test]
+async fn try_send_tells_a_full_channel_from_a_closed_one() {
+    let client = ApiClient::builder().base_url("http://backend.test").build();
+    let id = SessionSensorId::from("7");
+    let config = UploaderConfig {
+        channel_capacity: 0,
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, _reports) = DatapointUploader::new(client, config);
+
+    // Every handle can queue one datapoint on top of the capacity
+    uploader.try_send(reading(&id, 0)).unwrap();
+    let error = uploader.try_send(reading(&id, 1)).unwrap_err();
+    assert!(error.is_full());
+    assert_eq!(error, TrySendError::Full(reading(&id, 1)));
+
+    drop(worker);
+    let error = uploader.try_send(reading(&id, 2)).unwrap_err();
+    assert_eq!(error, TrySendError::Closed(reading(&id, 2)));
+    assert_eq!(error.to_string(), "datapoint uploader has shut down");
+}
+
+#[tokio::test]
+async fn unread_reports_are_dropped_when_the_channel_is_full() {
+    let server = MockServer::start().unwrap();
+    let (client, id) = setup(&server).await;
+    let config = UploaderConfig {
+        report_capacity: 1,
+        ..UploaderConfig::default()
+    };
+    let (mut uploader, worker, reports) = DatapointUploader::new(client.clone(), config);
+    let worker = tokio::spawn(worker.run());
+
+    for n in 0..4 {
+        uploader.send(reading(&id, n)).await.unwrap();
+        uploader.flush().await.unwrap();
+    }
+    uploader.shutdown().await.unwrap();
+    worker.await.unwrap();
+
+    // One report fits the channel and one more the worker's own slot
+    let reports: Vec<_> = reports.collect().await;
+    let datapoints: Vec<_> = reports.iter().map(|report| &report.datapoints[0]).collect();
+    assert_eq!(datapoints, [&reading(&id, 0), &reading(&id, 1)]);
+    // Dropping reports does not affect the uploads
+    assert_eq!(
+        client.datapoints().list().await.unwrap().datapoints.len(),
+        4
+    );
+}